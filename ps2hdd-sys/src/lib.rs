@@ -128,7 +128,7 @@ mod tests {
                     .expect(
                         "util_iomanx_dopen_dread: could not convert the partition name to a String",
                     );
-                println!("util_iomanx_dopen_dread: result: {} {}", result, name);
+                log::debug!("util_iomanx_dopen_dread: result: {} {}", result, name);
             }
 
             result > 0
@@ -0,0 +1,489 @@
+//! Direct access to the on-disk APA partition map structures, without going
+//! through the drivers (or their global state) in `ps2hdd-sys`
+//!
+//! Mostly useful for debugging, and comparing against other tools.
+
+use std::convert::{TryFrom, TryInto};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::Error;
+use crate::fs::DateTime;
+use crate::partition_kind::{PartitionFlags, PartitionKind};
+
+/// The magic number found in every APA partition header; `"APA\0"`
+pub const APA_MAGIC: u32 = 0x0041_5041;
+
+/// The size of a single sector, in bytes
+///
+/// All partition sizes and locations are measured in these sectors. The ATA
+/// layer beneath the drivers always addresses the disk in 512-byte logical
+/// sectors, as the PlayStation®2 does, regardless of the disk's physical
+/// sector size, so this is fixed rather than queried from the disk.
+pub const SECTOR_SIZE: u64 = 512;
+
+/// The size of an APA partition header, in bytes; the header occupies the
+/// first two sectors of every partition
+pub const HEADER_SIZE: usize = 1024;
+
+/// Offset of the magic number within an APA partition header
+const APA_MAGIC_OFFSET: u64 = 4;
+
+/// The offset of the partition name within an APA partition header
+const APA_ID_OFFSET: usize = 0x10;
+
+/// The maximum length of a partition name, including its null terminator
+pub(crate) const APA_ID_MAX: usize = 32;
+
+/// The offset of the sub-partition table within an APA partition header
+const APA_SUBS_OFFSET: usize = 0x200;
+
+/// The maximum number of sub-partitions a partition may have
+const APA_SUB_MAX: usize = 64;
+
+/// The sector of the APA journal, which records the partition headers about
+/// to be overwritten, so that interrupted changes can be reverted
+const APA_JOURNAL_SECTOR: u64 = 8;
+
+/// The first sector of the copies of partition headers held by the journal
+const APA_JOURNAL_HEADERS_SECTOR: u64 = 10;
+
+/// The magic number found at the start of a pending journal; `"APAL"`
+const APA_JOURNAL_MAGIC: u32 = 0x4c41_5041;
+
+/// The maximum number of headers the journal can hold
+const APA_JOURNAL_MAX: usize = 126;
+
+/// What to do with the partition headers in a pending APA journal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JournalAction {
+    /// Write the journaled headers back to the disk, reverting the partition
+    /// map to how it was before the interrupted change, just as the APA
+    /// driver does when a disk is opened
+    Replay,
+    /// Throw the journaled headers away, keeping the partition map as it was
+    /// left by the interrupted change
+    Discard,
+}
+
+/// The outcome of repairing an APA journal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JournalRepair {
+    /// There was no pending journal, so nothing was done
+    Clean,
+    /// The given number of journaled headers were written back to the disk
+    Replayed(usize),
+    /// The given number of journaled headers were thrown away
+    Discarded(usize),
+}
+
+/// A problem with where a partition lies on the disk.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartitionConflict {
+    /// Two partitions' sectors overlap
+    Overlapping(ApaHeader, ApaHeader),
+    /// A partition extends past the end of the disk
+    OutOfBounds(ApaHeader),
+}
+
+/// The parsed fields of an APA partition header.
+///
+/// The password fields, padding, and the `__mbr` partition's boot
+/// information are not included; consult the raw header for those.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApaHeader {
+    /// The checksum recorded in the header
+    pub checksum: u32,
+    /// The header's magic number, which should always be [`APA_MAGIC`]
+    ///
+    /// [`APA_MAGIC`]: constant.APA_MAGIC.html
+    pub magic: u32,
+    /// The start sector of the next partition in the partition map
+    pub next: u32,
+    /// The start sector of the previous partition in the partition map
+    pub prev: u32,
+    /// The partition's name
+    pub id: String,
+    /// The sector the partition, and this header, starts at
+    pub start: u32,
+    /// The length of the partition, in sectors
+    pub length: u32,
+    /// The partition's type code
+    pub type_code: u16,
+    /// The partition's flags
+    pub flags: PartitionFlags,
+    /// The number of sub-partitions linked to this partition
+    pub nsub: u32,
+    /// When the partition was created
+    pub created: DateTime,
+    /// For sub-partitions, the start sector of the main partition
+    pub main: u32,
+    /// For sub-partitions, the index of this sub-partition
+    pub number: u32,
+    pub modver: u32,
+    /// For main partitions, the start sector and length of each of their
+    /// sub-partitions
+    pub subs: Vec<(u32, u32)>,
+}
+
+impl ApaHeader {
+    /// Parses the fields of a raw partition header.
+    pub fn parse(bytes: &[u8; HEADER_SIZE]) -> Self {
+        let mut created = [0u8; 8];
+        created.copy_from_slice(&bytes[0x50..0x58]);
+
+        Self {
+            checksum: read_u32(bytes, 0x00),
+            magic: read_u32(bytes, 0x04),
+            next: read_u32(bytes, 0x08),
+            prev: read_u32(bytes, 0x0c),
+            id: String::from_utf8_lossy(raw_id(bytes)).into_owned(),
+            start: read_u32(bytes, 0x40),
+            length: read_u32(bytes, 0x44),
+            type_code: u16::from_le_bytes([bytes[0x48], bytes[0x49]]),
+            flags: PartitionFlags::from_bits(u16::from_le_bytes([bytes[0x4a], bytes[0x4b]]) as u32),
+            nsub: read_u32(bytes, 0x4c),
+            created: DateTime::from(created),
+            main: read_u32(bytes, 0x58),
+            number: read_u32(bytes, 0x5c),
+            modver: read_u32(bytes, 0x60),
+            subs: (0..(read_u32(bytes, 0x4c) as usize).min(APA_SUB_MAX))
+                .map(|index| {
+                    let offset = APA_SUBS_OFFSET + index * 8;
+                    (read_u32(bytes, offset), read_u32(bytes, offset + 4))
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the kind of partition this is, or `None` for free space.
+    pub fn kind(&self) -> Option<PartitionKind> {
+        PartitionKind::try_from(self.type_code as u32).ok()
+    }
+
+    /// Checks that the header's magic, checksum and flags are consistent.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error describing the first problem found
+    /// with the header.
+    pub fn validate(&self, bytes: &[u8; HEADER_SIZE]) -> Result<(), Error> {
+        if self.magic != APA_MAGIC {
+            return Err(Error::Other(format!(
+                "partition header at sector {} has an invalid magic",
+                self.start
+            )));
+        }
+
+        let expected_checksum = checksum(bytes);
+
+        if self.checksum != expected_checksum {
+            return Err(Error::Other(format!(
+                "partition header at sector {} has checksum {:#010x}, expected {:#010x}",
+                self.start, self.checksum, expected_checksum
+            )));
+        }
+
+        if self.flags.is_sub() && self.nsub != 0 {
+            return Err(Error::Other(format!(
+                "sub-partition header at sector {} has its own sub-partitions",
+                self.start
+            )));
+        }
+
+        if self.flags.is_main() && self.main != 0 {
+            return Err(Error::Other(format!(
+                "main partition header at sector {} refers to a main partition",
+                self.start
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(
+        bytes[offset..offset + 4]
+            .try_into()
+            .expect("slice with incorrect length"),
+    )
+}
+
+/// Calculates the checksum of a raw partition header, which is the sum of
+/// every 32-bit word in the header, save for the checksum itself.
+pub fn checksum(bytes: &[u8; HEADER_SIZE]) -> u32 {
+    bytes[4..]
+        .chunks_exact(4)
+        .fold(0u32, |sum, word| sum.wrapping_add(read_u32(word, 0)))
+}
+
+/// Sets the start sectors of the next and previous partitions a raw partition
+/// header links to, updating its checksum to match.
+pub fn set_links(bytes: &mut [u8; HEADER_SIZE], next: u32, prev: u32) {
+    bytes[0x08..0x0c].copy_from_slice(&next.to_le_bytes());
+    bytes[0x0c..0x10].copy_from_slice(&prev.to_le_bytes());
+
+    let checksum = checksum(bytes);
+    bytes[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Returns the size of a disk, in sectors.
+///
+/// The size is found by seeking to the end of the disk, rather than from its
+/// metadata, as block devices report their length as zero.
+pub fn disk_sectors(file: &mut std::fs::File) -> Result<u64, Error> {
+    match file.seek(SeekFrom::End(0)) {
+        Ok(length) => Ok(length / SECTOR_SIZE),
+        Err(error) => Err(Error::Other(format!(
+            "couldn't find the size of the disk: {}",
+            error
+        ))),
+    }
+}
+
+/// Reads the raw partition header at the given sector.
+pub fn read_header(file: &mut std::fs::File, sector: u64) -> Result<[u8; HEADER_SIZE], Error> {
+    let mut bytes = [0u8; HEADER_SIZE];
+
+    if let Err(error) = file.seek(SeekFrom::Start(sector * SECTOR_SIZE)) {
+        return Err(Error::Other(format!(
+            "couldn't seek to sector {}: {}",
+            sector, error
+        )));
+    }
+
+    if let Err(error) = file.read_exact(&mut bytes) {
+        return Err(Error::Other(format!(
+            "couldn't read partition header at sector {}: {}",
+            sector, error
+        )));
+    }
+
+    Ok(bytes)
+}
+
+/// Writes a raw partition header at the given sector.
+pub fn write_header(
+    file: &mut std::fs::File,
+    sector: u64,
+    bytes: &[u8; HEADER_SIZE],
+) -> Result<(), Error> {
+    if let Err(error) = file.seek(SeekFrom::Start(sector * SECTOR_SIZE)) {
+        return Err(Error::Other(format!(
+            "couldn't seek to sector {}: {}",
+            sector, error
+        )));
+    }
+
+    if let Err(error) = file.write_all(bytes) {
+        return Err(Error::Other(format!(
+            "couldn't write partition header at sector {}: {}",
+            sector, error
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the partition's name exactly as stored in a raw partition header,
+/// up to its null terminator. Unlike [`ApaHeader::id`], this isn't
+/// necessarily valid UTF-8.
+///
+/// [`ApaHeader::id`]: struct.ApaHeader.html#structfield.id
+pub fn raw_id(bytes: &[u8; HEADER_SIZE]) -> &[u8] {
+    let id = &bytes[APA_ID_OFFSET..APA_ID_OFFSET + APA_ID_MAX];
+    let id_length = id.iter().position(|&c| c == 0x00).unwrap_or(APA_ID_MAX);
+
+    &id[..id_length]
+}
+
+/// Reads every raw partition header in the partition map, in the order they
+/// are linked together, starting with the `__mbr` partition at sector 0.
+pub fn read_partition_map(file: &mut std::fs::File) -> Result<Vec<[u8; HEADER_SIZE]>, Error> {
+    let mut headers: Vec<[u8; HEADER_SIZE]> = Vec::new();
+    let mut sector = 0;
+
+    loop {
+        let bytes = read_header(file, sector)?;
+        let header = ApaHeader::parse(&bytes);
+
+        if header.magic != APA_MAGIC {
+            return Err(Error::Other(format!(
+                "no partition header at sector {}",
+                sector
+            )));
+        }
+
+        headers.push(bytes);
+
+        // The last partition links back around to the `__mbr` partition
+        if header.next == 0 {
+            break;
+        }
+
+        if headers
+            .iter()
+            .any(|bytes| ApaHeader::parse(bytes).start == header.next)
+        {
+            return Err(Error::Other(format!(
+                "partition map loops back to sector {}",
+                header.next
+            )));
+        }
+
+        sector = header.next as u64;
+    }
+
+    Ok(headers)
+}
+
+/// Checks the given partition headers for partitions which overlap each
+/// other, or extend past the end of a disk of `disk_sectors` sectors.
+pub fn find_conflicts(headers: &[ApaHeader], disk_sectors: u64) -> Vec<PartitionConflict> {
+    let end = |header: &ApaHeader| header.start as u64 + header.length as u64;
+    let mut conflicts = Vec::new();
+
+    for (index, header) in headers.iter().enumerate() {
+        if end(header) > disk_sectors {
+            conflicts.push(PartitionConflict::OutOfBounds(header.clone()));
+        }
+
+        for other in headers.iter().skip(index + 1) {
+            if (header.start as u64) < end(other) && (other.start as u64) < end(header) {
+                conflicts.push(PartitionConflict::Overlapping(
+                    header.clone(),
+                    other.clone(),
+                ));
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Reads the partition headers held by a pending journal, along with the
+/// sectors they belong at. If there is no pending journal, nothing is
+/// returned.
+pub fn read_journal(file: &mut std::fs::File) -> Result<Vec<(u64, [u8; HEADER_SIZE])>, Error> {
+    let mut journal = [0u8; SECTOR_SIZE as usize];
+
+    if let Err(error) = file.seek(SeekFrom::Start(APA_JOURNAL_SECTOR * SECTOR_SIZE)) {
+        return Err(Error::Other(format!("couldn't seek to journal: {}", error)));
+    }
+
+    if let Err(error) = file.read_exact(&mut journal) {
+        return Err(Error::Other(format!("couldn't read journal: {}", error)));
+    }
+
+    if read_u32(&journal, 0) != APA_JOURNAL_MAGIC {
+        return Ok(Vec::new());
+    }
+
+    let count = read_u32(&journal, 4) as usize;
+
+    if count > APA_JOURNAL_MAX {
+        return Err(Error::Other(format!(
+            "journal has too many entries ({})",
+            count
+        )));
+    }
+
+    let header_sectors = HEADER_SIZE as u64 / SECTOR_SIZE;
+    let mut entries = Vec::with_capacity(count);
+
+    for index in 0..count {
+        let sector = read_u32(&journal, 8 + index * 4) as u64;
+        let bytes = read_header(
+            file,
+            APA_JOURNAL_HEADERS_SECTOR + index as u64 * header_sectors,
+        )?;
+
+        if ApaHeader::parse(&bytes).magic != APA_MAGIC {
+            return Err(Error::Other(format!(
+                "journal entry {} for sector {} is not a partition header",
+                index, sector
+            )));
+        }
+
+        entries.push((sector, bytes));
+    }
+
+    Ok(entries)
+}
+
+/// Marks the journal as having nothing pending.
+pub fn clear_journal(file: &mut std::fs::File) -> Result<(), Error> {
+    if let Err(error) = file.seek(SeekFrom::Start(APA_JOURNAL_SECTOR * SECTOR_SIZE)) {
+        return Err(Error::Other(format!("couldn't seek to journal: {}", error)));
+    }
+
+    if let Err(error) = file.write_all(&[0u8; SECTOR_SIZE as usize]) {
+        return Err(Error::Other(format!("couldn't clear journal: {}", error)));
+    }
+
+    Ok(())
+}
+
+/// Reads the magic number of the partition header at the start of the disk
+/// at `path`, and checks whether it matches the APA magic.
+///
+/// Any error reading the file (including it being too short) is treated as
+/// the file not being an APA disk.
+pub fn has_apa_magic<P: AsRef<Path>>(path: P) -> bool {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+
+    if file.seek(SeekFrom::Start(APA_MAGIC_OFFSET)).is_err() {
+        return false;
+    }
+
+    let mut magic = [0u8; 4];
+
+    match file.read_exact(&mut magic) {
+        Ok(()) => u32::from_le_bytes(magic) == APA_MAGIC,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_header(id: &str, start: u32, length: u32) -> ApaHeader {
+        let mut bytes = [0u8; HEADER_SIZE];
+
+        bytes[0x04..0x08].copy_from_slice(&APA_MAGIC.to_le_bytes());
+        bytes[APA_ID_OFFSET..APA_ID_OFFSET + id.len()].copy_from_slice(id.as_bytes());
+        bytes[0x40..0x44].copy_from_slice(&start.to_le_bytes());
+        bytes[0x44..0x48].copy_from_slice(&length.to_le_bytes());
+
+        ApaHeader::parse(&bytes)
+    }
+
+    #[test]
+    fn finds_conflicts() {
+        let mbr = make_header("__mbr", 0, 0x40000);
+        let a = make_header("A", 0x40000, 0x40000);
+        let b = make_header("B", 0x80000, 0x40000);
+
+        assert_eq!(
+            find_conflicts(&[mbr.clone(), a.clone(), b.clone()], 0xc0000),
+            vec![]
+        );
+
+        let long_a = make_header("A", 0x40000, 0x80000);
+
+        assert_eq!(
+            find_conflicts(&[mbr.clone(), long_a.clone(), b.clone()], 0xc0000),
+            vec![PartitionConflict::Overlapping(long_a, b.clone())]
+        );
+
+        assert_eq!(
+            find_conflicts(&[mbr, a, b.clone()], 0xa0000),
+            vec![PartitionConflict::OutOfBounds(b)]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Checksums of disk images, for recording and later verifying their integrity
+
+use std::io::Read;
+
+/// The size of the buffer used when streaming an image into a digest
+const DIGEST_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// The algorithms which [`PS2HDD::image_digest`] can checksum a disk with.
+///
+/// [`PS2HDD::image_digest`]: ../struct.PS2HDD.html#method.image_digest
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DigestAlgorithm {
+    /// CRC-32, as used by ZIP and most disc image tools; quick to compute, but
+    /// only suitable for detecting accidental corruption
+    Crc32,
+    /// SHA-256
+    Sha256,
+}
+
+/// Which part of the disk [`PS2HDD::image_digest`] checksums.
+///
+/// [`PS2HDD::image_digest`]: ../struct.PS2HDD.html#method.image_digest
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DigestRegion {
+    /// Every byte of the disk
+    WholeImage,
+    /// Only the bytes up to the end of the last partition, as given by
+    /// [`PS2HDD::logical_size`](../struct.PS2HDD.html#method.logical_size)
+    LogicalSize,
+}
+
+/// Hashes up to `limit` bytes of `reader`, returning the digest as a
+/// lowercase hexadecimal string.
+pub(crate) fn digest_reader<R: Read>(
+    reader: R,
+    algorithm: DigestAlgorithm,
+    limit: u64,
+) -> std::io::Result<String> {
+    let mut reader = reader.take(limit);
+    let mut buffer = vec![0u8; DIGEST_BUFFER_SIZE];
+
+    let mut update = |data: &mut dyn FnMut(&[u8])| -> std::io::Result<()> {
+        loop {
+            let length = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(length) => length,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+
+            data(&buffer[..length]);
+        }
+    };
+
+    match algorithm {
+        DigestAlgorithm::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            update(&mut |data| hasher.update(data))?;
+
+            Ok(format!("{:08x}", hasher.finalize()))
+        }
+        DigestAlgorithm::Sha256 => {
+            use sha2::Digest;
+
+            let mut hasher = sha2::Sha256::new();
+            update(&mut |data| hasher.update(data))?;
+
+            Ok(hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_readers() {
+        assert_eq!(
+            digest_reader(&b"123456789"[..], DigestAlgorithm::Crc32, u64::MAX).unwrap(),
+            "cbf43926"
+        );
+
+        assert_eq!(
+            digest_reader(&b"abc"[..], DigestAlgorithm::Sha256, u64::MAX).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        assert_eq!(
+            digest_reader(&b"abcdef"[..], DigestAlgorithm::Sha256, 3).unwrap(),
+            digest_reader(&b"abc"[..], DigestAlgorithm::Sha256, u64::MAX).unwrap(),
+            "limit wasn't respected"
+        );
+    }
+}
@@ -1,17 +1,74 @@
 //! PlayStation®2 file system driver-specific functionality
 
-use std::io;
 use std::path::Path;
 
+use log::{debug, warn};
+
+use crate::error::Error;
 use crate::ffi_utils::{ok_on_nonnegative_or_strerror, ok_on_zero_or_strerror};
-use crate::fs::DirEntry;
+use crate::fs::{
+    CopyOptions, DateTime, DirEntry, DirSummary, File, FileType, Metadata, WriteOptions,
+    PRIVATE_DATA_SIZE,
+};
+use crate::ioctl::{ioctl2, Ioctl2Command};
+
+/// The size of the buffer used when reading whole files, and by buffered
+/// readers and writers
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Joins `path` onto the root of a device, and converts it into a C String
+/// suitable for passing to the functions of `ps2hdd-sys`.
+///
+/// Leading, trailing and repeated slashes are dropped, so `testdir`,
+/// `/testdir` and `testdir/` all refer to the same place.
+pub(crate) fn device_path(device_root: &str, path: &Path) -> Result<std::ffi::CString, Error> {
+    let components: Vec<_> = path
+        .components()
+        .filter_map(|component| match component {
+            std::path::Component::Prefix(_) | std::path::Component::RootDir => None,
+            component => Some(component.as_os_str().to_string_lossy()),
+        })
+        .collect();
+
+    match std::ffi::CString::new(format!("{}/{}", device_root, components.join("/"))) {
+        Ok(path) => Ok(path),
+        Err(error) => Err(Error::NulByte {
+            context: "couldn't convert path",
+            error,
+        }),
+    }
+}
 
-fn create_dir_impl(device_root: &str, path: &Path) -> Result<(), String> {
-    let path = match std::ffi::CString::new(format!("{}/{}", device_root, path.display())) {
+/// Checks whether `path` refers to a directory on the device, as opposed to
+/// on the host.
+fn is_dir_impl(device_root: &str, path: &Path) -> bool {
+    let path = match device_path(device_root, path) {
         Ok(path) => path,
-        Err(error) => return Err(format!("couldn't convert path: {}", error)),
+        Err(_) => return false,
     };
 
+    let mut stat: ps2hdd_sys::iox_stat_t = unsafe { std::mem::zeroed() };
+
+    if unsafe { ps2hdd_sys::iomanx_getstat(path.as_ptr(), &mut stat) } < 0 {
+        return false;
+    }
+
+    stat.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR
+}
+
+/// Fails with an "Is a directory" error if `path` is a directory, so that
+/// opening one as a file fails clearly, rather than however the driver does.
+fn ensure_not_dir(device_root: &str, path: &Path) -> Result<(), Error> {
+    if is_dir_impl(device_root, path) {
+        return Err(Error::Other(format!("{}: Is a directory", path.display())));
+    }
+
+    Ok(())
+}
+
+fn create_dir_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    let path = device_path(device_root, path)?;
+
     ok_on_nonnegative_or_strerror(
         unsafe { ps2hdd_sys::iomanx_mkdir(path.as_ptr(), 0o777) },
         "failed to create directory",
@@ -20,203 +77,4714 @@ fn create_dir_impl(device_root: &str, path: &Path) -> Result<(), String> {
     Ok(())
 }
 
-fn create_dir_all_impl(device_root: &str, path: &Path) -> Result<(), String> {
+fn create_dir_all_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    create_dir_all_reported_impl(device_root, path, &mut Vec::new())
+}
+
+/// Creates any missing parent directories of `path`, so that it can then be
+/// created or renamed into place.
+fn create_parents_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        if parent
+            .components()
+            .any(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            create_dir_all_impl(device_root, parent)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates `path` and any missing parents, adding each directory actually
+/// created to `created`, parents first.
+fn create_dir_all_reported_impl(
+    device_root: &str,
+    path: &Path,
+    created: &mut Vec<std::path::PathBuf>,
+) -> Result<(), Error> {
     match create_dir_impl(device_root, path) {
-        Ok(()) => return Ok(()),
-        Err(ref e) if e == "failed to create directory: -2, No such file or directory" => {}
-        Err(_) if path.is_dir() => return Ok(()),
+        Ok(()) => {
+            created.push(path.to_path_buf());
+            return Ok(());
+        }
+        Err(Error::Io { errno, .. }) if errno == -libc::ENOENT => {}
+        Err(_) if is_dir_impl(device_root, path) => return Ok(()),
         Err(e) => return Err(e),
     }
 
     match path.parent() {
-        Some(p) => create_dir_all_impl(device_root, p)?,
-        None => return Err("failed to create whole tree".to_string()),
+        Some(p) => create_dir_all_reported_impl(device_root, p, created)?,
+        None => return Err(Error::Other("failed to create whole tree".to_string())),
     }
 
     match create_dir_impl(device_root, path) {
-        Ok(()) => Ok(()),
-        Err(_) if path.is_dir() => Ok(()),
+        Ok(()) => {
+            created.push(path.to_path_buf());
+            Ok(())
+        }
+        Err(_) if is_dir_impl(device_root, path) => Ok(()),
         Err(e) => Err(e),
     }
 }
 
-pub trait Driver {
-    /// Retrieves the root of the given device's file system
-    fn get_device_root(&self) -> &str;
+fn read_file_impl(device_root: &str, path: &Path) -> Result<Vec<u8>, Error> {
+    ensure_not_dir(device_root, path)?;
 
-    /// Creates a new, empty directory at the provided path
-    fn create_dir<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        create_dir_impl(self.get_device_root(), path.as_ref())
+    let c_path = device_path(device_root, path)?;
+
+    let handle = ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_open(c_path.as_ptr(), ps2hdd_sys::IOMANX_O_RDONLY as i32) },
+        "failed to open file",
+    )
+    .map_err(|error| error.or_not_found(path.display(), "file or directory"))?;
+
+    let mut contents = Vec::new();
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+
+    loop {
+        let result = unsafe {
+            ps2hdd_sys::iomanx_read(
+                handle,
+                buffer.as_mut_ptr() as *mut core::ffi::c_void,
+                buffer.len() as i32,
+            )
+        };
+
+        if result < 0 {
+            if unsafe { ps2hdd_sys::iomanx_close(handle) } != 0 {
+                warn!("Failed to close {:?} after a failed read", path);
+            }
+
+            ok_on_nonnegative_or_strerror(result, "failed to read file")?;
+        }
+
+        if result == 0 {
+            break;
+        }
+
+        contents.extend_from_slice(&buffer[..result as usize]);
+    }
+
+    ok_on_zero_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_close(handle) },
+        "failed to close file",
+    )?;
+
+    Ok(contents)
+}
+
+/// Writes `contents` to a file, creating it if it does not exist. If
+/// `truncate` is `false`, an existing file is overwritten from the start,
+/// without first freeing its blocks, so it must be no longer than `contents`.
+fn write_file_impl(
+    device_root: &str,
+    path: &Path,
+    contents: &[u8],
+    truncate: bool,
+) -> Result<(), Error> {
+    ensure_not_dir(device_root, path)?;
+
+    let path = device_path(device_root, path)?;
+
+    let mut open_flags = ps2hdd_sys::IOMANX_O_WRONLY as i32 | ps2hdd_sys::IOMANX_O_CREAT as i32;
+
+    if truncate {
+        open_flags |= ps2hdd_sys::IOMANX_O_TRUNC as i32;
+    }
+
+    let handle = ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_open(path.as_ptr(), open_flags, 0o644) },
+        "failed to open file",
+    )?;
+
+    let mut remaining = contents;
+
+    while !remaining.is_empty() {
+        let length = remaining.len().min(i32::MAX as usize);
+
+        let result = unsafe {
+            ps2hdd_sys::iomanx_write(
+                handle,
+                remaining.as_ptr() as *mut core::ffi::c_void,
+                length as i32,
+            )
+        };
+
+        if result <= 0 {
+            if unsafe { ps2hdd_sys::iomanx_close(handle) } != 0 {
+                warn!("Failed to close {:?} after a failed write", path);
+            }
+
+            if result == 0 {
+                return Err(Error::Other(format!(
+                    "failed to write file: only wrote {} of {} bytes",
+                    contents.len() - remaining.len(),
+                    contents.len()
+                )));
+            }
+
+            ok_on_nonnegative_or_strerror(result, "failed to write file")?;
+        }
+
+        remaining = &remaining[result as usize..];
+    }
+
+    ok_on_zero_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_close(handle) },
+        "failed to close file",
+    )?;
+
+    Ok(())
+}
+
+/// Writes `contents` to a file as [`Driver::write_file_with`] does, other
+/// than keeping the file's permissions.
+///
+/// [`Driver::write_file_with`]: trait.Driver.html#method.write_file_with
+fn write_file_with_impl(
+    device_root: &str,
+    path: &Path,
+    contents: &[u8],
+    options: &WriteOptions,
+) -> Result<(), Error> {
+    if !options.is_atomic() {
+        // Files can't be shortened without truncating them, so only
+        // rewrite in place when the contents are at least as long
+        let in_place = options.is_in_place()
+            && match stat_impl(device_root, path) {
+                Ok(stat) => {
+                    (((stat.hisize as u64) << 32) | stat.size as u64) <= contents.len() as u64
+                }
+                Err(_) => false,
+            };
+
+        return write_file_impl(device_root, path, contents, !in_place);
+    }
+
+    let temp_path = atomic_temp_path(path)?;
+
+    debug!(
+        "Writing {} atomically via {}",
+        path.display(),
+        temp_path.display()
+    );
+
+    // Until it's renamed, the temporary file is the only thing changed, so
+    // it can be removed to leave things as they were
+    if let Err(message) = write_file_impl(device_root, &temp_path, contents, true)
+        .and_then(|()| sync_impl(device_root))
+    {
+        if remove_file_impl(device_root, &temp_path).is_err() {
+            warn!(
+                "Failed to remove {} after a failed write",
+                temp_path.display()
+            );
+        }
+
+        return Err(message);
+    }
+
+    rename_impl(device_root, &temp_path, path)
+}
+
+/// Reads back `path` and compares it against `expected`, returning an error
+/// describing the first difference found.
+fn verify_contents_impl(device_root: &str, path: &Path, expected: &[u8]) -> Result<(), Error> {
+    let actual = read_file_impl(device_root, path)?;
+
+    if actual.len() != expected.len() {
+        return Err(Error::Other(format!(
+            "{}: verification failed; read back {} bytes, expected {}",
+            path.display(),
+            actual.len(),
+            expected.len()
+        )));
+    }
+
+    match actual.iter().zip(expected).position(|(a, b)| a != b) {
+        Some(offset) => Err(Error::Other(format!(
+            "{}: verification failed; contents differ at byte {}",
+            path.display(),
+            offset
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn remove_dir_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    let c_path = device_path(device_root, path)?;
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_rmdir(c_path.as_ptr()) },
+        "failed to delete directory",
+    )
+    .map_err(|error| error.or_not_found(path.display(), "directory"))?;
+
+    Ok(())
+}
+
+fn remove_file_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    // Symlinks to directories are removed like any other symlink
+    if read_link_impl(device_root, path).is_err() {
+        ensure_not_dir(device_root, path)?;
+    }
+
+    let c_path = device_path(device_root, path)?;
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_remove(c_path.as_ptr()) },
+        "failed to remove file",
+    )
+    .map_err(|error| error.or_not_found(path.display(), "file"))?;
+
+    Ok(())
+}
+
+/// Removes everything within a directory, depth-first, and then the
+/// directory itself. Symlinks are removed, rather than followed.
+fn remove_dir_all_impl(device_root: &str, path: &Path) -> Result<(), Error> {
+    if read_link_impl(device_root, path).is_ok() {
+        return remove_file_impl(device_root, path);
+    }
+
+    let mut children = Vec::new();
+
+    for_each_dirent(device_root, path, |dirent| {
+        let is_dir = dirent.stat.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR;
+        children.push((dirent_name(dirent), is_dir));
+    })?;
+
+    // Only remove entries once the directory has been closed, as the driver
+    // can't be relied upon to list a directory while it's being changed
+    for (name, is_dir) in children {
+        let child = path.join(name?);
+
+        if is_dir {
+            remove_dir_all_impl(device_root, &child)?;
+        } else {
+            remove_file_impl(device_root, &child)?;
+        }
+    }
+
+    remove_dir_impl(device_root, path)
+}
+
+fn stat_impl(device_root: &str, path: &Path) -> Result<ps2hdd_sys::iox_stat_t, Error> {
+    let c_path = device_path(device_root, path)?;
+
+    let mut stat: ps2hdd_sys::iox_stat_t = unsafe { std::mem::zeroed() };
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_getstat(c_path.as_ptr(), &mut stat) },
+        "failed to stat file",
+    )
+    .map_err(|error| error.or_not_found(path.display(), "file or directory"))?;
+
+    Ok(stat)
+}
+
+/// Changes the fields of a file's stat selected by `mask`, a combination of
+/// the `FIO_CST_*` flags.
+fn chstat_impl(
+    device_root: &str,
+    path: &Path,
+    stat: &mut ps2hdd_sys::iox_stat_t,
+    mask: u32,
+    err_message: &str,
+) -> Result<(), Error> {
+    let path = device_path(device_root, path)?;
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_chstat(path.as_ptr(), stat, mask) },
+        err_message,
+    )?;
+
+    Ok(())
+}
+
+/// Reads the target of a symlink, failing if `path` is not a symlink.
+fn read_link_impl(device_root: &str, path: &Path) -> Result<std::ffi::CString, Error> {
+    let path = device_path(device_root, path)?;
+
+    // PFS limits symlink targets to a single 1KiB zone's worth of path
+    let mut buffer = vec![0u8; 1024];
+
+    let length = ok_on_nonnegative_or_strerror(
+        unsafe {
+            ps2hdd_sys::iomanx_readlink(
+                path.as_ptr(),
+                buffer.as_mut_ptr() as *mut std::os::raw::c_char,
+                buffer.len() as std::os::raw::c_uint,
+            )
+        },
+        "failed to read symlink",
+    )?;
+
+    buffer.truncate(length as usize);
+
+    if let Some(end) = buffer.iter().position(|&byte| byte == 0x00) {
+        buffer.truncate(end);
+    }
+
+    match std::ffi::CString::new(buffer) {
+        Ok(target) => Ok(target),
+        Err(error) => Err(Error::NulByte {
+            context: "couldn't convert symlink target",
+            error,
+        }),
+    }
+}
+
+fn copy_impl(
+    device_root: &str,
+    from: &Path,
+    to: &Path,
+    options: &CopyOptions,
+) -> Result<u64, Error> {
+    let source_stat = stat_impl(device_root, from)?;
+
+    if stat_impl(device_root, to).is_ok() {
+        if !options.will_overwrite() {
+            return Err(Error::Other(format!("{}: File exists", to.display())));
+        }
+
+        if is_dir_impl(device_root, to) {
+            return Err(Error::Other(format!("{}: Is a directory", to.display())));
+        }
+
+        // The destination is truncated before the source is read, so copying
+        // a file onto itself would lose its contents
+        if options.will_follow_symlinks()
+            && canonicalize_impl(device_root, from)? == canonicalize_impl(device_root, to)?
+        {
+            return Err(Error::Other(format!(
+                "{}: source and destination are the same file",
+                to.display()
+            )));
+        }
+    }
+
+    if !options.will_follow_symlinks() {
+        if let Ok(target) = read_link_impl(device_root, from) {
+            use std::os::unix::ffi::OsStrExt;
+
+            let destination = device_path(device_root, to)?;
+
+            // Relative targets are rewritten to be relative to the new link,
+            // so that it points at the same entry wherever it's copied to
+            let mut target = match split_link_target(device_root, target.as_bytes()) {
+                (_, true) => target.into_bytes(),
+                (_, false) => relative_path(
+                    &normalize_path(to.parent().unwrap_or_else(|| Path::new(""))),
+                    &resolve_link_target(device_root, from, target.as_bytes()),
+                )
+                .as_os_str()
+                .as_bytes()
+                .to_vec(),
+            };
+
+            // Both paths given to the driver must be on the same device
+            if !target.starts_with(device_root.as_bytes()) {
+                target = [device_root.as_bytes(), &target].concat();
+            }
+
+            let target = match std::ffi::CString::new(target) {
+                Ok(target) => target,
+                Err(error) => {
+                    return Err(Error::Other(format!(
+                        "couldn't convert symlink target: {}",
+                        error
+                    )))
+                }
+            };
+
+            // Symlinks can't be created over existing files
+            unsafe { ps2hdd_sys::iomanx_remove(destination.as_ptr()) };
+
+            ok_on_nonnegative_or_strerror(
+                unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), destination.as_ptr()) },
+                "failed to create symlink",
+            )?;
+
+            return Ok(0);
+        }
+    }
+
+    if source_stat.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR {
+        return Err(Error::Other(format!("{}: Is a directory", from.display())));
+    }
+
+    let mut source = File::open(
+        &device_path(device_root, from)?,
+        ps2hdd_sys::IOMANX_O_RDONLY as i32,
+        0,
+    )?;
+
+    let mut destination = File::open(
+        &device_path(device_root, to)?,
+        ps2hdd_sys::IOMANX_O_WRONLY as i32
+            | ps2hdd_sys::IOMANX_O_CREAT as i32
+            | ps2hdd_sys::IOMANX_O_TRUNC as i32,
+        0o644,
+    )?;
+
+    let result = if options.is_sparse() {
+        copy_sparse_impl(&mut source, &mut destination)
+    } else {
+        std::io::copy(&mut source, &mut destination)
+    };
+
+    let copied = match result {
+        Ok(copied) => copied,
+        Err(error) => return Err(Error::Other(format!("failed to copy file: {}", error))),
+    };
+
+    drop(source);
+    destination.close()?;
+
+    if options.will_preserve() {
+        let mut stat = stat_impl(device_root, to)?;
+        stat.mode = (stat.mode & ps2hdd_sys::FIO_S_IFMT) | (source_stat.mode & 0o7777);
+        stat.atime = source_stat.atime;
+        stat.mtime = source_stat.mtime;
+
+        chstat_impl(
+            device_root,
+            to,
+            &mut stat,
+            ps2hdd_sys::FIO_CST_MODE | ps2hdd_sys::FIO_CST_AT | ps2hdd_sys::FIO_CST_MT,
+            "failed to preserve file metadata",
+        )?;
+    }
+
+    Ok(copied)
+}
+
+/// Renames `from` to `to`, replacing `to` if it exists, as `std::fs::rename`
+/// does on Unix.
+///
+/// Files may only replace files, and directories may only replace empty
+/// directories. The driver is first asked to rename over `to` itself, so the
+/// replacement is atomic where it supports that; otherwise, `to` is removed
+/// and the rename retried.
+fn rename_impl(device_root: &str, from: &Path, to: &Path) -> Result<(), Error> {
+    let from_is_dir =
+        stat_impl(device_root, from)?.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR;
+
+    let to_exists = match stat_impl(device_root, to) {
+        Ok(stat) => {
+            // Renaming something onto itself does nothing, and mustn't fall
+            // through to removing it below
+            if entry_path(device_root, from)? == entry_path(device_root, to)? {
+                return Ok(());
+            }
+
+            let to_is_dir = stat.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR;
+
+            match (from_is_dir, to_is_dir) {
+                (false, true) => {
+                    return Err(Error::Other(format!("{}: Is a directory", to.display())))
+                }
+                (true, false) => {
+                    return Err(Error::Other(format!("{}: Not a directory", to.display())))
+                }
+                (true, true) => {
+                    let mut entries = 0;
+                    for_each_dirent(device_root, to, |_| entries += 1)?;
+
+                    if entries > 0 {
+                        return Err(Error::Other(format!(
+                            "{}: Directory not empty",
+                            to.display()
+                        )));
+                    }
+                }
+                (false, false) => {}
+            }
+
+            true
+        }
+        Err(_) => false,
+    };
+
+    let from_path = device_path(device_root, from)?;
+    let to_path = device_path(device_root, to)?;
+
+    let result = unsafe { ps2hdd_sys::iomanx_rename(from_path.as_ptr(), to_path.as_ptr()) };
+
+    if result >= 0 || !to_exists {
+        ok_on_nonnegative_or_strerror(result, "failed to rename")?;
+        return Ok(());
+    }
+
+    debug!(
+        "Driver wouldn't rename over {}; removing it first",
+        to.display()
+    );
+
+    if from_is_dir {
+        ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_rmdir(to_path.as_ptr()) },
+            "failed to replace directory",
+        )?;
+    } else {
+        ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_remove(to_path.as_ptr()) },
+            "failed to replace file",
+        )?;
+    }
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_rename(from_path.as_ptr(), to_path.as_ptr()) },
+        "failed to rename",
+    )?;
+
+    Ok(())
+}
+
+/// Resolves the directory containing `path`, but not `path` itself, so that
+/// two paths can be compared to tell whether they name the same entry, even
+/// if it's a symlink.
+fn entry_path(device_root: &str, path: &Path) -> Result<std::path::PathBuf, Error> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() || parent == Path::new("/") {
+                std::path::PathBuf::new()
+            } else {
+                canonicalize_impl(device_root, parent)?
+            };
+
+            Ok(parent.join(name))
+        }
+        _ => canonicalize_impl(device_root, path),
+    }
+}
+
+/// Splits the target of a symlink, as read from the device, into the path it
+/// names, and whether that path starts from the root of the device, rather
+/// than from the directory containing the link.
+///
+/// Targets carrying the device's prefix, or starting with `/`, start from the
+/// root, and any others are relative to the link's directory, as PFS itself
+/// resolves them.
+fn split_link_target<'a>(device_root: &str, target: &'a [u8]) -> (&'a Path, bool) {
+    use std::os::unix::ffi::OsStrExt;
+
+    match target.strip_prefix(device_root.as_bytes()) {
+        Some(path) => (Path::new(std::ffi::OsStr::from_bytes(path)), true),
+        None => {
+            let path = Path::new(std::ffi::OsStr::from_bytes(target));
+            (path, path.has_root())
+        }
+    }
+}
+
+/// Resolves `.` and `..` within `path` without following any symlinks,
+/// giving a path relative to the root of the device.
+fn normalize_path(path: &Path) -> std::path::PathBuf {
+    use std::path::Component;
+
+    let mut normalized = std::path::PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => {}
+        }
     }
 
-    /// Recursively create a directory and all of its parent components if they
-    /// are missing.
-    fn create_dir_all<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        create_dir_all_impl(self.get_device_root(), path.as_ref())
-    }
+    normalized
+}
+
+/// Resolves the target of the symlink at `link`, as read from the device, to
+/// the path of the entry it points at, relative to the root of the device.
+///
+/// `..` within the target is resolved without following any symlinks, which
+/// is only right when none of the directories involved are symlinks;
+/// `canonicalize_impl` resolves targets fully.
+fn resolve_link_target(device_root: &str, link: &Path, target: &[u8]) -> std::path::PathBuf {
+    match split_link_target(device_root, target) {
+        (path, true) => normalize_path(path),
+        (path, false) => normalize_path(&link.parent().unwrap_or_else(|| Path::new("")).join(path)),
+    }
+}
+
+/// Returns the path of `target` relative to the directory `dir`, where both
+/// are normalized paths from the same root.
+fn relative_path(dir: &Path, target: &Path) -> std::path::PathBuf {
+    let common = dir
+        .components()
+        .zip(target.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = std::path::PathBuf::new();
+
+    for _ in common..dir.components().count() {
+        relative.push("..");
+    }
+
+    relative.extend(target.components().skip(common));
+
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+
+    relative
+}
+
+/// Rewrites the target of the symlink at `link`, as read from the device, to
+/// be relative to the link, so that it still points at the same entry once
+/// `src` has been copied onto the host.
+///
+/// Returns `None` if the target is outside of `src`, and so wouldn't exist on
+/// the host.
+fn host_link_target(
+    device_root: &str,
+    src: &Path,
+    link: &Path,
+    target: &[u8],
+) -> Option<std::path::PathBuf> {
+    let target = resolve_link_target(device_root, link, target);
+
+    if !target.starts_with(normalize_path(src)) {
+        return None;
+    }
+
+    Some(relative_path(
+        &normalize_path(link.parent().unwrap_or_else(|| Path::new(""))),
+        &target,
+    ))
+}
+
+fn sync_impl(device_root: &str) -> Result<(), Error> {
+    let device = match std::ffi::CString::new(device_root) {
+        Ok(device) => device,
+        Err(error) => {
+            return Err(Error::NulByte {
+                context: "couldn't convert device",
+                error,
+            })
+        }
+    };
+
+    ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_sync(device.as_ptr(), 0) },
+        "failed to sync device",
+    )?;
+
+    Ok(())
+}
+
+/// Copies `source` into `destination`, seeking past blocks which are all
+/// zero rather than writing them, for as long as the destination allows it.
+fn copy_sparse_impl<R: std::io::Read, W: std::io::Write + std::io::Seek>(
+    source: &mut R,
+    destination: &mut W,
+) -> std::io::Result<u64> {
+    use std::io::SeekFrom;
+
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    let mut copied = 0u64;
+    let mut can_seek = true;
+    let mut ends_in_hole = false;
+
+    loop {
+        let length = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(length) => length,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        let block = &buffer[..length];
+
+        if can_seek && block.iter().all(|&byte| byte == 0) {
+            if destination.seek(SeekFrom::Current(length as i64)).is_ok() {
+                copied += length as u64;
+                ends_in_hole = true;
+                continue;
+            }
+
+            debug!("Destination won't seek past the end of the file; writing zeroes");
+            can_seek = false;
+        }
+
+        destination.write_all(block)?;
+        copied += length as u64;
+        ends_in_hole = false;
+    }
+
+    // Files only extend as far as their last byte written, so a trailing
+    // hole needs its last byte filling in
+    if ends_in_hole {
+        destination.seek(SeekFrom::Current(-1))?;
+        destination.write_all(&[0])?;
+    }
+
+    Ok(copied)
+}
+
+/// Returns the path of the temporary file used while atomically writing to
+/// `path`; a hidden file alongside it.
+fn atomic_temp_path(path: &Path) -> Result<std::path::PathBuf, Error> {
+    match path.file_name() {
+        Some(name) => Ok(path.with_file_name(format!(".{}.tmp", name.to_string_lossy()))),
+        None => Err(Error::Other(format!("{}: not a file path", path.display()))),
+    }
+}
+
+/// The most symlinks followed while resolving a single path, to avoid looping
+/// forever on symlinks which point at each other
+const MAX_SYMLINK_DEPTH: usize = 32;
+
+/// Resolves `path` to the path of the file it refers to, relative to the root
+/// of the device, following any symlinks and removing any `.` or `..`.
+fn canonicalize_impl(device_root: &str, path: &Path) -> Result<std::path::PathBuf, Error> {
+    use std::path::Component;
+
+    let mut resolved = std::path::PathBuf::new();
+    let mut pending: Vec<std::ffi::OsString> = Vec::new();
+    let mut depth = 0;
+
+    fn push_components(pending: &mut Vec<std::ffi::OsString>, path: &Path) {
+        for component in path.components().rev() {
+            match component {
+                Component::Normal(name) => pending.push(name.to_os_string()),
+                Component::ParentDir => pending.push("..".into()),
+                _ => {}
+            }
+        }
+    }
+
+    push_components(&mut pending, path);
+
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            resolved.pop();
+            continue;
+        }
+
+        let candidate = resolved.join(&name);
+
+        match read_link_impl(device_root, &candidate) {
+            Ok(target) => {
+                depth += 1;
+
+                if depth > MAX_SYMLINK_DEPTH {
+                    return Err(Error::Other(format!(
+                        "{}: Too many levels of symbolic links",
+                        path.display()
+                    )));
+                }
+
+                // `resolved` still names the directory containing the link,
+                // which relative targets are resolved against
+                let (target, from_root) = split_link_target(device_root, target.as_bytes());
+
+                if from_root {
+                    resolved = std::path::PathBuf::new();
+                }
+
+                push_components(&mut pending, target);
+            }
+            Err(_) => resolved = candidate,
+        }
+    }
+
+    stat_impl(device_root, &resolved)?;
+
+    Ok(resolved)
+}
+
+/// Returns the name of a directory entry as a `String`.
+fn dirent_name(dirent: &ps2hdd_sys::iox_dirent_t) -> Result<String, Error> {
+    match unsafe { std::ffi::CStr::from_ptr(dirent.name.as_ptr()) }.to_str() {
+        Ok(name) => Ok(name.to_owned()),
+        Err(error) => Err(Error::Other(format!(
+            "could not convert the directory name to a String: {}",
+            error
+        ))),
+    }
+}
+
+/// Calls `f` with every entry within a directory, other than `.` and `..`,
+/// reusing a single dirent for each of them.
+fn for_each_dirent<F: FnMut(&ps2hdd_sys::iox_dirent_t)>(
+    device_root: &str,
+    path: &Path,
+    mut f: F,
+) -> Result<(), Error> {
+    let c_path = device_path(device_root, path)?;
+
+    let mut temp_dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
+
+    let directory_handle = ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_dopen(c_path.as_ptr()) },
+        "Failed to list directory",
+    )
+    .map_err(|error| error.or_not_found(path.display(), "directory"))?;
+
+    while {
+        let result = unsafe { ps2hdd_sys::iomanx_dread(directory_handle, &mut temp_dirent) };
+
+        if result < 0 {
+            unsafe { ps2hdd_sys::iomanx_close(directory_handle) };
+            let name = dirent_name(&temp_dirent)?;
+            return Err(Error::Other(format!(
+                "Failed to list directories: {} {}",
+                result, name
+            )));
+        }
+
+        result > 0
+    } {
+        let name = match dirent_name(&temp_dirent) {
+            Ok(name) => name,
+            Err(message) => {
+                unsafe { ps2hdd_sys::iomanx_close(directory_handle) };
+                return Err(message);
+            }
+        };
+
+        // Based on Rust's unix ReadDir implementation:
+        // https://github.com/rust-lang/rust/blob/19e1aac6ea9879c6d10eed7106b3bc883e5bf9a5/library/std/src/sys/unix/fs.rs#L488
+        if name != "." && name != ".." {
+            f(&temp_dirent);
+        }
+    }
+
+    ok_on_zero_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_close(directory_handle) },
+        "Failed to close directory handle",
+    )?;
+
+    Ok(())
+}
+
+pub trait Driver {
+    /// Retrieves the root of the given device's file system
+    fn get_device_root(&self) -> &str;
+
+    /// Retrieves the name of the partition mounted on the given device
+    fn get_partition_name(&self) -> &str;
+
+    /// Creates a new, empty directory at the provided path
+    fn create_dir<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        create_dir_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Creates a new, empty directory at the provided path, unless there is
+    /// already a directory there, returning whether it was newly created.
+    ///
+    /// Unlike [`create_dir`](#method.create_dir), this doesn't fail if the
+    /// directory already exists, but does if there's a file in its place.
+    fn try_create_dir<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<bool, Error> {
+        match create_dir_impl(self.get_device_root(), path.as_ref()) {
+            Ok(()) => Ok(true),
+            Err(_) if is_dir_impl(self.get_device_root(), path.as_ref()) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Recursively create a directory and all of its parent components if they
+    /// are missing.
+    fn create_dir_all<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        create_dir_all_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Recursively create a directory and all of its parent components if they
+    /// are missing, as [`create_dir_all`] does, returning the directories
+    /// which were actually created, parents first.
+    ///
+    /// Directories which already existed aren't included, so removing the
+    /// returned directories in reverse order undoes exactly what was done.
+    ///
+    /// [`create_dir_all`]: #method.create_dir_all
+    fn create_dir_all_reported<P: std::fmt::Display + AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<std::path::PathBuf>, Error> {
+        let mut created = Vec::new();
+        create_dir_all_reported_impl(self.get_device_root(), path.as_ref(), &mut created)?;
+
+        Ok(created)
+    }
+
+    /// List the entries within a directory.
+    ///
+    /// Note that unlike `std::fs::read_dir` or the like, which return an
+    /// iterator, all entries are fetched upfront, due to the underlying
+    /// driver involving internal state we can't fully rely on.
+    fn list_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DirEntry>, Error> {
+        let mut dirents = Vec::new();
+        let root: std::rc::Rc<Path> = path.as_ref().into();
+        let partition: std::rc::Rc<str> = self.get_partition_name().into();
+
+        for_each_dirent(self.get_device_root(), path.as_ref(), |dirent| {
+            dirents.push(DirEntry::new(*dirent, root.clone()).with_partition(partition.clone()));
+        })?;
+
+        Ok(dirents)
+    }
+
+    /// List up to `limit` of the entries within a directory, skipping the
+    /// first `offset`, such as to show one page of a very large directory at
+    /// a time.
+    ///
+    /// No directory handle is kept open between calls, so every call lists
+    /// the directory from the start, only keeping the entries within the
+    /// page; fetching every page of a directory this way costs far more than
+    /// a single [`list_dir`]. Entries are returned in the order the driver
+    /// lists them, so pages are only consistent while the directory isn't
+    /// being changed.
+    ///
+    /// [`list_dir`]: #method.list_dir
+    fn read_dir_page<P: AsRef<Path>>(
+        &self,
+        path: P,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<DirEntry>, Error> {
+        let mut dirents = Vec::new();
+        let mut index = 0;
+        let root: std::rc::Rc<Path> = path.as_ref().into();
+        let partition: std::rc::Rc<str> = self.get_partition_name().into();
+
+        for_each_dirent(self.get_device_root(), path.as_ref(), |dirent| {
+            if index >= offset && dirents.len() < limit {
+                dirents
+                    .push(DirEntry::new(*dirent, root.clone()).with_partition(partition.clone()));
+            }
+
+            index += 1;
+        })?;
+
+        Ok(dirents)
+    }
+
+    /// List only the subdirectories within a directory.
+    fn list_dirs<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DirEntry>, Error> {
+        let mut dirs = Vec::new();
+
+        for entry in self.list_dir(path)? {
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry);
+            }
+        }
+
+        Ok(dirs)
+    }
+
+    /// List only the regular files within a directory.
+    fn list_files<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DirEntry>, Error> {
+        let mut files = Vec::new();
+
+        for entry in self.list_dir(path)? {
+            if entry.file_type()?.is_file() {
+                files.push(entry);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Counts the entries within a directory, and, if `recursive` is `true`,
+    /// all of the entries beneath it, without building a [`DirEntry`] for
+    /// each of them.
+    ///
+    /// This is intended to give progress reporting a total to work towards
+    /// before starting a recursive operation.
+    ///
+    /// [`DirEntry`]: ../fs/struct.DirEntry.html
+    fn total_entries<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<u64, Error> {
+        let mut total = 0;
+        let mut children = Vec::new();
+
+        for_each_dirent(self.get_device_root(), path.as_ref(), |dirent| {
+            total += 1;
+
+            if recursive && dirent.stat.mode & ps2hdd_sys::FIO_S_IFMT == ps2hdd_sys::FIO_S_IFDIR {
+                children.push(dirent_name(dirent));
+            }
+        })?;
+
+        // Only descend once the directory has been closed, so that deep trees
+        // don't hold a directory handle open for every level
+        for child in children {
+            total += self.total_entries(path.as_ref().join(child?), true)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Counts the files, directories and symlinks directly within a
+    /// directory, such as to describe it as having "12 files and 3 folders".
+    ///
+    /// Each entry's type is taken from the directory listing itself, so no
+    /// entry is stat'd separately, and no [`DirEntry`] is built for it.
+    ///
+    /// [`DirEntry`]: ../fs/struct.DirEntry.html
+    fn summarize_dir<P: AsRef<Path>>(&self, path: P) -> Result<DirSummary, Error> {
+        let mut summary = DirSummary::default();
+
+        for_each_dirent(self.get_device_root(), path.as_ref(), |dirent| {
+            summary.add(FileType::from_mode(dirent.stat.mode))
+        })?;
+
+        Ok(summary)
+    }
+
+    /// Recursively list every entry beneath a directory, depth-first, with
+    /// each directory's entries immediately following the directory itself.
+    ///
+    /// The file types used to decide which entries to descend into come from
+    /// the stats embedded in each listed entry (see [`DirEntry::metadata`]),
+    /// so walking a tree only reads each directory once, and never has to
+    /// stat individual entries.
+    ///
+    /// [`DirEntry::metadata`]: ../fs/struct.DirEntry.html#method.metadata
+    fn walk_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<DirEntry>, Error> {
+        let mut entries = Vec::new();
+
+        for entry in self.list_dir(path.as_ref())? {
+            let child = if entry.metadata()?.is_dir() {
+                Some(entry.path())
+            } else {
+                None
+            };
+
+            entries.push(entry);
+
+            if let Some(child) = child {
+                entries.append(&mut self.walk_dir(child)?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes an empty directory.
+    fn remove_dir<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        remove_dir_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Returns the metadata of the file or directory at a path, such as its
+    /// size, type and timestamps, as `std::fs::metadata` does.
+    ///
+    /// Unlike [`DirEntry::metadata`], this doesn't require listing the
+    /// parent directory to find the entry.
+    ///
+    /// [`DirEntry::metadata`]: ../fs/struct.DirEntry.html#method.metadata
+    fn stat<P: AsRef<Path>>(&self, path: P) -> Result<Metadata, Error> {
+        Ok(Metadata::from(&stat_impl(
+            self.get_device_root(),
+            path.as_ref(),
+        )?))
+    }
+
+    /// Read the entire contents of a file into a bytes vector.
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        read_file_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Returns the length of a file in bytes, without reading the rest of its
+    /// metadata.
+    ///
+    /// This is the file's exact length, not the space allocated to it, which
+    /// is rounded up to whole sectors.
+    fn file_size<P: AsRef<Path>>(&self, path: P) -> Result<u64, Error> {
+        let stat = stat_impl(self.get_device_root(), path.as_ref())?;
+
+        Ok(((stat.hisize as u64) << 32) | stat.size as u64)
+    }
+
+    /// Reads exactly enough bytes to fill `buf`, starting `offset` bytes into
+    /// a file, as `Read::read_exact` does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file could not be opened or
+    /// read, or if the end of the file is reached before `buf` is filled, in
+    /// which case the contents of `buf` are unspecified.
+    fn read_exact_at<P: AsRef<Path>>(
+        &self,
+        path: P,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        use std::io::{Read, Seek};
+
+        let length = buf.len();
+        let unexpected_eof = || {
+            Error::Other(format!(
+                "unexpected end of file reading {} bytes at offset {}",
+                length, offset
+            ))
+        };
+
+        // PFS refuses to seek past the end of a file, so check the length
+        // first, rather than reporting a failed seek
+        match offset.checked_add(length as u64) {
+            Some(end) if end <= self.file_size(path.as_ref())? => {}
+            _ => return Err(unexpected_eof()),
+        }
+
+        let mut file = self.open_file(path)?;
+
+        if let Err(error) = file.seek(std::io::SeekFrom::Start(offset)) {
+            return Err(Error::Other(format!("failed to seek file: {}", error)));
+        }
+
+        match file.read_exact(buf) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Err(unexpected_eof())
+            }
+            Err(error) => Err(Error::Other(format!("failed to read file: {}", error))),
+        }
+    }
+
+    /// Opens a file for reading.
+    ///
+    /// Directories can't be opened as files; use [`list_dir`] to read them.
+    ///
+    /// [`list_dir`]: #method.list_dir
+    fn open_file<P: AsRef<Path>>(&self, path: P) -> Result<File, Error> {
+        ensure_not_dir(self.get_device_root(), path.as_ref())?;
+
+        let c_path = device_path(self.get_device_root(), path.as_ref())?;
+
+        File::open(&c_path, ps2hdd_sys::IOMANX_O_RDONLY as i32, 0)
+            .map_err(|error| error.or_not_found(path.as_ref().display(), "file or directory"))
+    }
+
+    /// Opens a file for writing, creating it if it does not exist, and
+    /// truncating it if it does.
+    fn create_file<P: AsRef<Path>>(&self, path: P) -> Result<File, Error> {
+        ensure_not_dir(self.get_device_root(), path.as_ref())?;
+
+        let path = device_path(self.get_device_root(), path.as_ref())?;
+
+        let open_flags = ps2hdd_sys::IOMANX_O_WRONLY as i32
+            | ps2hdd_sys::IOMANX_O_CREAT as i32
+            | ps2hdd_sys::IOMANX_O_TRUNC as i32;
+
+        File::open(&path, open_flags, 0o644)
+    }
+
+    /// Creates a file of `len` bytes, all zero, replacing any existing file,
+    /// such as to set aside a fixed-size container to be filled in later.
+    ///
+    /// The driver is first asked to seek to the end of the new file, so only
+    /// its last byte needs to be written. PFS has no sparse files, though, and
+    /// doesn't allow seeking past the end of a file, so in practice the zeroes
+    /// are written out in chunks, and every block of the file is allocated.
+    fn create_file_with_len<P: AsRef<Path>>(&self, path: P, len: u64) -> Result<(), Error> {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let mut file = self.create_file(path.as_ref())?;
+
+        if len == 0 {
+            return file.close();
+        }
+
+        if file.seek(SeekFrom::Start(len - 1)).is_ok() {
+            return match file.write_all(&[0]) {
+                Ok(()) => file.close(),
+                Err(error) => Err(Error::Other(format!("failed to extend file: {}", error))),
+            };
+        }
+
+        debug!(
+            "Driver won't seek past the end of {}; writing zeroes",
+            path.as_ref().display()
+        );
+
+        if let Err(error) = file.seek(SeekFrom::Start(0)) {
+            return Err(Error::Other(format!("failed to seek file: {}", error)));
+        }
+
+        match std::io::copy(&mut std::io::repeat(0).take(len), &mut file) {
+            Ok(_) => file.close(),
+            Err(error) => Err(Error::Other(format!("failed to extend file: {}", error))),
+        }
+    }
+
+    /// Opens a file for reading, wrapped in a `BufReader`, so that small
+    /// reads, such as reading line-by-line, don't each call into the drivers.
+    fn open_buffered_reader<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<std::io::BufReader<File>, Error> {
+        Ok(std::io::BufReader::with_capacity(
+            READ_BUFFER_SIZE,
+            self.open_file(path)?,
+        ))
+    }
+
+    /// Opens a file for writing as [`create_file`] does, wrapped in a
+    /// `BufWriter`, so that small writes don't each call into the drivers.
+    ///
+    /// As with any `BufWriter`, errors writing out the last of the buffer
+    /// when it is dropped are ignored; call `flush` to observe them.
+    ///
+    /// [`create_file`]: #method.create_file
+    fn open_buffered_writer<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<std::io::BufWriter<File>, Error> {
+        Ok(std::io::BufWriter::with_capacity(
+            READ_BUFFER_SIZE,
+            self.create_file(path)?,
+        ))
+    }
+
+    /// Write a slice as the entire contents of a file, creating it if it does
+    /// not exist, and replacing its contents if it does.
+    fn write_file<P: AsRef<Path>>(&self, path: P, contents: &[u8]) -> Result<(), Error> {
+        self.write_file_with(path, contents, &WriteOptions::new())
+    }
+
+    /// Write a slice as the entire contents of a file, as controlled by
+    /// `options`.
+    ///
+    /// If the file already exists, its permissions are kept, unless
+    /// [`WriteOptions::reset_permissions`] is set.
+    ///
+    /// [`WriteOptions::reset_permissions`]: ../fs/struct.WriteOptions.html#method.reset_permissions
+    fn write_file_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        contents: &[u8],
+        options: &WriteOptions,
+    ) -> Result<(), Error> {
+        let previous_mode = if options.resets_permissions() {
+            None
+        } else {
+            stat_impl(self.get_device_root(), path.as_ref())
+                .ok()
+                .map(|stat| stat.mode)
+        };
+
+        write_file_with_impl(self.get_device_root(), path.as_ref(), contents, options)?;
+
+        if let Some(previous_mode) = previous_mode {
+            let mut stat = stat_impl(self.get_device_root(), path.as_ref())?;
+
+            if stat.mode & 0o7777 != previous_mode & 0o7777 {
+                stat.mode = (stat.mode & ps2hdd_sys::FIO_S_IFMT) | (previous_mode & 0o7777);
+
+                chstat_impl(
+                    self.get_device_root(),
+                    path.as_ref(),
+                    &mut stat,
+                    ps2hdd_sys::FIO_CST_MODE,
+                    "failed to restore file permissions",
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a slice as the entire contents of a file, via a temporary file
+    /// which is only renamed over the destination once fully written.
+    ///
+    /// See [`WriteOptions::atomic`](../fs/struct.WriteOptions.html#method.atomic).
+    fn write_atomic<P: AsRef<Path>>(&self, path: P, contents: &[u8]) -> Result<(), Error> {
+        self.write_file_with(path, contents, &WriteOptions::new().atomic(true))
+    }
+
+    /// Write a slice as the entire contents of a file, as [`write_file`]
+    /// does, then sync the device and read the file back, returning an error
+    /// if what was read doesn't match `contents`.
+    ///
+    /// This is intended for important data, such as saves, on drives which
+    /// may be failing, and costs a full read of the file on every write.
+    /// Note that the read back may be served from the driver's cache rather
+    /// than the disk itself, so this can't catch every failure.
+    ///
+    /// [`write_file`]: #method.write_file
+    fn write_verified<P: AsRef<Path>>(&self, path: P, contents: &[u8]) -> Result<(), Error> {
+        self.write_file(path.as_ref(), contents)?;
+        sync_impl(self.get_device_root())?;
+        verify_contents_impl(self.get_device_root(), path.as_ref(), contents)
+    }
+
+    /// Write the concatenation of several slices as the entire contents of a
+    /// file, as [`write_file`] does, writing each in turn rather than first
+    /// copying them into a single buffer.
+    ///
+    /// [`write_file`]: #method.write_file
+    fn write_vectored<P: AsRef<Path>>(
+        &self,
+        path: P,
+        bufs: &[std::io::IoSlice],
+    ) -> Result<(), Error> {
+        use std::io::Write;
+
+        let mut file = self.create_file(path)?;
+
+        for buf in bufs {
+            if let Err(error) = file.write_all(buf) {
+                return Err(Error::Other(format!("failed to write file: {}", error)));
+            }
+        }
+
+        file.close()
+    }
+
+    /// Writes many files at once, such as when extracting an archive, each as
+    /// [`write_file`] does, returning the number of files written.
+    ///
+    /// The device is synced once after all of the files are written, so that
+    /// they've all reached the disk when this returns. Until then, if the
+    /// process is interrupted part way through, any of the files may be
+    /// missing or incomplete, not just the last one.
+    ///
+    /// [`write_file`]: #method.write_file
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the files could not be
+    /// written, in which case the files before it are kept, and still synced,
+    /// and those after it are not written.
+    fn write_files<I, P, C>(&self, files: I) -> Result<usize, Error>
+    where
+        I: IntoIterator<Item = (P, C)>,
+        P: AsRef<Path>,
+        C: AsRef<[u8]>,
+    {
+        let mut written = 0;
+
+        for (path, contents) in files {
+            if let Err(message) = write_file_impl(
+                self.get_device_root(),
+                path.as_ref(),
+                contents.as_ref(),
+                true,
+            ) {
+                sync_impl(self.get_device_root())?;
+                return Err(message);
+            }
+
+            written += 1;
+        }
+
+        debug!("Wrote {} files; syncing", written);
+
+        sync_impl(self.get_device_root())?;
+
+        Ok(written)
+    }
+
+    /// Issues an `ioctl2` command to the file at the provided path, passing
+    /// `arg` as the command's input and receiving its output into `buf`.
+    ///
+    /// Only the `File*` commands of [`Ioctl2Command`] are understood by
+    /// files.
+    ///
+    /// [`Ioctl2Command`]: ../ioctl/enum.Ioctl2Command.html
+    fn ioctl2<P: AsRef<Path>>(
+        &self,
+        path: P,
+        command: Ioctl2Command,
+        arg: &[u8],
+        buf: &mut [u8],
+    ) -> Result<i32, Error> {
+        let path = device_path(self.get_device_root(), path.as_ref())?;
+
+        let handle = ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_open(path.as_ptr(), ps2hdd_sys::IOMANX_O_RDWR as i32) },
+            "failed to open file",
+        )?;
+
+        let result = ioctl2(handle, command, arg, buf);
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_close(handle) },
+            "failed to close file",
+        )?;
+
+        result
+    }
+
+    /// Removes a directory at this path, after removing all its contents. Use
+    /// carefully!
+    ///
+    /// Symlinks within the directory are removed themselves, rather than
+    /// followed, so nothing outside of the directory is removed.
+    fn remove_dir_all<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        remove_dir_all_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Removes a file from the filesystem.
+    ///
+    /// Symlinks are removed themselves, rather than the files they point at.
+    /// Directories can't be removed this way; see [`remove_dir`].
+    ///
+    /// [`remove_dir`]: #method.remove_dir
+    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        remove_file_impl(self.get_device_root(), path.as_ref())
+    }
+
+    /// Copies the contents of one file to another, returning the number of
+    /// bytes copied, with the behaviour controlled by `options`.
+    ///
+    /// If `from` is a symlink which isn't followed, it is copied as a new
+    /// symlink to the same target, and zero bytes are reported as copied.
+    fn copy<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        to: Q,
+        options: &CopyOptions,
+    ) -> Result<u64, Error> {
+        copy_impl(self.get_device_root(), from.as_ref(), to.as_ref(), options)
+    }
+
+    /// Copies `len` bytes from `from_offset` bytes into one file, to
+    /// `to_offset` bytes into another, returning the number of bytes copied.
+    ///
+    /// The bytes are streamed between the files in chunks, rather than
+    /// reading the whole of `from` into memory. `to` is created if it does
+    /// not exist, and is otherwise not truncated, so only the bytes in the
+    /// range are replaced. If `to_offset` is past the end of `to`, the gap is
+    /// filled with zeroes. The ranges shouldn't overlap if `from` and `to`
+    /// are the same file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either path is a directory, if
+    /// the range extends past the end of `from`, or if either file could not
+    /// be opened, read or written.
+    fn copy_range<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        from_offset: u64,
+        len: u64,
+        to: Q,
+        to_offset: u64,
+    ) -> Result<u64, Error> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let from_len = self.file_size(from.as_ref())?;
+
+        if from_offset.saturating_add(len) > from_len {
+            return Err(Error::Other(format!(
+                "{}: range of {} bytes at offset {} is past the end of the file ({} bytes)",
+                from.as_ref().display(),
+                len,
+                from_offset,
+                from_len
+            )));
+        }
+
+        let mut source = self.open_file(from)?;
+
+        ensure_not_dir(self.get_device_root(), to.as_ref())?;
+
+        let to_path = device_path(self.get_device_root(), to.as_ref())?;
+        let mut destination = File::open(
+            &to_path,
+            ps2hdd_sys::IOMANX_O_WRONLY as i32 | ps2hdd_sys::IOMANX_O_CREAT as i32,
+            0o644,
+        )?;
+
+        if let Err(error) = source.seek(SeekFrom::Start(from_offset)) {
+            return Err(Error::Other(format!("failed to seek file: {}", error)));
+        }
+
+        let to_len = match destination.seek(SeekFrom::End(0)) {
+            Ok(to_len) => to_len,
+            Err(error) => return Err(Error::Other(format!("failed to seek file: {}", error))),
+        };
+
+        // Extend the file up to the offset, rather than seeking past its end
+        let result = if to_offset > to_len {
+            std::io::copy(
+                &mut std::io::repeat(0).take(to_offset - to_len),
+                &mut destination,
+            )
+            .map(|_| to_offset)
+        } else {
+            destination.seek(SeekFrom::Start(to_offset))
+        };
+
+        if let Err(error) = result {
+            return Err(Error::Other(format!("failed to extend file: {}", error)));
+        }
+
+        match std::io::copy(&mut source.take(len), &mut destination) {
+            Ok(copied) if copied == len => {
+                destination.close()?;
+                Ok(copied)
+            }
+            Ok(copied) => Err(Error::Other(format!(
+                "failed to copy range: only copied {} of {} bytes",
+                copied, len
+            ))),
+            Err(error) => Err(Error::Other(format!("failed to copy range: {}", error))),
+        }
+    }
+
+    /// Copies a file from the host's filesystem onto the device, replacing
+    /// any existing file at `dest`, and returning the number of bytes copied.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `host` could not be read, if
+    /// `dest` is a directory or its parent doesn't exist, or if the copy
+    /// could not be written out in full.
+    fn copy_from_host<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        host: P,
+        dest: Q,
+    ) -> Result<u64, Error> {
+        self.copy_from_host_with(host, dest, &CopyOptions::new())
+    }
+
+    /// Copies a file from the host's filesystem onto the device, as with
+    /// [`copy_from_host`], with the behaviour controlled by `options`.
+    ///
+    /// Preserving keeps the host file's permissions, and its access and
+    /// modification times. A host symlink which isn't followed is recreated
+    /// on the device with the same target, which must be relative, as the
+    /// host's absolute paths mean nothing on the device.
+    ///
+    /// [`copy_from_host`]: #method.copy_from_host
+    fn copy_from_host_with<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        host: P,
+        dest: Q,
+        options: &CopyOptions,
+    ) -> Result<u64, Error> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::PermissionsExt;
+
+        let device_root = self.get_device_root();
+
+        if !options.will_overwrite() && stat_impl(device_root, dest.as_ref()).is_ok() {
+            return Err(Error::Other(format!(
+                "{}: File exists",
+                dest.as_ref().display()
+            )));
+        }
+
+        if !options.will_follow_symlinks() {
+            if let Ok(target) = std::fs::read_link(host.as_ref()) {
+                if target.is_absolute() {
+                    return Err(Error::Other(format!(
+                        "{}: symlink target {} is a host path",
+                        host.as_ref().display(),
+                        target.display()
+                    )));
+                }
+
+                ensure_not_dir(device_root, dest.as_ref())?;
+
+                let link = device_path(device_root, dest.as_ref())?;
+                let target = match std::ffi::CString::new(
+                    [device_root.as_bytes(), target.as_os_str().as_bytes()].concat(),
+                ) {
+                    Ok(target) => target,
+                    Err(error) => {
+                        return Err(Error::Other(format!(
+                            "couldn't convert symlink target: {}",
+                            error
+                        )))
+                    }
+                };
+
+                // Symlinks can't be created over existing files
+                unsafe { ps2hdd_sys::iomanx_remove(link.as_ptr()) };
+
+                ok_on_nonnegative_or_strerror(
+                    unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) },
+                    "failed to create symlink",
+                )?;
+
+                return Ok(0);
+            }
+        }
+
+        let mut source = match std::fs::File::open(host.as_ref()) {
+            Ok(source) => source,
+            Err(error) => {
+                return Err(Error::Other(format!(
+                    "{}: {}",
+                    host.as_ref().display(),
+                    error
+                )))
+            }
+        };
+
+        let mut destination = self.create_file(dest.as_ref())?;
+
+        let result = if options.is_sparse() {
+            copy_sparse_impl(&mut source, &mut destination)
+        } else {
+            std::io::copy(&mut source, &mut destination)
+        };
+
+        let copied = match result {
+            Ok(copied) => copied,
+            Err(error) => return Err(Error::Other(format!("failed to copy from host: {}", error))),
+        };
+
+        destination.close()?;
+
+        if options.will_preserve() {
+            let metadata = match source.metadata() {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    return Err(Error::Other(format!(
+                        "{}: {}",
+                        host.as_ref().display(),
+                        error
+                    )))
+                }
+            };
+
+            let mut stat = stat_impl(device_root, dest.as_ref())?;
+            let mut mask = ps2hdd_sys::FIO_CST_MODE;
+            stat.mode =
+                (stat.mode & ps2hdd_sys::FIO_S_IFMT) | (metadata.permissions().mode() & 0o7777);
+
+            if let Some(accessed) = metadata
+                .accessed()
+                .ok()
+                .and_then(DateTime::from_system_time)
+            {
+                stat.atime = accessed.into();
+                mask |= ps2hdd_sys::FIO_CST_AT;
+            }
+
+            if let Some(modified) = metadata
+                .modified()
+                .ok()
+                .and_then(DateTime::from_system_time)
+            {
+                stat.mtime = modified.into();
+                mask |= ps2hdd_sys::FIO_CST_MT;
+            }
+
+            chstat_impl(
+                device_root,
+                dest.as_ref(),
+                &mut stat,
+                mask,
+                "failed to preserve file metadata",
+            )?;
+        }
+
+        Ok(copied)
+    }
+
+    /// Copies a file from the host's filesystem onto the device, as with
+    /// [`copy_from_host`], first creating any of the parent directories of
+    /// `dest` which are missing.
+    ///
+    /// [`copy_from_host`]: #method.copy_from_host
+    fn copy_from_host_create_parents<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        host: P,
+        dest: Q,
+    ) -> Result<u64, Error> {
+        create_parents_impl(self.get_device_root(), dest.as_ref())?;
+
+        self.copy_from_host(host, dest)
+    }
+
+    /// Copies a file from the device onto the host's filesystem, replacing
+    /// any existing file at `host`, and returning the number of bytes copied.
+    ///
+    /// The host file is synced to disk before returning.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `src` is a directory, as those
+    /// need to be copied recursively, if it could not be read, or if `host`
+    /// could not be written and synced.
+    fn copy_to_host<P: AsRef<Path>, Q: AsRef<Path>>(&self, src: P, host: Q) -> Result<u64, Error> {
+        self.copy_to_host_with(src, host, &CopyOptions::new())
+    }
+
+    /// Copies a file from the device onto the host's filesystem, as with
+    /// [`copy_to_host`], with the behaviour controlled by `options`.
+    ///
+    /// Preserving keeps the device file's permissions, and its access and
+    /// modification times. A symlink which isn't followed is recreated on the
+    /// host with its target made relative to the link, as the device's own
+    /// paths mean nothing to the host.
+    ///
+    /// [`copy_to_host`]: #method.copy_to_host
+    fn copy_to_host_with<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        src: P,
+        host: Q,
+        options: &CopyOptions,
+    ) -> Result<u64, Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let device_root = self.get_device_root();
+
+        if is_dir_impl(device_root, src.as_ref()) {
+            return Err(Error::Other(format!(
+                "{}: Is a directory, and must be copied recursively",
+                src.as_ref().display()
+            )));
+        }
+
+        if !options.will_overwrite() && std::fs::symlink_metadata(host.as_ref()).is_ok() {
+            return Err(Error::Other(format!(
+                "{}: File exists",
+                host.as_ref().display()
+            )));
+        }
+
+        if !options.will_follow_symlinks() {
+            if let Ok(target) = read_link_impl(device_root, src.as_ref()) {
+                let host_target = match host_link_target(
+                    device_root,
+                    Path::new(""),
+                    src.as_ref(),
+                    target.as_bytes(),
+                ) {
+                    Some(host_target) => host_target,
+                    None => {
+                        return Err(Error::Other(format!(
+                            "{}: couldn't resolve symlink target {:?}",
+                            src.as_ref().display(),
+                            target
+                        )))
+                    }
+                };
+
+                // Symlinks can't be created over existing files
+                let _ = std::fs::remove_file(host.as_ref());
+
+                if let Err(error) = std::os::unix::fs::symlink(host_target, host.as_ref()) {
+                    return Err(Error::Other(format!(
+                        "{}: {}",
+                        host.as_ref().display(),
+                        error
+                    )));
+                }
+
+                return Ok(0);
+            }
+        }
+
+        let source_stat = stat_impl(device_root, src.as_ref())?;
+
+        let mut source = self.open_file(src)?;
+
+        let mut destination = match std::fs::File::create(host.as_ref()) {
+            Ok(destination) => destination,
+            Err(error) => {
+                return Err(Error::Other(format!(
+                    "{}: {}",
+                    host.as_ref().display(),
+                    error
+                )))
+            }
+        };
+
+        let result = if options.is_sparse() {
+            copy_sparse_impl(&mut source, &mut destination)
+        } else {
+            std::io::copy(&mut source, &mut destination)
+        };
+
+        let copied = match result {
+            Ok(copied) => copied,
+            Err(error) => return Err(Error::Other(format!("failed to copy to host: {}", error))),
+        };
+
+        if options.will_preserve() {
+            let permissions = std::fs::Permissions::from_mode(source_stat.mode & 0o7777);
+
+            if let Err(error) = destination.set_permissions(permissions) {
+                return Err(Error::Other(format!(
+                    "{}: {}",
+                    host.as_ref().display(),
+                    error
+                )));
+            }
+
+            let mut times = std::fs::FileTimes::new();
+
+            if let Some(accessed) = DateTime::from(source_stat.atime).to_system_time() {
+                times = times.set_accessed(accessed);
+            }
+
+            if let Some(modified) = DateTime::from(source_stat.mtime).to_system_time() {
+                times = times.set_modified(modified);
+            }
+
+            if let Err(error) = destination.set_times(times) {
+                return Err(Error::Other(format!(
+                    "{}: {}",
+                    host.as_ref().display(),
+                    error
+                )));
+            }
+        }
+
+        if let Err(error) = destination.sync_all() {
+            return Err(Error::Other(format!(
+                "{}: {}",
+                host.as_ref().display(),
+                error
+            )));
+        }
+
+        Ok(copied)
+    }
+
+    /// Copies a directory and everything within it from the device onto the
+    /// host's filesystem, creating `host_dest` and any of its parents which
+    /// are missing, and replacing any files which already exist.
+    ///
+    /// Symlinks are recreated on the host pointing at the same entry, with
+    /// their targets rewritten to be relative to the link, as the device's
+    /// own paths mean nothing to the host. Symlinks to entries outside of
+    /// `src` would dangle on the host, so they're skipped with a warning.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `src` is not a directory, or if
+    /// any entry within it could not be read, or written to the host.
+    fn copy_dir_to_host<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        src: P,
+        host_dest: Q,
+    ) -> Result<(), Error> {
+        if !is_dir_impl(self.get_device_root(), src.as_ref()) {
+            return Err(Error::Other(format!(
+                "{}: Not a directory",
+                src.as_ref().display()
+            )));
+        }
+
+        if let Err(error) = std::fs::create_dir_all(host_dest.as_ref()) {
+            return Err(Error::Other(format!(
+                "{}: {}",
+                host_dest.as_ref().display(),
+                error
+            )));
+        }
+
+        for entry in self.walk_dir(src.as_ref())? {
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+
+            let host_path = match path.strip_prefix(src.as_ref()) {
+                Ok(relative) => host_dest.as_ref().join(relative),
+                Err(_) => {
+                    return Err(Error::Other(format!(
+                        "{}: Not within {}",
+                        path.display(),
+                        src.as_ref().display()
+                    )))
+                }
+            };
+
+            if file_type.is_symlink() {
+                let target = read_link_impl(self.get_device_root(), &path)?;
+
+                let host_target = match host_link_target(
+                    self.get_device_root(),
+                    src.as_ref(),
+                    &path,
+                    target.as_bytes(),
+                ) {
+                    Some(host_target) => host_target,
+                    None => {
+                        warn!(
+                            "Skipping symlink {}, as its target {:?} is outside of {}",
+                            host_path.display(),
+                            target,
+                            src.as_ref().display()
+                        );
+                        continue;
+                    }
+                };
+
+                if let Err(error) = std::os::unix::fs::symlink(host_target, &host_path) {
+                    warn!(
+                        "Skipping symlink {}, as it couldn't be recreated: {}",
+                        host_path.display(),
+                        error
+                    );
+                }
+            } else if file_type.is_dir() {
+                if let Err(error) = std::fs::create_dir_all(&host_path) {
+                    return Err(Error::Other(format!("{}: {}", host_path.display(), error)));
+                }
+            } else {
+                self.copy_to_host(&path, host_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether two paths refer to the same file, following symlinks,
+    /// such as to avoid copying a file onto itself.
+    ///
+    /// The drivers don't expose inode numbers, but PFS doesn't support hard
+    /// links either, so two paths refer to the same file exactly when they
+    /// resolve to the same path once symlinks, `.` and `..` are resolved.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either path does not exist, or
+    /// if resolving either of them follows too many symlinks.
+    fn same_file<P: AsRef<Path>, Q: AsRef<Path>>(&self, a: P, b: Q) -> Result<bool, Error> {
+        Ok(canonicalize_impl(self.get_device_root(), a.as_ref())?
+            == canonicalize_impl(self.get_device_root(), b.as_ref())?)
+    }
+
+    /// Changes the attribute bits of a file, as returned by
+    /// [`Metadata::attributes`].
+    ///
+    /// [`Metadata::attributes`]: ../fs/struct.Metadata.html#method.attributes
+    fn set_attributes<P: AsRef<Path>>(&self, path: P, attributes: u32) -> Result<(), Error> {
+        let mut stat: ps2hdd_sys::iox_stat_t = unsafe { std::mem::zeroed() };
+        stat.attr = attributes;
+
+        chstat_impl(
+            self.get_device_root(),
+            path.as_ref(),
+            &mut stat,
+            ps2hdd_sys::FIO_CST_ATTR,
+            "failed to set attributes",
+        )
+    }
+
+    /// Changes the private data carried in a file's stat, as returned by
+    /// [`Metadata::private_data`].
+    ///
+    /// The driver decides which parts of the data it stores; PFS only keeps
+    /// the first eight bytes, which hold the owner's user and group IDs.
+    ///
+    /// [`Metadata::private_data`]: ../fs/struct.Metadata.html#method.private_data
+    fn set_private_data<P: AsRef<Path>>(
+        &self,
+        path: P,
+        data: &[u8; PRIVATE_DATA_SIZE],
+    ) -> Result<(), Error> {
+        let mut stat: ps2hdd_sys::iox_stat_t = unsafe { std::mem::zeroed() };
+        crate::fs::set_private_data(&mut stat, data);
+
+        chstat_impl(
+            self.get_device_root(),
+            path.as_ref(),
+            &mut stat,
+            ps2hdd_sys::FIO_CST_PRVT,
+            "failed to set private data",
+        )
+    }
+
+    /// Rename a file or directory to a new name, replacing the original file if
+    /// `to` already exists.
+    ///
+    /// As with [`std::fs::rename`] on Unix, a file can only replace another
+    /// file, and a directory can only replace an empty directory. Renaming a
+    /// path onto itself does nothing.
+    ///
+    /// This will fail if the parent directory of `to` does not exist; see
+    /// [`rename_create_parents`](#method.rename_create_parents) to create it.
+    ///
+    /// [`std::fs::rename`]: https://doc.rust-lang.org/std/fs/fn.rename.html
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<(), Error> {
+        rename_impl(self.get_device_root(), from.as_ref(), to.as_ref())
+    }
+
+    /// Rename a file or directory to a new name, first creating any of the
+    /// parent directories of `to` which are missing.
+    fn rename_create_parents<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        to: Q,
+    ) -> Result<(), Error> {
+        create_parents_impl(self.get_device_root(), to.as_ref())?;
+
+        self.rename(from, to)
+    }
+}
+
+#[derive(Debug)]
+pub struct PFS {
+    pub partition_name: String,
+}
+
+impl Driver for PFS {
+    fn get_device_root(&self) -> &str {
+        "pfs0:"
+    }
+
+    fn get_partition_name(&self) -> &str {
+        &self.partition_name
+    }
+}
+
+#[derive(Debug)]
+pub struct HDLFS {
+    pub partition_name: String,
+}
+
+impl Driver for HDLFS {
+    fn get_device_root(&self) -> &str {
+        "hdl0:"
+    }
+
+    fn get_partition_name(&self) -> &str {
+        &self.partition_name
+    }
+}
+
+/// Either of the mounted drivers, as returned by
+/// [`PS2HDD::mounted_driver`](../struct.PS2HDD.html#method.mounted_driver),
+/// for code which doesn't care which kind of file system it's working with.
+///
+/// `Driver`'s methods are generic, so it can't be used as a `dyn Driver`;
+/// this implements `Driver` by passing through to whichever it holds instead.
+#[derive(Copy, Clone, Debug)]
+pub enum MountedDriver<'a> {
+    PFS(&'a PFS),
+    HDLFS(&'a HDLFS),
+}
+
+impl Driver for MountedDriver<'_> {
+    fn get_device_root(&self) -> &str {
+        match self {
+            Self::PFS(pfs) => pfs.get_device_root(),
+            Self::HDLFS(hdlfs) => hdlfs.get_device_root(),
+        }
+    }
+
+    fn get_partition_name(&self) -> &str {
+        match self {
+            Self::PFS(pfs) => pfs.get_partition_name(),
+            Self::HDLFS(hdlfs) => hdlfs.get_partition_name(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partition_kind::FormattablePartitionKind;
+    use crate::PS2HDD;
+    use serial_test::serial;
+
+    // A disk image needs to be at least 6GB in size for APA to work
+    // more is better, and 20GB is the "normal" minimum, but this is
+    // just enough  to fit any of the minimum-size 128MB partitions in
+    static DEMO_FILE_SIZE: u64 = 6 * 1024 * 1024 * 1024;
+
+    fn get_directory_entry_names(dirents: Vec<DirEntry>) -> Vec<String> {
+        dirents
+            .iter()
+            .map(|dirent| {
+                dirent
+                    .file_name()
+                    .to_str()
+                    .expect("could not convert partition entry name")
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_mount_create_and_read_dir() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            pfs.partition_name, "TESTPART",
+            "Unexpected partition reference"
+        );
+
+        pfs.create_dir("testdir").expect("Could not create testdir");
+
+        let direntries = pfs.list_dir("/").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["testdir"],
+            "Unexpected directory list"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    fn device_paths_ignore_extra_slashes() {
+        for path in &[
+            "testdir",
+            "/testdir",
+            "testdir/",
+            "/testdir/",
+            "//testdir//",
+        ] {
+            assert_eq!(
+                device_path("pfs0:", Path::new(path)),
+                Ok(std::ffi::CString::new("pfs0:/testdir").unwrap()),
+                "unexpected device path for {:?}",
+                path
+            );
+        }
+
+        assert_eq!(
+            device_path("pfs0:", Path::new("a//b/")),
+            Ok(std::ffi::CString::new("pfs0:/a/b").unwrap())
+        );
+        assert_eq!(
+            device_path("pfs0:", Path::new("/")),
+            Ok(std::ffi::CString::new("pfs0:/").unwrap())
+        );
+        assert_eq!(
+            device_path("pfs0:", Path::new("")),
+            Ok(std::ffi::CString::new("pfs0:/").unwrap())
+        );
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_dir_entry_paths() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/b/c")
+            .expect("Could not create directories");
+
+        let entries = pfs.list_dir("/a").expect("Could not list directory");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path(), Path::new("/a/b"));
+
+        let entries = pfs.list_dir("a").expect("Could not list directory");
+        assert_eq!(entries[0].path(), Path::new("a/b"));
+
+        // Entry paths can be listed in turn
+        let entries = pfs
+            .list_dir(entries[0].path())
+            .expect("Could not list entry's path");
+        assert_eq!(entries[0].path(), Path::new("a/b/c"));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_slash_permutations() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let permutations = ["testdir", "/testdir", "testdir/", "/testdir/"];
+
+        for (index, create) in permutations.iter().enumerate() {
+            pfs.create_dir(create)
+                .unwrap_or_else(|message| panic!("Could not create {:?}: {}", create, message));
+            pfs.create_dir("testdir/child")
+                .expect("Could not create child directory");
+
+            for list in &permutations {
+                assert_eq!(
+                    pfs.list_dir(list).map(get_directory_entry_names),
+                    Ok(vec!["child".to_string()]),
+                    "Unexpected listing of {:?} created as {:?}",
+                    list,
+                    create
+                );
+            }
+
+            assert_eq!(
+                pfs.list_dir("/").map(get_directory_entry_names),
+                Ok(vec!["testdir".to_string()])
+            );
+
+            pfs.remove_dir("testdir/child/")
+                .expect("Could not remove child directory");
+
+            // Remove using each permutation in turn, rather than the one used
+            // to create the directory
+            let remove = permutations[(index + 1) % permutations.len()];
+
+            pfs.remove_dir(remove)
+                .unwrap_or_else(|message| panic!("Could not remove {:?}: {}", remove, message));
+
+            assert_eq!(
+                pfs.list_dir("/").map(get_directory_entry_names),
+                Ok(Vec::<String>::new())
+            );
+        }
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_mount_create_dir_all() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            pfs.partition_name, "TESTPART",
+            "Unexpected partition reference"
+        );
+
+        pfs.create_dir_all("a/b/c/d")
+            .expect("Could not create path");
+
+        let direntries = pfs.list_dir("/").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["a"],
+            "Unexpected directory list"
+        );
+
+        let direntries = pfs.list_dir("/a").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["b"],
+            "Unexpected directory list"
+        );
+
+        let direntries = pfs.list_dir("/a/b").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["c"],
+            "Unexpected directory list"
+        );
+
+        let direntries = pfs.list_dir("/a/b/c").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["d"],
+            "Unexpected directory list"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_try_create_dir() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(pfs.try_create_dir("/a"), Ok(true));
+        assert_eq!(pfs.try_create_dir("/a"), Ok(false));
+
+        assert!(
+            pfs.create_dir("/a").is_err(),
+            "Creating an existing directory strictly didn't return an error"
+        );
+
+        pfs.write_file("file.txt", b"file")
+            .expect("Could not write file.txt");
+
+        assert!(
+            pfs.try_create_dir("file.txt").is_err(),
+            "Creating a directory over a file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_create_dir_all_reported() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("/a").expect("Could not create path");
+
+        assert_eq!(
+            pfs.create_dir_all_reported("/a/b/c"),
+            Ok(vec![
+                std::path::PathBuf::from("/a/b"),
+                std::path::PathBuf::from("/a/b/c")
+            ])
+        );
+
+        assert_eq!(pfs.create_dir_all_reported("/a/b/c"), Ok(vec![]));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_walk_dir() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/b/c/d")
+            .expect("Could not create path");
+        pfs.create_dir("e").expect("Could not create e");
+
+        let direntries = pfs.walk_dir("/").expect("Could not walk directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["a", "b", "c", "d", "e"],
+            "Unexpected directory walk"
+        );
+
+        let direntries = pfs.walk_dir("/a/b").expect("Could not walk directory");
+
+        assert!(
+            direntries
+                .iter()
+                .all(|entry| entry.partition() == Some("TESTPART")),
+            "Entries didn't record their partition"
+        );
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["c", "d"],
+            "Unexpected directory walk"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_total_entries() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/b/c/d")
+            .expect("Could not create path");
+        pfs.create_dir("e").expect("Could not create e");
+
+        assert_eq!(pfs.total_entries("/", false), Ok(2));
+        assert_eq!(pfs.total_entries("/", true), Ok(5));
+        assert_eq!(pfs.total_entries("/a/b", true), Ok(2));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_atomic() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("save.dat", b"original contents")
+            .expect("Could not write file");
+
+        // Leave too little space for the next write, so it's interrupted by
+        // the partition filling up partway through the temporary file
+        pfs.create_file_with_len("filler", 96 * 1024 * 1024)
+            .expect("Could not create filler file");
+
+        assert!(
+            pfs.write_atomic("save.dat", &vec![0xAA; 48 * 1024 * 1024])
+                .is_err(),
+            "Write larger than the free space succeeded"
+        );
+
+        assert_eq!(
+            pfs.read_file("save.dat").expect("Could not read file"),
+            b"original contents",
+            "Interrupted write changed the original file"
+        );
+
+        let mut names =
+            get_directory_entry_names(pfs.list_dir("/").expect("Could not list directory"));
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec!["filler", "save.dat"],
+            "Interrupted write left a temporary file behind"
+        );
+
+        if let Err(message) = pfs.remove_file("filler") {
+            panic!(message);
+        }
+
+        pfs.write_atomic("save.dat", b"new contents")
+            .expect("Could not atomically write file");
+
+        assert_eq!(
+            pfs.read_file("save.dat").expect("Could not read file"),
+            b"new contents",
+            "Atomic write didn't replace the file"
+        );
+
+        let direntries = pfs.list_dir("/").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["save.dat"],
+            "Atomic write left a temporary file behind"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_list_dirs_and_files() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("a").expect("Could not create a");
+        pfs.create_dir("b").expect("Could not create b");
+        pfs.write_file("c.txt", b"c")
+            .expect("Could not write c.txt");
+
+        assert_eq!(
+            get_directory_entry_names(pfs.list_dirs("/").expect("Could not list directories")),
+            vec!["a", "b"],
+            "Unexpected directory listing"
+        );
+
+        assert_eq!(
+            get_directory_entry_names(pfs.list_files("/").expect("Could not list files")),
+            vec!["c.txt"],
+            "Unexpected file listing"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_copy() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("source.txt", b"source")
+            .expect("Could not write source.txt");
+        pfs.write_file("existing.txt", b"existing")
+            .expect("Could not write existing.txt");
+
+        // Give the source unusual permissions, to tell whether they're kept
+        let source = device_path("pfs0:", Path::new("source.txt")).expect("Invalid path");
+        let mut stat = stat_impl("pfs0:", Path::new("source.txt")).expect("Could not stat");
+        stat.mode = (stat.mode & ps2hdd_sys::FIO_S_IFMT) | 0o600;
+        assert!(
+            unsafe {
+                ps2hdd_sys::iomanx_chstat(source.as_ptr(), &mut stat, ps2hdd_sys::FIO_CST_MODE)
+            } >= 0,
+            "Could not change permissions"
+        );
+
+        let permissions = |name: &str| {
+            pfs.list_files("/")
+                .expect("Could not list files")
+                .iter()
+                .find(|entry| entry.file_name() == name)
+                .expect("File was missing")
+                .metadata()
+                .expect("Could not read metadata")
+                .permissions()
+        };
+
+        // Overwriting
+        assert!(
+            pfs.copy(
+                "source.txt",
+                "existing.txt",
+                &CopyOptions::new().overwrite(false)
+            )
+            .is_err(),
+            "Copying over an existing file didn't return an error"
+        );
+        assert_eq!(pfs.read_file("existing.txt"), Ok(b"existing".to_vec()));
+
+        assert_eq!(
+            pfs.copy("source.txt", "existing.txt", &CopyOptions::new()),
+            Ok(6)
+        );
+        assert_eq!(pfs.read_file("existing.txt"), Ok(b"source".to_vec()));
+
+        // Preserving
+        pfs.copy("source.txt", "kept.txt", &CopyOptions::new())
+            .expect("Could not copy");
+        assert_eq!(permissions("kept.txt"), 0o600);
+
+        pfs.copy(
+            "source.txt",
+            "fresh.txt",
+            &CopyOptions::new().preserve(false),
+        )
+        .expect("Could not copy");
+        assert_eq!(permissions("fresh.txt"), 0o644);
+
+        // Following symlinks
+        let link = device_path("pfs0:", Path::new("link")).expect("Invalid path");
+        let target = std::ffi::CString::new("pfs0:source.txt").expect("Invalid target");
+        assert!(
+            unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+            "Could not create symlink"
+        );
+
+        pfs.copy("link", "followed.txt", &CopyOptions::new())
+            .expect("Could not copy");
+        assert_eq!(pfs.read_file("followed.txt"), Ok(b"source".to_vec()));
+
+        assert_eq!(
+            pfs.copy("link", "link2", &CopyOptions::new().follow_symlinks(false)),
+            Ok(0)
+        );
+        assert_eq!(
+            read_link_impl("pfs0:", Path::new("link2")),
+            read_link_impl("pfs0:", Path::new("link"))
+        );
+
+        // Files larger than a single read are streamed across in full
+        let large: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        pfs.write_file("large.bin", &large)
+            .expect("Could not write large.bin");
+
+        assert_eq!(
+            pfs.copy("large.bin", "large2.bin", &CopyOptions::new()),
+            Ok(large.len() as u64)
+        );
+        assert!(
+            pfs.read_file("large2.bin") == Ok(large),
+            "Copied contents didn't match"
+        );
+
+        // Copying a file onto itself, directly or through a symlink, mustn't
+        // truncate it
+        assert!(pfs
+            .copy("source.txt", "source.txt", &CopyOptions::new())
+            .is_err());
+        assert!(pfs.copy("source.txt", "link", &CopyOptions::new()).is_err());
+        assert_eq!(pfs.read_file("source.txt"), Ok(b"source".to_vec()));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_same_file() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("dir").expect("Could not create dir");
+        pfs.write_file("a.txt", b"a")
+            .expect("Could not write a.txt");
+        pfs.write_file("b.txt", b"b")
+            .expect("Could not write b.txt");
+
+        for (target, link) in &[("pfs0:a.txt", "link"), ("pfs0:dir", "dir_link")] {
+            let link = device_path("pfs0:", Path::new(link)).expect("Invalid path");
+            let target = std::ffi::CString::new(*target).expect("Invalid target");
+            assert!(
+                unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+                "Could not create symlink"
+            );
+        }
+
+        assert_eq!(pfs.same_file("a.txt", "a.txt"), Ok(true));
+        assert_eq!(pfs.same_file("a.txt", "link"), Ok(true));
+        assert_eq!(pfs.same_file("a.txt", "dir_link/../a.txt"), Ok(true));
+        assert_eq!(pfs.same_file("a.txt", "b.txt"), Ok(false));
+        assert_eq!(pfs.same_file("link", "b.txt"), Ok(false));
+
+        // Links within a subdirectory, where a relative target and one from
+        // the root of the device name different entries
+        pfs.write_file("dir/a.txt", b"dir/a")
+            .expect("Could not write dir/a.txt");
+
+        for (target, link) in &[
+            ("../a.txt", "dir/up"),
+            ("a.txt", "dir/sibling"),
+            ("pfs0:/a.txt", "dir/from_root"),
+        ] {
+            let link = device_path("pfs0:", Path::new(link)).expect("Invalid path");
+            let target = std::ffi::CString::new(*target).expect("Invalid target");
+            assert!(
+                unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+                "Could not create symlink"
+            );
+        }
+
+        assert_eq!(pfs.same_file("a.txt", "dir/up"), Ok(true));
+        assert_eq!(pfs.same_file("dir/up", "dir/from_root"), Ok(true));
+        assert_eq!(pfs.same_file("dir/a.txt", "dir/sibling"), Ok(true));
+        assert_eq!(pfs.same_file("a.txt", "dir/sibling"), Ok(false));
+        assert_eq!(pfs.same_file("dir_link/up", "link"), Ok(true));
+        assert!(
+            pfs.same_file("a.txt", "missing.txt").is_err(),
+            "Comparing a missing file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_canonicalize_relative_links() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("APPS/OPL")
+            .expect("Could not create APPS/OPL");
+        pfs.write_file("APPS/title.cfg", b"title=Apps")
+            .expect("Could not write file");
+        pfs.write_file("title.cfg", b"title=Root")
+            .expect("Could not write file");
+
+        for (link, target) in &[
+            ("APPS/OPL/relative", "../title.cfg"),
+            ("APPS/current", "OPL"),
+        ] {
+            let link = device_path("pfs0:", Path::new(link)).expect("Invalid path");
+            let target = std::ffi::CString::new(*target).expect("Invalid target");
+            assert!(
+                unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+                "Could not create symlink"
+            );
+        }
+
+        // Relative targets resolve against the link's own directory, not the
+        // root of the device, including through a linked directory
+        for path in &["APPS/OPL/relative", "APPS/current/relative"] {
+            assert_eq!(
+                canonicalize_impl("pfs0:", Path::new(path)),
+                Ok(std::path::PathBuf::from("APPS/title.cfg"))
+            );
+        }
+
+        assert_eq!(
+            resolve_link_target("pfs0:", Path::new("APPS/OPL/relative"), b"../title.cfg"),
+            std::path::PathBuf::from("APPS/title.cfg")
+        );
+        assert_eq!(
+            host_link_target(
+                "pfs0:",
+                Path::new("APPS"),
+                Path::new("APPS/OPL/relative"),
+                b"../title.cfg"
+            ),
+            Some(std::path::PathBuf::from("../title.cfg"))
+        );
+
+        // A copied link is rewritten to point at the same entry from its new
+        // directory
+        assert_eq!(
+            pfs.copy(
+                "APPS/OPL/relative",
+                "moved",
+                &CopyOptions::new().follow_symlinks(false)
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            read_link_impl("pfs0:", Path::new("moved"))
+                .expect("Could not read symlink")
+                .as_bytes(),
+            b"APPS/title.cfg"
+        );
+        assert_eq!(pfs.read_file("moved"), Ok(b"title=Apps".to_vec()));
+        assert_eq!(pfs.same_file("moved", "APPS/OPL/relative"), Ok(true));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_buffered_files() {
+        use std::io::{BufRead, Write};
+
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let mut writer = pfs
+            .open_buffered_writer("lines.txt")
+            .expect("Could not create lines.txt");
+
+        for line in 0..1000 {
+            writeln!(writer, "line {}", line).expect("Could not write line");
+        }
+
+        writer.flush().expect("Could not flush lines.txt");
+        drop(writer);
+
+        let reader = pfs
+            .open_buffered_reader("lines.txt")
+            .expect("Could not open lines.txt");
+
+        let lines: Vec<String> = reader
+            .lines()
+            .collect::<Result<_, _>>()
+            .expect("Could not read lines");
+
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(lines[0], "line 0");
+        assert_eq!(lines[999], "line 999");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    // Run with `cargo test -- --ignored --nocapture pfs_buffered_read_timing`
+    // to compare the two
+    #[test]
+    #[ignore]
+    #[serial(atad_device_path)]
+    fn pfs_buffered_read_timing() {
+        use std::io::{BufRead, Write};
+
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let mut writer = pfs
+            .open_buffered_writer("lines.txt")
+            .expect("Could not create lines.txt");
+
+        for line in 0..20_000 {
+            writeln!(writer, "line {}", line).expect("Could not write line");
+        }
+
+        writer.flush().expect("Could not flush lines.txt");
+        drop(writer);
+
+        let count_lines = |reader: &mut dyn BufRead| {
+            let mut lines = 0;
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).expect("Could not read line") > 0 {
+                lines += 1;
+                line.clear();
+            }
+
+            lines
+        };
+
+        // A one-byte buffer makes every byte its own read through the driver,
+        // just as reading the bare file a byte at a time would
+        let mut unbuffered = std::io::BufReader::with_capacity(
+            1,
+            pfs.open_file("lines.txt")
+                .expect("Could not open lines.txt"),
+        );
+        let started = std::time::Instant::now();
+        assert_eq!(count_lines(&mut unbuffered), 20_000);
+        let unbuffered_time = started.elapsed();
+
+        let mut buffered = pfs
+            .open_buffered_reader("lines.txt")
+            .expect("Could not open lines.txt");
+        let started = std::time::Instant::now();
+        assert_eq!(count_lines(&mut buffered), 20_000);
+        let buffered_time = started.elapsed();
+
+        println!(
+            "Reading 20000 lines took {:?} unbuffered, and {:?} buffered",
+            unbuffered_time, buffered_time
+        );
+
+        assert!(
+            buffered_time < unbuffered_time,
+            "Buffered reads weren't faster than unbuffered ones"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_open_directory_as_file() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("somedir").expect("Could not create somedir");
+
+        assert_eq!(
+            pfs.open_file("/somedir").unwrap_err(),
+            Error::Other("/somedir: Is a directory".to_string())
+        );
+        assert_eq!(
+            pfs.create_file("somedir").unwrap_err(),
+            Error::Other("somedir: Is a directory".to_string())
+        );
+        assert_eq!(
+            pfs.read_file("somedir"),
+            Err(Error::Other("somedir: Is a directory".to_string()))
+        );
+        assert_eq!(
+            pfs.write_file("somedir", b"contents"),
+            Err(Error::Other("somedir: Is a directory".to_string()))
+        );
+
+        assert!(
+            pfs.list_dir("somedir").is_ok(),
+            "Could not list directory after trying to open it as a file"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_read_dir_page() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("paged").expect("Could not create directory");
+
+        let files = (0..250).map(|index| (format!("paged/{}.txt", index), b"page"));
+        assert_eq!(pfs.write_files(files), Ok(250));
+
+        let mut paged = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page = pfs
+                .read_dir_page("paged", offset, 64)
+                .expect("Could not read page");
+
+            assert!(page.len() <= 64, "Page was larger than the limit");
+
+            if page.is_empty() {
+                break;
+            }
+
+            offset += page.len();
+            paged.append(&mut get_directory_entry_names(page));
+        }
+
+        let listed = get_directory_entry_names(pfs.list_dir("paged").expect("Could not list"));
+
+        assert_eq!(paged.len(), 250);
+        assert_eq!(paged, listed);
+
+        assert_eq!(
+            pfs.read_dir_page("paged", 1000, 64).map(|page| page.len()),
+            Ok(0)
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_summarize_dir() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("mixed/x/nested")
+            .expect("Could not create directories");
+
+        for name in &["mixed/a.txt", "mixed/b.txt", "mixed/c.txt"] {
+            pfs.write_file(name, b"file").expect("Could not write file");
+        }
+
+        pfs.create_dir("mixed/y")
+            .expect("Could not create directory");
+
+        let link = device_path("pfs0:", Path::new("mixed/link")).expect("Invalid path");
+        let target = std::ffi::CString::new("pfs0:mixed/a.txt").expect("Invalid target");
+        assert!(
+            unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+            "Could not create symlink"
+        );
+
+        let summary = pfs.summarize_dir("mixed").expect("Could not summarize");
+
+        assert_eq!(
+            summary,
+            DirSummary {
+                files: 3,
+                dirs: 2,
+                symlinks: 1,
+                other: 0
+            }
+        );
+        assert_eq!(summary.total(), 6);
+
+        assert_eq!(
+            pfs.summarize_dir("mixed/y"),
+            Ok(DirSummary::default()),
+            "Empty directory had entries"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_verified() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let contents: Vec<u8> = (0..100_000u32).map(|index| index as u8).collect();
+
+        if let Err(message) = pfs.write_verified("save.bin", &contents) {
+            panic!(message);
+        }
+
+        assert_eq!(pfs.read_file("save.bin"), Ok(contents.clone()));
+
+        // Simulate corruption by verifying against different contents
+        let mut corrupted = contents.clone();
+        corrupted[1234] ^= 0xff;
+
+        assert_eq!(
+            verify_contents_impl(pfs.get_device_root(), Path::new("save.bin"), &corrupted),
+            Err(Error::Other(
+                "save.bin: verification failed; contents differ at byte 1234".to_string()
+            ))
+        );
+        assert_eq!(
+            verify_contents_impl(
+                pfs.get_device_root(),
+                Path::new("save.bin"),
+                &contents[..1000]
+            ),
+            Err(Error::Other(
+                "save.bin: verification failed; read back 100000 bytes, expected 1000".to_string()
+            ))
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_files() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("many").expect("Could not create directory");
+
+        let files = (0..1000).map(|index| {
+            (
+                format!("many/{}.txt", index),
+                format!("file number {}", index),
+            )
+        });
+
+        assert_eq!(pfs.write_files(files), Ok(1000));
+
+        assert_eq!(
+            pfs.total_entries("many", false),
+            Ok(1000),
+            "Not every file was written"
+        );
+        assert_eq!(
+            pfs.read_file("many/567.txt"),
+            Ok(b"file number 567".to_vec())
+        );
+
+        let failing = vec![
+            ("before.txt", &b"before"[..]),
+            ("missing/during.txt", &b"during"[..]),
+            ("after.txt", &b"after"[..]),
+        ];
+
+        assert!(
+            pfs.write_files(failing).is_err(),
+            "Writing into a missing directory didn't return an error"
+        );
+        assert_eq!(pfs.read_file("before.txt"), Ok(b"before".to_vec()));
+        assert!(
+            pfs.read_file("after.txt").is_err(),
+            "Files after a failure were still written"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_vectored() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let header = b"HEADER";
+        let body = vec![0x42u8; READ_BUFFER_SIZE + 1];
+
+        pfs.write_vectored(
+            "file.bin",
+            &[std::io::IoSlice::new(header), std::io::IoSlice::new(&body)],
+        )
+        .expect("Could not write file.bin");
+
+        assert_eq!(
+            pfs.read_file("file.bin"),
+            Ok([&header[..], &body[..]].concat())
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_read_file_round_trip() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("empty.bin", b"")
+            .expect("Could not write empty.bin");
+        assert_eq!(pfs.read_file("empty.bin"), Ok(Vec::new()));
+
+        // Spans several reads, the last of them partial
+        let contents: Vec<u8> = (0..READ_BUFFER_SIZE * 7 / 2)
+            .map(|index| (index % 251) as u8)
+            .collect();
+
+        pfs.write_file("large.bin", &contents)
+            .expect("Could not write large.bin");
+        assert_eq!(pfs.read_file("large.bin"), Ok(contents));
+
+        assert!(
+            pfs.read_file("missing.bin").is_err(),
+            "Reading a missing file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_file_round_trip() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let contents: Vec<u8> = (0..1024 * 1024).map(|index| (index % 253) as u8).collect();
+
+        pfs.write_file("megabyte.bin", &contents)
+            .expect("Could not write megabyte.bin");
+        assert_eq!(pfs.read_file("megabyte.bin"), Ok(contents));
+
+        // Rewriting truncates the previous, longer contents
+        pfs.write_file("megabyte.bin", b"short")
+            .expect("Could not rewrite megabyte.bin");
+        assert_eq!(pfs.read_file("megabyte.bin"), Ok(b"short".to_vec()));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_remove_file() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("doomed.txt", b"doomed")
+            .expect("Could not write file");
+        pfs.write_file("kept.txt", b"kept")
+            .expect("Could not write file");
+        pfs.create_dir("somedir")
+            .expect("Could not create directory");
+
+        if let Err(message) = pfs.remove_file("doomed.txt") {
+            panic!(message);
+        }
+
+        let mut names = get_directory_entry_names(pfs.list_dir("/").expect("Could not list"));
+        names.sort();
+        assert_eq!(names, vec!["kept.txt", "somedir"]);
+
+        assert_eq!(
+            pfs.remove_file("somedir"),
+            Err(Error::Other("somedir: Is a directory".to_string()))
+        );
+        assert!(
+            pfs.remove_file("doomed.txt").is_err(),
+            "Removing a missing file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_remove_dir_all() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/b/c/d")
+            .expect("Could not create directories");
+
+        for dir in &["a", "a/b", "a/b/c", "a/b/c/d"] {
+            pfs.write_file(format!("{}/file.txt", dir), b"file")
+                .expect("Could not write file");
+        }
+
+        // A symlink out of the tree, whose target must survive
+        pfs.create_dir("outside")
+            .expect("Could not create directory");
+        pfs.write_file("outside/kept.txt", b"kept")
+            .expect("Could not write file");
+
+        let link = device_path("pfs0:", Path::new("a/b/link")).expect("Invalid path");
+        let target = std::ffi::CString::new("pfs0:outside").expect("Invalid target");
+        assert!(
+            unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+            "Could not create symlink"
+        );
+
+        if let Err(message) = pfs.remove_dir_all("a") {
+            panic!(message);
+        }
+
+        assert_eq!(
+            pfs.list_dir("/").map(get_directory_entry_names),
+            Ok(vec!["outside".to_string()])
+        );
+        assert_eq!(pfs.read_file("outside/kept.txt"), Ok(b"kept".to_vec()));
+
+        assert!(
+            pfs.remove_dir_all("a").is_err(),
+            "Removing a missing directory didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_stat() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("somedir")
+            .expect("Could not create directory");
+        pfs.write_file("somedir/file.txt", b"twelve bytes")
+            .expect("Could not write file");
+
+        let metadata = pfs.stat("somedir/file.txt").expect("Could not stat file");
+
+        assert!(metadata.is_file());
+        assert!(!metadata.is_dir());
+        assert_eq!(metadata.len(), 12);
+        assert!(metadata.modified().is_some());
+
+        // Matches what listing the parent directory finds
+        let listed = pfs.list_dir("somedir").expect("Could not list directory");
+        assert_eq!(listed[0].metadata(), Ok(metadata));
+
+        let metadata = pfs.stat("somedir").expect("Could not stat directory");
+
+        assert!(metadata.is_dir());
+        assert!(!metadata.is_file());
+
+        assert!(
+            pfs.stat("missing.txt").is_err(),
+            "Statting a missing file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_modified_times() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir("fresh").expect("Could not create directory");
+
+        let find_entry = || {
+            pfs.list_dir("/")
+                .expect("Could not list directory")
+                .into_iter()
+                .find(|entry| entry.file_name() == "fresh")
+                .expect("Directory wasn't listed")
+        };
+
+        let modified = find_entry()
+            .modified()
+            .and_then(|modified| modified.to_system_time())
+            .expect("Modification time was invalid");
+
+        let now = std::time::SystemTime::now();
+        let difference = match now.duration_since(modified) {
+            Ok(difference) => difference,
+            Err(error) => error.duration(),
+        };
+
+        // The drivers take the time from the host's clock, in whichever time
+        // zone it's set to, so this can only check that a time was recorded
+        assert!(
+            difference < std::time::Duration::from_secs(24 * 60 * 60),
+            "Modification time {:?} is too far from now, {:?}",
+            modified,
+            now
+        );
+
+        // 2020-10-15 12:34:56 JST, stored through the driver and read back
+        let mut stat = stat_impl("pfs0:", Path::new("fresh")).expect("Could not stat directory");
+        stat.mtime = [0, 56, 34, 12, 15, 10, 0xe4, 0x07];
+
+        chstat_impl(
+            "pfs0:",
+            Path::new("fresh"),
+            &mut stat,
+            ps2hdd_sys::FIO_CST_MT,
+            "Could not set modification time",
+        )
+        .expect("Could not set modification time");
+
+        let modified = find_entry().modified();
+
+        assert_eq!(
+            modified,
+            Some(DateTime {
+                year: 2020,
+                month: 10,
+                day: 15,
+                hour: 12,
+                minute: 34,
+                second: 56
+            })
+        );
+
+        // 2020-10-15 03:34:56 UTC
+        assert_eq!(
+            modified.and_then(|modified| modified.to_system_time()),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_602_732_896))
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_stream_file() {
+        use std::io::{Seek, SeekFrom};
+
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let contents: Vec<u8> = (0..5 * 1024 * 1024)
+            .map(|index| (index % 241) as u8)
+            .collect();
+
+        pfs.write_file("stream.bin", &contents)
+            .expect("Could not write stream.bin");
+
+        let mut file = pfs.open_file("stream.bin").expect("Could not open file");
+
+        // Copied through std::io::copy's own small buffer, a piece at a time
+        let mut copied = Vec::new();
+        assert_eq!(
+            std::io::copy(&mut file, &mut copied).expect("Could not copy file"),
+            contents.len() as u64
+        );
+        assert!(copied == contents, "Streamed contents didn't match");
+
+        assert_eq!(
+            file.seek(SeekFrom::End(-1)).expect("Could not seek"),
+            contents.len() as u64 - 1
+        );
+
+        let mut last = Vec::new();
+        std::io::copy(&mut file, &mut last).expect("Could not copy file");
+        assert_eq!(last, vec![contents[contents.len() - 1]]);
+
+        drop(file);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_stream_writes() {
+        use std::io::Write;
+
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let mut file = pfs
+            .create_file("payload.elf")
+            .expect("Could not create file");
+        let mut expected = Vec::new();
+
+        for chunk in 0..64u8 {
+            let bytes = vec![chunk; 1000 + chunk as usize];
+            file.write_all(&bytes).expect("Could not write chunk");
+            expected.extend_from_slice(&bytes);
+
+            if chunk % 16 == 15 {
+                file.flush().expect("Could not flush");
+            }
+        }
+
+        if let Err(message) = file.close() {
+            panic!(message);
+        }
+
+        assert!(
+            pfs.read_file("payload.elf") == Ok(expected),
+            "Streamed contents didn't match"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_seek_positions() {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("digits.txt", b"0123456789")
+            .expect("Could not write digits.txt");
+
+        let mut file = pfs.open_file("digits.txt").expect("Could not open file");
+        let mut buf = [0u8; 3];
+
+        assert_eq!(file.stream_position().unwrap(), 0);
+
+        file.read_exact(&mut buf).expect("Could not read");
+        assert_eq!(&buf, b"012");
+        assert_eq!(file.stream_position().unwrap(), 3);
+
+        assert_eq!(file.seek(SeekFrom::Current(2)).unwrap(), 5);
+        file.read_exact(&mut buf).expect("Could not read");
+        assert_eq!(&buf, b"567");
+        assert_eq!(file.stream_position().unwrap(), 8);
+
+        assert_eq!(file.seek(SeekFrom::End(-4)).unwrap(), 6);
+        assert_eq!(file.stream_position().unwrap(), 6);
+
+        file.rewind().expect("Could not rewind");
+        assert_eq!(file.stream_position().unwrap(), 0);
+
+        file.read_exact(&mut buf).expect("Could not read");
+        assert_eq!(&buf, b"012");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_file_size() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        // Deliberately not a multiple of the sector size
+        let contents = vec![0x5au8; 12345];
+
+        pfs.write_file("sized.bin", &contents)
+            .expect("Could not write sized.bin");
+        pfs.write_file("empty.bin", b"")
+            .expect("Could not write empty.bin");
+
+        assert_eq!(pfs.file_size("sized.bin").unwrap(), 12345);
+        assert_eq!(pfs.file_size("empty.bin").unwrap(), 0);
+        assert!(
+            pfs.file_size("missing.bin").is_err(),
+            "Sizing a missing file didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_read_exact_at() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("header.bin", b"0123456789")
+            .expect("Could not write header.bin");
+
+        let mut buf = [0u8; 4];
+
+        pfs.read_exact_at("header.bin", 6, &mut buf)
+            .expect("Could not read the end of header.bin");
+        assert_eq!(&buf, b"6789");
+
+        assert_eq!(
+            pfs.read_exact_at("header.bin", 8, &mut buf),
+            Err(Error::Other(
+                "unexpected end of file reading 4 bytes at offset 8".to_string()
+            )),
+            "reading past the end of the file didn't fail"
+        );
+        assert_eq!(
+            pfs.read_exact_at("header.bin", 20, &mut buf),
+            Err(Error::Other(
+                "unexpected end of file reading 4 bytes at offset 20".to_string()
+            )),
+            "reading from past the end of the file didn't fail"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_create_file_with_len() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("container.bin", b"previous contents")
+            .expect("Could not write container.bin");
+
+        pfs.create_file_with_len("container.bin", 1024 * 1024 + 3)
+            .expect("Could not create container.bin");
+
+        assert_eq!(pfs.file_size("container.bin"), Ok(1024 * 1024 + 3));
+        assert_eq!(
+            pfs.read_file("container.bin"),
+            Ok(vec![0u8; 1024 * 1024 + 3]),
+            "File wasn't zero-filled"
+        );
+
+        pfs.create_file_with_len("empty.bin", 0)
+            .expect("Could not create empty.bin");
+
+        assert_eq!(pfs.file_size("empty.bin"), Ok(0));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_copy_range() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let contents: Vec<u8> = (0..4096u32).map(|index| (index % 251) as u8).collect();
+
+        pfs.write_file("source.bin", &contents)
+            .expect("Could not write source.bin");
+
+        assert_eq!(
+            pfs.copy_range("source.bin", 1536, 1024, "middle.bin", 0),
+            Ok(1024)
+        );
+        assert_eq!(
+            pfs.read_file("middle.bin"),
+            Ok(contents[1536..2560].to_vec())
+        );
+
+        // Copying past the end of the destination pads it with zeroes
+        assert_eq!(
+            pfs.copy_range("source.bin", 0, 16, "middle.bin", 2048),
+            Ok(16)
+        );
+
+        let mut expected = contents[1536..2560].to_vec();
+        expected.resize(2048, 0);
+        expected.extend_from_slice(&contents[..16]);
+
+        assert_eq!(pfs.read_file("middle.bin"), Ok(expected));
+
+        assert!(
+            pfs.copy_range("source.bin", 4000, 1024, "past.bin", 0)
+                .is_err(),
+            "Copying past the end of the source didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_attributes_and_private_data() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("file.txt", b"file")
+            .expect("Could not write file.txt");
+
+        let mut data = [0u8; PRIVATE_DATA_SIZE];
+        data[0..4].copy_from_slice(&1000u32.to_le_bytes());
+        data[4..8].copy_from_slice(&100u32.to_le_bytes());
+
+        pfs.set_attributes("file.txt", 0x0123)
+            .expect("Could not set attributes");
+        pfs.set_private_data("file.txt", &data)
+            .expect("Could not set private data");
+
+        let metadata = pfs
+            .list_files("/")
+            .expect("Could not list files")
+            .first()
+            .expect("File was missing")
+            .metadata()
+            .expect("Could not read metadata");
+
+        assert_eq!(metadata.attributes(), 0x0123);
+        assert_eq!(metadata.private_data()[0..8], data[0..8]);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_keeps_permissions() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let permissions = |name: &str| {
+            stat_impl("pfs0:", Path::new(name))
+                .expect("Could not stat")
+                .mode
+                & 0o7777
+        };
+
+        let make_private = |name: &str| {
+            let mut stat = stat_impl("pfs0:", Path::new(name)).expect("Could not stat");
+            stat.mode = (stat.mode & ps2hdd_sys::FIO_S_IFMT) | 0o600;
+
+            chstat_impl(
+                "pfs0:",
+                Path::new(name),
+                &mut stat,
+                ps2hdd_sys::FIO_CST_MODE,
+                "Could not change permissions",
+            )
+            .expect("Could not change permissions");
+        };
+
+        pfs.write_file("config.txt", b"first")
+            .expect("Could not write config.txt");
+        make_private("config.txt");
+
+        pfs.write_file("config.txt", b"second")
+            .expect("Could not overwrite config.txt");
+        assert_eq!(permissions("config.txt"), 0o600);
+
+        pfs.write_atomic("config.txt", b"third")
+            .expect("Could not atomically overwrite config.txt");
+        assert_eq!(permissions("config.txt"), 0o600);
+        assert_eq!(pfs.read_file("config.txt"), Ok(b"third".to_vec()));
+
+        pfs.write_file_with(
+            "config.txt",
+            b"fourth",
+            &WriteOptions::new().atomic(true).reset_permissions(true),
+        )
+        .expect("Could not overwrite config.txt");
+        assert_eq!(permissions("config.txt"), 0o644);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_write_in_place() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let partition_start = match ps2hdd.read_apa_header("TESTPART") {
+            Ok((header, _)) => header.start as u64 * crate::apa::SECTOR_SIZE,
+            Err(message) => panic!(message),
+        };
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        // Finds where on the disk a file's contents, starting with a unique
+        // marker, were written
+        let find_on_disk = |marker: &[u8]| -> Option<usize> {
+            use std::io::{Read, Seek, SeekFrom};
+
+            sync_impl("pfs0:").expect("Could not sync device");
+
+            let mut file = std::fs::File::open(demo_file_path).expect("couldn't open demo file");
+            let mut partition = vec![0u8; 128 * 1024 * 1024];
+
+            file.seek(SeekFrom::Start(partition_start))
+                .expect("couldn't seek demo file");
+            file.read_exact(&mut partition)
+                .expect("couldn't read demo file");
+
+            partition
+                .windows(marker.len())
+                .position(|window| window == marker)
+        };
+
+        let contents = |marker: &[u8], fill: u8, len: usize| {
+            let mut contents = vec![fill; len];
+            contents[..marker.len()].copy_from_slice(marker);
+            contents
+        };
+
+        let options = WriteOptions::new().in_place(true);
+
+        let first = contents(b"IN-PLACE-FIRST", 1, 65536);
+        let second = contents(b"IN-PLACE-SECOND", 2, 65536);
+
+        pfs.write_file_with("save.bin", &first, &options)
+            .expect("Could not write save.bin");
+
+        let first_offset = find_on_disk(b"IN-PLACE-FIRST").expect("Contents weren't on the disk");
+
+        pfs.write_file_with("save.bin", &second, &options)
+            .expect("Could not rewrite save.bin");
+
+        assert_eq!(pfs.read_file("save.bin"), Ok(second));
+
+        // Rewriting the file reused its allocation, rather than being given
+        // new blocks elsewhere
+        assert_eq!(
+            find_on_disk(b"IN-PLACE-SECOND"),
+            Some(first_offset),
+            "Rewriting in place moved the file's contents"
+        );
+
+        pfs.write_file_with("save.bin", &[3u8; 100000], &options)
+            .expect("Could not grow save.bin");
+
+        assert_eq!(pfs.read_file("save.bin"), Ok(vec![3u8; 100000]));
+
+        // Shrinking the file has to fall back to truncating it
+        pfs.write_file_with("save.bin", &[4u8; 10], &options)
+            .expect("Could not shrink save.bin");
+
+        assert_eq!(pfs.read_file("save.bin"), Ok(vec![4u8; 10]));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_rename_overwrites() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.write_file("new.txt", b"new")
+            .expect("Could not write new.txt");
+        pfs.write_file("old.txt", b"old")
+            .expect("Could not write old.txt");
+
+        pfs.rename("new.txt", "old.txt")
+            .expect("Could not rename over an existing file");
+
+        assert_eq!(pfs.read_file("old.txt"), Ok(b"new".to_vec()));
+        assert!(
+            stat_impl("pfs0:", Path::new("new.txt")).is_err(),
+            "Renamed file was left behind"
+        );
+
+        pfs.rename("old.txt", "old.txt")
+            .expect("Could not rename a file onto itself");
+
+        assert_eq!(pfs.read_file("old.txt"), Ok(b"new".to_vec()));
+
+        pfs.create_dir("empty").expect("Could not create directory");
+        pfs.create_dir("full").expect("Could not create directory");
+        pfs.create_dir("moved").expect("Could not create directory");
+        pfs.write_file("full/kept.txt", b"kept")
+            .expect("Could not write full/kept.txt");
+
+        assert_eq!(
+            pfs.rename("moved", "full"),
+            Err(Error::Other("full: Directory not empty".to_string()))
+        );
+        assert_eq!(pfs.read_file("full/kept.txt"), Ok(b"kept".to_vec()));
+
+        assert_eq!(
+            pfs.rename("old.txt", "empty"),
+            Err(Error::Other("empty: Is a directory".to_string()))
+        );
+        assert_eq!(
+            pfs.rename("moved", "old.txt"),
+            Err(Error::Other("old.txt: Not a directory".to_string()))
+        );
+
+        pfs.rename("moved", "empty")
+            .expect("Could not rename over an empty directory");
+
+        assert!(
+            is_dir_impl("pfs0:", Path::new("empty")),
+            "Renamed directory is missing"
+        );
+        assert!(
+            stat_impl("pfs0:", Path::new("moved")).is_err(),
+            "Renamed directory was left behind"
+        );
+
+        pfs.rename("full", "renamed")
+            .expect("Could not rename a non-empty directory");
+
+        assert_eq!(pfs.read_file("renamed/kept.txt"), Ok(b"kept".to_vec()));
+        assert!(
+            stat_impl("pfs0:", Path::new("full")).is_err(),
+            "Renamed directory was left behind"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_rename_create_parents() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/x").expect("Could not create path");
+        pfs.create_dir_all("a/y").expect("Could not create path");
+
+        assert!(
+            pfs.rename("a/y", "d/e/y").is_err(),
+            "Strict rename into a missing directory should fail"
+        );
+
+        pfs.rename_create_parents("a/x", "b/c/x")
+            .expect("Could not rename with missing parents");
+
+        let direntries = pfs.list_dir("/a").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["y"],
+            "Unexpected directory list"
+        );
+
+        let direntries = pfs.list_dir("/b/c").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["x"],
+            "Unexpected directory list"
+        );
+
+        // Parents which already exist are left alone
+        pfs.rename_create_parents("a/y", "b/c/y")
+            .expect("Could not rename into existing parents");
+
+        let direntries = pfs.list_dir("/b/c").expect("Could not list directory");
+
+        assert_eq!(
+            get_directory_entry_names(direntries),
+            vec!["x", "y"],
+            "Unexpected directory list"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_copy_from_host() {
+        let demo_file_path = "hdd.img";
+        let host_file_path = "copy_from_host.bin";
+        let empty_host_file_path = "copy_from_host.empty";
+
+        let contents: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        std::fs::write(host_file_path, &contents).expect("could not write host file");
+        std::fs::write(empty_host_file_path, b"").expect("could not write host file");
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
 
-    /// List the entries within a directory.
-    ///
-    /// Note that unlike `std::fs::read_dir` or the like, which return an
-    /// iterator, all entries are fetched upfront, due to the underlying
-    /// driver involving internal state we can't fully rely on.
-    fn list_dir<P: std::fmt::Display + AsRef<Path>>(
-        &self,
-        path: P,
-    ) -> Result<Vec<DirEntry>, String> {
-        let cPath = match std::ffi::CString::new(format!("{}/{}", self.get_device_root(), path)) {
-            Ok(cPath) => cPath,
-            Err(error) => return Err(format!("couldn't convert path: {}", error)),
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
         };
 
-        let mut temp_dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
-        let mut dirents = Vec::new();
-
-        let directory_handle = ok_on_nonnegative_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_dopen(cPath.as_ptr()) },
-            "Failed to list directory",
-        )?;
+        assert_eq!(
+            pfs.copy_from_host(host_file_path, "payload.bin"),
+            Ok(contents.len() as u64)
+        );
+        assert!(
+            pfs.read_file("payload.bin") == Ok(contents.clone()),
+            "Copied contents didn't match"
+        );
 
-        while {
-            let result = unsafe { ps2hdd_sys::iomanx_dread(directory_handle, &mut temp_dirent) };
+        // Copying over an existing file replaces it entirely
+        assert_eq!(
+            pfs.copy_from_host(empty_host_file_path, "payload.bin"),
+            Ok(0)
+        );
+        assert_eq!(pfs.read_file("payload.bin"), Ok(Vec::new()));
 
-            if result < 0 {
-                match unsafe { std::ffi::CStr::from_ptr(temp_dirent.name.as_ptr()) }.to_str() {
-                    Ok(name) => {
-                        return Err(format!("Failed to list directories: {} {}", result, name))
-                    }
-                    Err(error) => {
-                        return Err(format!(
-                            "could not convert the directory name to a String: {}",
-                            error
-                        ))
-                    }
-                }
-            }
+        assert!(pfs
+            .copy_from_host(host_file_path, "APPS/OPL/OPNPS2LD.ELF")
+            .is_err());
+        assert_eq!(
+            pfs.copy_from_host_create_parents(host_file_path, "/APPS/OPL/OPNPS2LD.ELF"),
+            Ok(contents.len() as u64)
+        );
+        assert!(
+            pfs.read_file("APPS/OPL/OPNPS2LD.ELF") == Ok(contents),
+            "Copied contents didn't match"
+        );
 
-            result > 0
-        } {
-            match unsafe { std::ffi::CStr::from_ptr(temp_dirent.name.as_ptr()) }.to_str() {
-                Ok(name) => {
-                    // Based on Rust's unix ReadDir implementation:
-                    // https://github.com/rust-lang/rust/blob/19e1aac6ea9879c6d10eed7106b3bc883e5bf9a5/library/std/src/sys/unix/fs.rs#L488
-                    if name != "." && name != ".." {
-                        dirents.push(DirEntry::new(
-                            temp_dirent.clone(),
-                            path.as_ref().to_path_buf(),
-                        ));
-                    }
-                }
-                Err(error) => {
-                    return Err(format!(
-                        "could not convert the directory name to a String: {}",
-                        error
-                    ))
-                }
-            }
+        if let Err(message) = pfs.create_dir("somedir") {
+            panic!(message);
         }
 
-        ok_on_zero_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_close(directory_handle) },
-            "Failed to close directory handle",
-        )?;
+        assert!(pfs.copy_from_host(host_file_path, "somedir").is_err());
+        assert!(pfs
+            .copy_from_host("no_such_host_file", "missing.bin")
+            .is_err());
+        assert!(pfs.stat("missing.bin").is_err());
 
-        Ok(dirents)
+        std::fs::remove_file(host_file_path).expect("could not delete host file");
+        std::fs::remove_file(empty_host_file_path).expect("could not delete host file");
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
-    /// Removes an empty directory.
-    fn remove_dir<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        let path = match std::ffi::CString::new(format!("{}/{}", self.get_device_root(), path)) {
-            Ok(path) => path,
-            Err(error) => return Err(format!("couldn't convert path: {}", error)),
-        };
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_copy_to_host() {
+        let demo_file_path = "hdd.img";
+        let host_file_path = "copy_to_host.bin";
+        let empty_host_file_path = "copy_to_host.empty";
 
-        ok_on_nonnegative_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_rmdir(path.as_ptr()) },
-            "failed to delete directory",
-        )?;
+        let contents: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
 
-        Ok(())
-    }
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
 
-    /// Removes a directory at this path, after removing all its contents. Use
-    /// carefully!
-    fn remove_dir_all<P: std::fmt::Display + AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        unimplemented!()
-        // for child in self.list_dir(path)? {
-        //     if child.file_type()?.is_dir() {
-        //         self.remove_dir_all(&child.path())?;
-        //     } else {
-        //         self.remove_file(&child.path())?;
-        //     }
-        // }
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
 
-        // self.remove_dir(path)
-    }
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
 
-    /// Removes a file from the filesystem.
-    fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        unimplemented!()
-    }
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
 
-    /// Rename a file or directory to a new name, replacing the original file if
-    /// `to` already exists.
-    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> io::Result<()> {
-        unimplemented!()
-    }
-}
+        if let Err(message) = pfs.write_file("payload.bin", &contents) {
+            panic!(message);
+        }
 
-#[derive(Debug)]
-pub struct PFS {
-    pub partition_name: String,
-}
+        if let Err(message) = pfs.write_file("empty.bin", &[]) {
+            panic!(message);
+        }
 
-impl Driver for PFS {
-    fn get_device_root(&self) -> &str {
-        "pfs0:"
-    }
-}
+        if let Err(message) = pfs.create_dir("somedir") {
+            panic!(message);
+        }
 
-#[derive(Debug)]
-pub struct HDLFS {
-    pub partition_name: String,
-}
+        assert_eq!(
+            pfs.copy_to_host("payload.bin", host_file_path),
+            Ok(contents.len() as u64)
+        );
+        assert!(
+            std::fs::read(host_file_path).expect("could not read host file") == contents,
+            "Copied contents didn't match"
+        );
 
-impl Driver for HDLFS {
-    fn get_device_root(&self) -> &str {
-        "hdl0:"
-    }
-}
+        assert_eq!(pfs.copy_to_host("empty.bin", empty_host_file_path), Ok(0));
+        assert_eq!(
+            std::fs::read(empty_host_file_path).expect("could not read host file"),
+            Vec::<u8>::new()
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::partition_kind::FormattablePartitionKind;
-    use crate::PS2HDD;
-    use serial_test::serial;
+        // A directory is refused before the host file is created
+        assert_eq!(
+            pfs.copy_to_host("somedir", "copy_to_host.dir"),
+            Err(Error::Other(
+                "somedir: Is a directory, and must be copied recursively".to_string()
+            ))
+        );
+        assert!(!Path::new("copy_to_host.dir").exists());
 
-    // A disk image needs to be at least 6GB in size for APA to work
-    // more is better, and 20GB is the "normal" minimum, but this is
-    // just enough  to fit any of the minimum-size 128MB partitions in
-    static DEMO_FILE_SIZE: u64 = 6 * 1024 * 1024 * 1024;
+        assert!(pfs
+            .copy_to_host("missing.bin", "copy_to_host.missing")
+            .is_err());
 
-    fn get_directory_entry_names(dirents: Vec<DirEntry>) -> Vec<String> {
-        dirents
-            .iter()
-            .map(|dirent| {
-                dirent
-                    .file_name()
-                    .to_str()
-                    .expect("could not convert partition entry name")
-                    .to_owned()
-            })
-            .collect()
+        std::fs::remove_file(host_file_path).expect("could not delete host file");
+        std::fs::remove_file(empty_host_file_path).expect("could not delete host file");
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
     #[test]
     #[serial(atad_device_path)]
-    fn pfs_mount_create_and_read_dir() {
+    fn pfs_host_copy_options() {
+        use std::os::unix::fs::PermissionsExt;
+
         let demo_file_path = "hdd.img";
+        let host_file_path = "host_copy_options.txt";
+        let host_link_path = "host_copy_options.lnk";
+        let exported_path = "host_copy_options.out";
+        let exported_link_path = "host_copy_options.out.lnk";
+
+        std::fs::write(host_file_path, b"host").expect("could not write host file");
+        std::fs::set_permissions(host_file_path, std::fs::Permissions::from_mode(0o600))
+            .expect("could not set host permissions");
+        std::os::unix::fs::symlink(host_file_path, host_link_path)
+            .expect("could not create host symlink");
 
         let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
             Ok(ps2hdd) => ps2hdd,
@@ -238,28 +4806,160 @@ mod tests {
             Err(message) => panic!(message),
         };
 
+        let permissions = |name: &str| {
+            stat_impl("pfs0:", Path::new(name))
+                .expect("Could not stat")
+                .mode
+                & 0o7777
+        };
+
+        // Overwriting
+        pfs.write_file("existing.txt", b"existing")
+            .expect("Could not write existing.txt");
+
         assert_eq!(
-            pfs.partition_name, "TESTPART",
-            "Unexpected partition reference"
+            pfs.copy_from_host_with(
+                host_file_path,
+                "existing.txt",
+                &CopyOptions::new().overwrite(false)
+            ),
+            Err(Error::Other("existing.txt: File exists".to_string()))
         );
+        assert_eq!(pfs.read_file("existing.txt"), Ok(b"existing".to_vec()));
 
-        pfs.create_dir("testdir").expect("Could not create testdir");
+        std::fs::write(exported_path, b"kept").expect("could not write host file");
 
-        let direntries = pfs.list_dir("/").expect("Could not list directory");
+        assert_eq!(
+            pfs.copy_to_host_with(
+                "existing.txt",
+                exported_path,
+                &CopyOptions::new().overwrite(false)
+            ),
+            Err(Error::Other(format!("{}: File exists", exported_path)))
+        );
+        assert_eq!(
+            std::fs::read(exported_path).expect("could not read host file"),
+            b"kept"
+        );
 
+        // Preserving, onto the device
         assert_eq!(
-            get_directory_entry_names(direntries),
-            vec!["testdir"],
-            "Unexpected directory list"
+            pfs.copy_from_host_with(host_file_path, "kept.txt", &CopyOptions::new()),
+            Ok(4)
+        );
+        assert_eq!(permissions("kept.txt"), 0o600);
+
+        let host_modified = std::fs::metadata(host_file_path)
+            .and_then(|metadata| metadata.modified())
+            .expect("could not read host modification time");
+        assert_eq!(
+            Some(DateTime::from(
+                stat_impl("pfs0:", Path::new("kept.txt"))
+                    .expect("Could not stat")
+                    .mtime
+            )),
+            DateTime::from_system_time(host_modified)
+        );
+
+        pfs.copy_from_host_with(
+            host_file_path,
+            "fresh.txt",
+            &CopyOptions::new().preserve(false),
+        )
+        .expect("Could not copy");
+        assert_eq!(permissions("fresh.txt"), 0o644);
+
+        // Preserving, onto the host; the device's times are whole seconds
+        let mut stat = stat_impl("pfs0:", Path::new("existing.txt")).expect("Could not stat");
+        stat.mode = (stat.mode & ps2hdd_sys::FIO_S_IFMT) | 0o640;
+        stat.mtime = [0, 56, 34, 12, 15, 10, 0xe4, 0x07];
+        chstat_impl(
+            "pfs0:",
+            Path::new("existing.txt"),
+            &mut stat,
+            ps2hdd_sys::FIO_CST_MODE | ps2hdd_sys::FIO_CST_MT,
+            "Could not change metadata",
+        )
+        .expect("Could not change metadata");
+
+        assert_eq!(
+            pfs.copy_to_host_with("existing.txt", exported_path, &CopyOptions::new()),
+            Ok(8)
+        );
+
+        let exported = std::fs::metadata(exported_path).expect("could not read host metadata");
+        assert_eq!(exported.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(
+            exported.modified().ok(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_602_732_896))
+        );
+
+        // Following symlinks
+        assert_eq!(
+            pfs.copy_from_host_with(host_link_path, "followed.txt", &CopyOptions::new()),
+            Ok(4)
+        );
+        assert_eq!(pfs.read_file("followed.txt"), Ok(b"host".to_vec()));
+
+        assert_eq!(
+            pfs.copy_from_host_with(
+                host_link_path,
+                "link",
+                &CopyOptions::new().follow_symlinks(false)
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            read_link_impl("pfs0:", Path::new("link"))
+                .expect("Could not read symlink")
+                .as_bytes(),
+            host_file_path.as_bytes()
+        );
+
+        assert_eq!(
+            pfs.copy_to_host_with(
+                "link",
+                exported_link_path,
+                &CopyOptions::new().follow_symlinks(false)
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            std::fs::read_link(exported_link_path).expect("could not read host symlink"),
+            Path::new(host_file_path)
+        );
+
+        // Sparse copies onto the host read back the same
+        let mut contents = vec![0u8; 2 * READ_BUFFER_SIZE];
+        contents.extend(b"payload");
+        contents.extend(vec![0u8; READ_BUFFER_SIZE + 5]);
+
+        pfs.write_file("image.bin", &contents)
+            .expect("Could not write image.bin");
+
+        assert_eq!(
+            pfs.copy_to_host_with("image.bin", exported_path, &CopyOptions::new().sparse(true)),
+            Ok(contents.len() as u64)
+        );
+        assert!(
+            std::fs::read(exported_path).expect("could not read host file") == contents,
+            "Sparse copy to the host didn't match"
         );
 
+        std::fs::remove_file(host_file_path).expect("could not delete host file");
+        std::fs::remove_file(host_link_path).expect("could not delete host symlink");
+        std::fs::remove_file(exported_path).expect("could not delete host file");
+        std::fs::remove_file(exported_link_path).expect("could not delete host symlink");
         std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
     #[test]
     #[serial(atad_device_path)]
-    fn pfs_mount_create_dir_all() {
+    fn pfs_copy_dir_to_host() {
+        use std::os::unix::ffi::OsStrExt;
+
         let demo_file_path = "hdd.img";
+        let host_dir_path = Path::new("copy_dir_to_host");
 
         let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
             Ok(ps2hdd) => ps2hdd,
@@ -281,46 +4981,161 @@ mod tests {
             Err(message) => panic!(message),
         };
 
-        assert_eq!(
-            pfs.partition_name, "TESTPART",
-            "Unexpected partition reference"
-        );
+        let large: Vec<u8> = (0..2 * READ_BUFFER_SIZE + 7)
+            .map(|i| (i % 251) as u8)
+            .collect();
 
-        pfs.create_dir_all("a/b/c/d")
-            .expect("Could not create path");
+        if let Err(message) = pfs.create_dir_all("APPS/OPL/CFG") {
+            panic!(message);
+        }
 
-        let direntries = pfs.list_dir("/").expect("Could not list directory");
+        if let Err(message) = pfs.create_dir("APPS/EMPTY") {
+            panic!(message);
+        }
 
-        assert_eq!(
-            get_directory_entry_names(direntries),
-            vec!["a"],
-            "Unexpected directory list"
-        );
+        pfs.write_file("APPS/title.cfg", b"title=Apps")
+            .expect("Could not write file");
+        pfs.write_file("APPS/OPL/OPNPS2LD.ELF", &large)
+            .expect("Could not write file");
+        pfs.write_file("APPS/OPL/CFG/empty.cfg", &[])
+            .expect("Could not write file");
 
-        let direntries = pfs.list_dir("/a").expect("Could not list directory");
+        pfs.write_file("outside.txt", b"outside")
+            .expect("Could not write file");
+
+        for (link, target) in &[
+            ("APPS/OPL/latest", "pfs0:APPS/OPL/OPNPS2LD.ELF"),
+            ("APPS/OPL/CFG/title.cfg", "pfs0:/APPS/title.cfg"),
+            ("APPS/OPL/relative", "../title.cfg"),
+            ("APPS/outside", "pfs0:outside.txt"),
+        ] {
+            let link = device_path("pfs0:", Path::new(link)).expect("Invalid path");
+            let target = std::ffi::CString::new(*target).expect("Invalid target");
+            assert!(
+                unsafe { ps2hdd_sys::iomanx_symlink(target.as_ptr(), link.as_ptr()) } >= 0,
+                "Could not create symlink"
+            );
+        }
+
+        if let Err(message) = pfs.copy_dir_to_host("APPS", host_dir_path) {
+            panic!(message);
+        }
 
         assert_eq!(
-            get_directory_entry_names(direntries),
-            vec!["b"],
-            "Unexpected directory list"
+            std::fs::read(host_dir_path.join("title.cfg")).expect("could not read host file"),
+            b"title=Apps"
+        );
+        assert!(
+            std::fs::read(host_dir_path.join("OPL/OPNPS2LD.ELF"))
+                .expect("could not read host file")
+                == large,
+            "Copied contents didn't match"
+        );
+        assert_eq!(
+            std::fs::read(host_dir_path.join("OPL/CFG/empty.cfg"))
+                .expect("could not read host file"),
+            Vec::<u8>::new()
         );
+        assert!(host_dir_path.join("EMPTY").is_dir());
 
-        let direntries = pfs.list_dir("/a/b").expect("Could not list directory");
+        // Links are rewritten to point at the same entries on the host
+        for (link, target) in &[
+            ("OPL/latest", "OPNPS2LD.ELF"),
+            ("OPL/CFG/title.cfg", "../../title.cfg"),
+            ("OPL/relative", "../title.cfg"),
+        ] {
+            assert_eq!(
+                std::fs::read_link(host_dir_path.join(link))
+                    .expect("could not read host symlink")
+                    .as_os_str()
+                    .as_bytes(),
+                target.as_bytes()
+            );
+        }
 
+        assert!(
+            std::fs::read(host_dir_path.join("OPL/latest")).expect("could not follow host symlink")
+                == large,
+            "Host symlink didn't point at the copied file"
+        );
         assert_eq!(
-            get_directory_entry_names(direntries),
-            vec!["c"],
-            "Unexpected directory list"
+            std::fs::read(host_dir_path.join("OPL/CFG/title.cfg"))
+                .expect("could not follow host symlink"),
+            b"title=Apps"
         );
 
-        let direntries = pfs.list_dir("/a/b/c").expect("Could not list directory");
+        // The link out of the copied directory is skipped, rather than left
+        // dangling
+        assert!(std::fs::symlink_metadata(host_dir_path.join("outside")).is_err());
+
+        let mut names: Vec<_> = std::fs::read_dir(host_dir_path.join("OPL"))
+            .expect("could not list host directory")
+            .map(|entry| entry.expect("could not read host entry").file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["CFG", "OPNPS2LD.ELF", "latest", "relative"]);
 
         assert_eq!(
-            get_directory_entry_names(direntries),
-            vec!["d"],
-            "Unexpected directory list"
+            pfs.copy_dir_to_host("APPS/title.cfg", "copy_dir_to_host.file"),
+            Err(Error::Other("APPS/title.cfg: Not a directory".to_string()))
         );
 
+        std::fs::remove_dir_all(host_dir_path).expect("could not delete host directory");
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn pfs_copy_from_host_sparse() {
+        let demo_file_path = "hdd.img";
+        let host_file_path = "copy_from_host.sparse";
+
+        // Zero runs at the start, in the middle and at the end, each several
+        // blocks long, and some not aligned to a block
+        let mut contents = vec![0u8; 3 * READ_BUFFER_SIZE];
+        contents.extend((0..READ_BUFFER_SIZE + 17).map(|i| (i % 251) as u8 | 1));
+        contents.extend(vec![0u8; 4 * READ_BUFFER_SIZE - 17]);
+        contents.extend(b"payload");
+        contents.extend(vec![0u8; 2 * READ_BUFFER_SIZE + 5]);
+
+        std::fs::write(host_file_path, &contents).expect("could not write host file");
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        for sparse in &[false, true] {
+            let options = CopyOptions::new().sparse(*sparse);
+
+            assert_eq!(
+                pfs.copy_from_host_with(host_file_path, "image.bin", &options),
+                Ok(contents.len() as u64)
+            );
+            assert_eq!(pfs.file_size("image.bin"), Ok(contents.len() as u64));
+            assert!(
+                pfs.read_file("image.bin") == Ok(contents.clone()),
+                "Copied contents didn't match when sparse was {}",
+                sparse
+            );
+        }
+
+        std::fs::remove_file(host_file_path).expect("could not delete host file");
         std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 }
@@ -0,0 +1,147 @@
+//! The error type returned throughout the crate
+//!
+//! Every fallible operation on a [`PS2HDD`] or a mounted [`Driver`] returns
+//! an [`Error`], so that failures such as a missing file or partition can be
+//! matched on, rather than by comparing messages. Failures which have no
+//! variant of their own are described by [`Error::Other`].
+//!
+//! Errors format as messages suitable for showing to users, and convert into
+//! the same message with `From`, for callers which only want a `String`.
+//!
+//! [`PS2HDD`]: ../struct.PS2HDD.html
+//! [`Driver`]: ../driver/trait.Driver.html
+//! [`Error`]: enum.Error.html
+//! [`Error::Other`]: enum.Error.html#variant.Other
+
+use crate::ffi_utils;
+
+/// The ways in which operations on a PS2 HDD can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A PS2 HDD is already open within this process
+    DeviceAlreadyOpen,
+    /// `name` doesn't exist; `kind` describes what was being looked for,
+    /// such as `"file"` or `"partition"`
+    NotFound { name: String, kind: &'static str },
+    /// A path of this many bytes is too long for the drivers to accept
+    PathTooLong(usize),
+    /// A partition size which isn't a power of 2
+    InvalidPartitionSize(u64),
+    /// The drivers returned the negative error number `errno` while doing
+    /// what `context` describes
+    Io { errno: i32, context: String },
+    /// A string couldn't be passed to the drivers, as it contains a null
+    /// character; `context` describes what was being converted
+    NulByte {
+        context: &'static str,
+        error: std::ffi::NulError,
+    },
+    /// Any other failure, described only by its message
+    Other(String),
+}
+
+impl Error {
+    /// Reports `name` as missing if this is the drivers failing with
+    /// `ENOENT`, and otherwise returns the error unchanged.
+    pub(crate) fn or_not_found<N: std::fmt::Display>(self, name: N, kind: &'static str) -> Self {
+        match self {
+            Self::Io { errno, .. } if errno == -libc::ENOENT => Self::NotFound {
+                name: name.to_string(),
+                kind,
+            },
+            error => error,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeviceAlreadyOpen => {
+                write!(f, "Only one PS2HDD instance may be mounted at a time")
+            }
+            Self::NotFound { name, kind } => write!(f, "{}: No such {}", name, kind),
+            Self::PathTooLong(length) => write!(
+                f,
+                "Path of length {} is too long to be null-terminated",
+                length
+            ),
+            Self::InvalidPartitionSize(_) => write!(f, "Partition size must be a power of 2"),
+            Self::Io { errno, context } => match ffi_utils::strerror(*errno) {
+                Ok(message) => write!(f, "{}: {}, {}", context, errno, message),
+                Err(_) => write!(f, "{}: {}", context, errno),
+            },
+            Self::NulByte { context, error } => write!(f, "{}: {}", context, error),
+            Self::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NulByte { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for String {
+    fn from(error: Error) -> Self {
+        error.to_string()
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_like_string_errors() {
+        assert_eq!(
+            String::from(Error::Io {
+                errno: -libc::ENOENT,
+                context: "failed to create directory".to_string()
+            }),
+            "failed to create directory: -2, No such file or directory"
+        );
+
+        assert_eq!(
+            Error::Io {
+                errno: -libc::ENOENT,
+                context: "failed to open file".to_string()
+            }
+            .or_not_found("missing.txt", "file or directory"),
+            Error::NotFound {
+                name: "missing.txt".to_string(),
+                kind: "file or directory"
+            }
+        );
+
+        assert_eq!(
+            Error::NotFound {
+                name: "NOTAPART".to_string(),
+                kind: "partition"
+            }
+            .to_string(),
+            "NOTAPART: No such partition"
+        );
+
+        let error = std::ffi::CString::new("a\0b").unwrap_err();
+
+        assert_eq!(
+            Error::NulByte {
+                context: "couldn't convert path",
+                error
+            }
+            .to_string(),
+            "couldn't convert path: nul byte found in provided data at position: 1"
+        );
+    }
+}
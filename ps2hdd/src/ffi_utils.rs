@@ -1,45 +1,76 @@
+use crate::error::Error;
+
 /// FFI utility function which converts the return value of a C function whose
 /// expected result is `0` into a `Result` type to reduce code repetition.
 ///
-/// If a negative return value is encountered, the result contains a combination
-/// of the `err_message` provided and the `strerror` value for the result.
+/// If any other return value is encountered, the result is an [`Error::Io`]
+/// carrying the `err_message` provided and the return value, which formats
+/// with the `strerror` value for it.
+///
+/// [`Error::Io`]: ../error/enum.Error.html#variant.Io
 pub fn ok_on_zero_or_strerror(
     result: std::os::raw::c_int,
     err_message: &str,
-) -> Result<std::os::raw::c_int, String> {
-    ok_on_pred_or_strerror(result, err_message, |ret| ret != 0)
+) -> Result<std::os::raw::c_int, Error> {
+    ok_on_pred_or_error(result, err_message, |ret| ret != 0)
 }
 
 /// FFI utility function which converts the return value of a C function whose
 /// expected result is positive into a `Result` type to reduce code repetition.
 ///
-/// If a negative return value is encountered, the result contains a combination
-/// of the `err_message` provided and the `strerror` value for the result.
+/// If a negative return value is encountered, the result is an [`Error::Io`]
+/// carrying the `err_message` provided and the return value, which formats
+/// with the `strerror` value for it.
+///
+/// [`Error::Io`]: ../error/enum.Error.html#variant.Io
 pub fn ok_on_nonnegative_or_strerror(
     result: std::os::raw::c_int,
     err_message: &str,
-) -> Result<std::os::raw::c_int, String> {
-    ok_on_pred_or_strerror(result, err_message, |ret| ret < 0)
+) -> Result<std::os::raw::c_int, Error> {
+    ok_on_pred_or_error(result, err_message, |ret| ret < 0)
 }
 
-fn ok_on_pred_or_strerror<F>(
+thread_local! {
+    /// The message and result of the most recent failed call checked by
+    /// `ok_on_pred_or_error` on this thread
+    static LAST_ERROR: std::cell::RefCell<Option<(String, std::os::raw::c_int)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Returns the message and result of the most recent failed call checked by
+/// one of these utility functions on the current thread, if any have failed.
+pub fn last_error() -> Option<(String, std::os::raw::c_int)> {
+    LAST_ERROR.with(|last_error| last_error.borrow().clone())
+}
+
+/// Returns the `strerror` message for the negative error number `result`.
+pub fn strerror(result: std::os::raw::c_int) -> Result<String, String> {
+    match unsafe { std::ffi::CStr::from_ptr(libc::strerror(-result)) }.to_str() {
+        Ok(err) => Ok(err.to_string()),
+        Err(error) => Err(format!(
+            "could not convert strerror message a String: {}",
+            error
+        )),
+    }
+}
+
+fn ok_on_pred_or_error<F>(
     result: std::os::raw::c_int,
     err_message: &str,
     f: F,
-) -> Result<std::os::raw::c_int, String>
+) -> Result<std::os::raw::c_int, Error>
 where
     F: Fn(std::os::raw::c_int) -> bool,
 {
     if f(result) {
-        match unsafe { std::ffi::CStr::from_ptr(libc::strerror(-result)) }.to_str() {
-            Ok(err) => return Err(format!("{}: {}, {}", err_message, result, err)),
-            Err(error) => {
-                return Err(format!(
-                    "could not convert strerror message a String: {}",
-                    error
-                ))
-            }
-        }
+        LAST_ERROR.with(|last_error| {
+            *last_error.borrow_mut() = Some((err_message.to_string(), result));
+        });
+
+        return Err(Error::Io {
+            errno: result,
+            context: err_message.to_string(),
+        });
     }
 
     Ok(result)
@@ -65,16 +96,36 @@ mod tests {
     #[test]
     fn return_err_on_positives() {
         assert_eq!(
-            ok_on_zero_or_strerror(-libc::EPERM, "This message should be returned"),
+            ok_on_zero_or_strerror(-libc::EPERM, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -1, Operation not permitted".to_string())
         );
 
         assert_eq!(
-            ok_on_zero_or_strerror(-libc::EACCES, "This message should be returned"),
+            ok_on_zero_or_strerror(-libc::EACCES, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -13, Permission denied".to_string())
         );
     }
 
+    #[test]
+    fn records_last_error() {
+        let _ = ok_on_zero_or_strerror(-libc::EPERM, "This failure should be recorded");
+        let _ = ok_on_zero_or_strerror(0, "This success should not be recorded");
+
+        assert_eq!(
+            last_error(),
+            Some(("This failure should be recorded".to_string(), -libc::EPERM))
+        );
+
+        let _ = ok_on_nonnegative_or_strerror(-libc::EIO, "This failure should replace it");
+
+        assert_eq!(
+            last_error(),
+            Some(("This failure should replace it".to_string(), -libc::EIO))
+        );
+    }
+
     #[test]
     fn return_ok_on_positives() {
         assert_eq!(
@@ -91,22 +142,26 @@ mod tests {
     #[test]
     fn return_err_on_negatives() {
         assert_eq!(
-            ok_on_zero_or_strerror(-libc::EPERM, "This message should be returned"),
+            ok_on_zero_or_strerror(-libc::EPERM, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -1, Operation not permitted".to_string())
         );
 
         assert_eq!(
-            ok_on_zero_or_strerror(-libc::EACCES, "This message should be returned"),
+            ok_on_zero_or_strerror(-libc::EACCES, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -13, Permission denied".to_string())
         );
 
         assert_eq!(
-            ok_on_nonnegative_or_strerror(-libc::EIO, "This message should be returned"),
+            ok_on_nonnegative_or_strerror(-libc::EIO, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -5, Input/output error".to_string())
         );
 
         assert_eq!(
-            ok_on_nonnegative_or_strerror(-libc::EBUSY, "This message should be returned"),
+            ok_on_nonnegative_or_strerror(-libc::EBUSY, "This message should be returned")
+                .map_err(String::from),
             Err("This message should be returned: -16, Resource busy".to_string())
         );
     }
@@ -3,7 +3,8 @@
 
 use std::convert::TryFrom;
 
-use crate::partition_kind::PartitionKind;
+use crate::error::Error;
+use crate::partition_kind::{PartitionFlags, PartitionKind};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct FileType {
@@ -11,6 +12,11 @@ pub struct FileType {
 }
 
 impl FileType {
+    /// Creates a `FileType` from a raw `mode`, as found in a file's stat.
+    pub fn from_mode(mode: std::os::raw::c_uint) -> Self {
+        Self { mode }
+    }
+
     pub fn is_dir(&self) -> bool {
         self.is(ps2hdd_sys::FIO_S_IFDIR)
     }
@@ -26,22 +32,846 @@ impl FileType {
     pub fn is(&self, mode: u32) -> bool {
         self.mode & ps2hdd_sys::FIO_S_IFMT == mode
     }
+
+    pub fn owner_can_read(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IRUSR)
+    }
+
+    pub fn owner_can_write(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IWUSR)
+    }
+
+    pub fn owner_can_execute(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IXUSR)
+    }
+
+    pub fn group_can_read(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IRGRP)
+    }
+
+    pub fn group_can_write(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IWGRP)
+    }
+
+    pub fn group_can_execute(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IXGRP)
+    }
+
+    pub fn others_can_read(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IROTH)
+    }
+
+    pub fn others_can_write(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IWOTH)
+    }
+
+    pub fn others_can_execute(&self) -> bool {
+        self.has(ps2hdd_sys::FIO_S_IXOTH)
+    }
+
+    /// Returns `true` if all of the given permission bits are set.
+    fn has(&self, bits: u32) -> bool {
+        self.mode & bits == bits
+    }
+}
+
+/// A timestamp, as stored by the PlayStation®2 file systems.
+///
+/// Timestamps are recorded in Japan Standard Time (UTC+9), as that is the
+/// time zone the console's clock is set to, regardless of region.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl From<[u8; 8]> for DateTime {
+    /// Decodes the raw bytes of a `ps2fs_datetime_type`, as found in the
+    /// timestamp fields of `iox_stat_t`.
+    fn from(raw: [u8; 8]) -> Self {
+        Self {
+            year: u16::from_le_bytes([raw[6], raw[7]]),
+            month: raw[5],
+            day: raw[4],
+            hour: raw[3],
+            minute: raw[2],
+            second: raw[1],
+        }
+    }
+}
+
+impl From<DateTime> for [u8; 8] {
+    /// Encodes the raw bytes of a `ps2fs_datetime_type`, as written to the
+    /// timestamp fields of `iox_stat_t`.
+    fn from(date_time: DateTime) -> Self {
+        let year = date_time.year.to_le_bytes();
+
+        [
+            0,
+            date_time.second,
+            date_time.minute,
+            date_time.hour,
+            date_time.day,
+            date_time.month,
+            year[0],
+            year[1],
+        ]
+    }
+}
+
+impl DateTime {
+    /// The offset of Japan Standard Time from UTC, in seconds
+    const JST_OFFSET: i64 = 9 * 60 * 60;
+
+    /// Converts a `SystemTime` into a timestamp in Japan Standard Time, as
+    /// the console would record it, discarding any fraction of a second.
+    ///
+    /// Returns `None` if the time falls outside of the years which can be
+    /// stored.
+    ///
+    /// ```
+    /// use ps2hdd::fs::DateTime;
+    /// use std::time::UNIX_EPOCH;
+    ///
+    /// let date_time = DateTime {
+    ///     year: 1970,
+    ///     month: 1,
+    ///     day: 1,
+    ///     hour: 9,
+    ///     minute: 0,
+    ///     second: 0,
+    /// };
+    ///
+    /// assert_eq!(DateTime::from_system_time(UNIX_EPOCH), Some(date_time));
+    /// ```
+    pub fn from_system_time(time: std::time::SystemTime) -> Option<Self> {
+        use std::convert::TryFrom;
+
+        let seconds = match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_secs()).ok()?,
+            Err(error) => {
+                let before = error.duration();
+                let seconds = i64::try_from(before.as_secs()).ok()?;
+
+                // Round down to the start of the second, as after the epoch
+                -seconds - if before.subsec_nanos() > 0 { 1 } else { 0 }
+            }
+        }
+        .checked_add(Self::JST_OFFSET)?;
+
+        // The civil date of a number of days since 1970-01-01, after Howard
+        // Hinnant's `civil_from_days`
+        let days = seconds.div_euclid(86_400) + 719_468;
+        let time_of_day = seconds.rem_euclid(86_400);
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Some(Self {
+            year: u16::try_from(year).ok()?,
+            month: month as u8,
+            day: day as u8,
+            hour: (time_of_day / 3_600) as u8,
+            minute: (time_of_day / 60 % 60) as u8,
+            second: (time_of_day % 60) as u8,
+        })
+    }
+
+    /// Converts this timestamp into a `SystemTime`, treating it as being in
+    /// Japan Standard Time.
+    ///
+    /// The year is stored in full, rather than as an offset from any epoch.
+    /// Returns `None` if any field is out of range, such as for the zeroed
+    /// timestamps of files which have never had one set.
+    ///
+    /// ```
+    /// use ps2hdd::fs::DateTime;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let date_time = DateTime {
+    ///     year: 1970,
+    ///     month: 1,
+    ///     day: 1,
+    ///     hour: 9,
+    ///     minute: 0,
+    ///     second: 0,
+    /// };
+    ///
+    /// assert_eq!(date_time.to_system_time(), Some(UNIX_EPOCH));
+    /// ```
+    pub fn to_system_time(&self) -> Option<std::time::SystemTime> {
+        if !(1..=12).contains(&self.month)
+            || !(1..=31).contains(&self.day)
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            return None;
+        }
+
+        // Days since 1970-01-01 of the civil date, after Howard Hinnant's
+        // `days_from_civil`
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds =
+            days * 86_400 + self.hour as i64 * 3_600 + self.minute as i64 * 60 + self.second as i64
+                - Self::JST_OFFSET;
+
+        if seconds >= 0 {
+            std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(seconds as u64))
+        } else {
+            std::time::UNIX_EPOCH
+                .checked_sub(std::time::Duration::from_secs(seconds.unsigned_abs()))
+        }
+    }
+}
+
+/// The size of the private data carried in a file's stat, in bytes
+pub const PRIVATE_DATA_SIZE: usize = 24;
+
+/// Packs the private fields of a stat into an opaque array of bytes.
+pub(crate) fn private_data(stat: &ps2hdd_sys::iox_stat_t) -> [u8; PRIVATE_DATA_SIZE] {
+    let mut bytes = [0u8; PRIVATE_DATA_SIZE];
+
+    for (index, word) in [
+        stat.private_0,
+        stat.private_1,
+        stat.private_2,
+        stat.private_3,
+        stat.private_4,
+        stat.private_5,
+    ]
+    .iter()
+    .enumerate()
+    {
+        bytes[index * 4..index * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Unpacks an opaque array of bytes into the private fields of a stat.
+pub(crate) fn set_private_data(stat: &mut ps2hdd_sys::iox_stat_t, bytes: &[u8; PRIVATE_DATA_SIZE]) {
+    let word = |index: usize| {
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[index * 4..index * 4 + 4]);
+        u32::from_le_bytes(word)
+    };
+
+    stat.private_0 = word(0);
+    stat.private_1 = word(1);
+    stat.private_2 = word(2);
+    stat.private_3 = word(3);
+    stat.private_4 = word(4);
+    stat.private_5 = word(5);
+}
+
+/// Metadata information about a file, directory or partition, intended to be
+/// comparable to `std::fs::Metadata`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Metadata {
+    len: u64,
+    file_type: FileType,
+    partition_kind: Option<Option<PartitionKind>>,
+    created: Option<DateTime>,
+    accessed: Option<DateTime>,
+    modified: Option<DateTime>,
+    attributes: u32,
+    private_data: [u8; PRIVATE_DATA_SIZE],
+}
+
+impl Metadata {
+    /// Returns the size, in bytes, of the file or partition this metadata is
+    /// for.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the file or partition this metadata is for is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the file type for this metadata.
+    ///
+    /// Partitions are neither files, directories nor symlinks.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type.is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type.is_symlink()
+    }
+
+    /// Returns `true` if this metadata is for a partition.
+    pub fn is_partition(&self) -> bool {
+        self.partition_kind.is_some()
+    }
+
+    /// Returns the kind of the partition this metadata is for, if it is for
+    /// a partition of a known kind.
+    pub fn partition_kind(&self) -> Option<PartitionKind> {
+        self.partition_kind.flatten()
+    }
+
+    /// Returns the permission bits of the file's mode.
+    pub fn permissions(&self) -> u32 {
+        self.file_type.mode & 0o7777
+    }
+
+    /// Returns the creation time listed in this metadata.
+    ///
+    /// Partitions only record their creation time, which is not carried
+    /// along by [`PartEntry`](struct.PartEntry.html), so this will be `None`
+    /// for them.
+    pub fn created(&self) -> Option<DateTime> {
+        self.created
+    }
+
+    /// Returns the last access time listed in this metadata.
+    pub fn accessed(&self) -> Option<DateTime> {
+        self.accessed
+    }
+
+    /// Returns the last modification time listed in this metadata.
+    pub fn modified(&self) -> Option<DateTime> {
+        self.modified
+    }
+
+    /// Returns the attribute bits of the file.
+    ///
+    /// These are specific to the PlayStation®2 file systems, and are zero
+    /// for partitions.
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    /// Returns the private data carried in the file's stat, as an opaque
+    /// array of bytes.
+    ///
+    /// For PFS, this includes the owner's user and group IDs, and the number
+    /// of blocks the file uses. It is all zeroes for partitions.
+    pub fn private_data(&self) -> [u8; PRIVATE_DATA_SIZE] {
+        self.private_data
+    }
+}
+
+impl From<&DirEntry> for Metadata {
+    fn from(entry: &DirEntry) -> Self {
+        Self::from(&entry.entry.stat)
+    }
+}
+
+impl From<&ps2hdd_sys::iox_stat_t> for Metadata {
+    fn from(stat: &ps2hdd_sys::iox_stat_t) -> Self {
+        Self {
+            len: ((stat.hisize as u64) << 32) | stat.size as u64,
+            file_type: FileType { mode: stat.mode },
+            partition_kind: None,
+            created: Some(DateTime::from(stat.ctime)),
+            accessed: Some(DateTime::from(stat.atime)),
+            modified: Some(DateTime::from(stat.mtime)),
+            attributes: stat.attr,
+            private_data: private_data(stat),
+        }
+    }
+}
+
+impl From<&PartEntry> for Metadata {
+    fn from(entry: &PartEntry) -> Self {
+        Self {
+            len: entry.size,
+            file_type: FileType { mode: 0 },
+            partition_kind: Some(entry.kind),
+            created: None,
+            accessed: None,
+            modified: None,
+            attributes: 0,
+            private_data: [0; PRIVATE_DATA_SIZE],
+        }
+    }
+}
+
+/// Options which control how files are written by
+/// [`Driver::write_file_with`](../driver/trait.Driver.html#method.write_file_with).
+///
+/// ```
+/// use ps2hdd::fs::WriteOptions;
+///
+/// let options = WriteOptions::new().atomic(true);
+///
+/// assert!(options.is_atomic());
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct WriteOptions {
+    atomic: bool,
+    in_place: bool,
+    reset_permissions: bool,
+}
+
+impl WriteOptions {
+    /// Creates a set of options with everything disabled, which writes
+    /// directly to the destination file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the contents should be written to a temporary file on the
+    /// same partition first, and only renamed over the destination once
+    /// they've been written and synced.
+    ///
+    /// This avoids leaving a half-written file behind if writing fails, at
+    /// the cost of briefly needing enough space for both files.
+    ///
+    /// The final rename is only atomic if the driver will rename over an
+    /// existing file. Where it refuses, the destination is removed before
+    /// the temporary file is renamed into its place, so an interruption
+    /// between the two leaves only the temporary file, named `.<name>.tmp`
+    /// alongside the destination.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    pub fn is_atomic(&self) -> bool {
+        self.atomic
+    }
+
+    /// Sets whether an existing file should be overwritten in place, reusing
+    /// its existing blocks, rather than being truncated and reallocated.
+    ///
+    /// This reduces fragmentation for files which are frequently rewritten
+    /// with contents of a similar size, such as save data. Files can't be
+    /// shortened without truncating them, so if the new contents are shorter
+    /// than the existing file, it is truncated as usual. Atomic writes always
+    /// write to a new file, so this has no effect on them.
+    pub fn in_place(mut self, in_place: bool) -> Self {
+        self.in_place = in_place;
+        self
+    }
+
+    pub fn is_in_place(&self) -> bool {
+        self.in_place
+    }
+
+    /// Sets whether an existing file's permissions should be replaced with
+    /// the defaults for a new file, rather than kept as they were.
+    pub fn reset_permissions(mut self, reset_permissions: bool) -> Self {
+        self.reset_permissions = reset_permissions;
+        self
+    }
+
+    pub fn resets_permissions(&self) -> bool {
+        self.reset_permissions
+    }
+}
+
+/// Options which control how files are copied, by
+/// [`Driver::copy`](../driver/trait.Driver.html#method.copy) within the
+/// device, and by
+/// [`Driver::copy_from_host_with`](../driver/trait.Driver.html#method.copy_from_host_with)
+/// and
+/// [`Driver::copy_to_host_with`](../driver/trait.Driver.html#method.copy_to_host_with)
+/// between the device and the host.
+///
+/// The defaults match `std::fs::copy`; an existing destination is
+/// overwritten, the source's permissions and timestamps are preserved, and
+/// symlinks are followed, copying the contents of the file they point to.
+/// Every byte is written out, rather than skipping over zeroes.
+///
+/// ```
+/// use ps2hdd::fs::CopyOptions;
+///
+/// let options = CopyOptions::new().overwrite(false);
+///
+/// assert!(!options.will_overwrite());
+/// assert!(options.will_preserve());
+/// assert!(options.will_follow_symlinks());
+/// assert!(!options.is_sparse());
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CopyOptions {
+    overwrite: bool,
+    preserve: bool,
+    follow_symlinks: bool,
+    sparse: bool,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            overwrite: true,
+            preserve: true,
+            follow_symlinks: true,
+            sparse: false,
+        }
+    }
+}
+
+impl CopyOptions {
+    /// Creates a set of options matching the behaviour of `std::fs::copy`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether an existing file at the destination should be replaced.
+    /// If not, copying to an existing destination returns an error.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Sets whether the source's permissions and access and modification
+    /// times should be applied to the destination. If not, the destination
+    /// gets the default permissions, and the current time.
+    pub fn preserve(mut self, preserve: bool) -> Self {
+        self.preserve = preserve;
+        self
+    }
+
+    /// Sets whether a symlink source should be followed, copying the contents
+    /// of the file it points to. If not, the destination is created as a
+    /// symlink to the same target.
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets whether blocks of the source which are entirely zero should be
+    /// skipped over by seeking past them, rather than written out.
+    ///
+    /// PFS has no sparse files; an inode can't describe a hole, and its
+    /// driver won't seek past the end of a file. On PFS, the zeroes are
+    /// written out regardless, and every block of the file is allocated, so
+    /// this only saves work when copying to the host, or on drivers which
+    /// allow seeking past the end. The copy reads back the same either way.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    pub fn will_overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn will_preserve(&self) -> bool {
+        self.preserve
+    }
+
+    pub fn will_follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        self.sparse
+    }
+}
+
+/// The number of entries of each type within a directory, as counted by
+/// [`Driver::summarize_dir`](../driver/trait.Driver.html#method.summarize_dir).
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct DirSummary {
+    pub files: u64,
+    pub dirs: u64,
+    pub symlinks: u64,
+    /// Entries of any other type, which shouldn't normally be present
+    pub other: u64,
+}
+
+impl DirSummary {
+    /// Counts one more entry of the given type.
+    pub(crate) fn add(&mut self, file_type: FileType) {
+        if file_type.is_file() {
+            self.files += 1;
+        } else if file_type.is_dir() {
+            self.dirs += 1;
+        } else if file_type.is_symlink() {
+            self.symlinks += 1;
+        } else {
+            self.other += 1;
+        }
+    }
+
+    /// Returns the total number of entries, of any type.
+    pub fn total(&self) -> u64 {
+        self.files + self.dirs + self.symlinks + self.other
+    }
+}
+
+/// An open file on a mounted partition.
+///
+/// Each read, write or seek is a call into the drivers, so small reads and
+/// writes are comparatively slow; consider wrapping the file in a
+/// `std::io::BufReader` or `std::io::BufWriter`, or using
+/// [`Driver::open_buffered_reader`] or [`Driver::open_buffered_writer`].
+///
+/// The file is closed when dropped, ignoring any error in doing so; use
+/// [`close`](#method.close) to find out whether it closed successfully.
+///
+/// [`Driver::open_buffered_reader`]: ../driver/trait.Driver.html#method.open_buffered_reader
+/// [`Driver::open_buffered_writer`]: ../driver/trait.Driver.html#method.open_buffered_writer
+#[derive(Debug)]
+pub struct File {
+    /// The driver's handle for the file, or `-1` once it's been closed
+    handle: std::os::raw::c_int,
+    /// The device the file is on, such as `pfs0:`, which is synced on flush
+    device: std::ffi::CString,
+}
+
+impl File {
+    /// Opens the file at a full device path, such as `pfs0:/file.txt`, with
+    /// the given `IOMANX_O_*` flags, creating it with `mode` if
+    /// `IOMANX_O_CREAT` is given.
+    ///
+    /// Directories are only ever opened by the listing functions, using
+    /// `iomanx_dopen`, so `IOMANX_O_DIROPEN` must not be given.
+    pub(crate) fn open(
+        path: &std::ffi::CStr,
+        flags: std::os::raw::c_int,
+        mode: std::os::raw::c_int,
+    ) -> Result<Self, Error> {
+        debug_assert_eq!(
+            flags & ps2hdd_sys::IOMANX_O_DIROPEN as std::os::raw::c_int,
+            0
+        );
+
+        let bytes = path.to_bytes();
+        let device = match bytes.iter().position(|&byte| byte == b':') {
+            Some(end) => &bytes[..=end],
+            None => {
+                return Err(Error::Other(format!(
+                    "{}: not a device path",
+                    path.to_string_lossy()
+                )))
+            }
+        };
+
+        let device = match std::ffi::CString::new(device) {
+            Ok(device) => device,
+            Err(error) => {
+                return Err(Error::NulByte {
+                    context: "couldn't convert device",
+                    error,
+                })
+            }
+        };
+
+        let handle = crate::ffi_utils::ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_open(path.as_ptr(), flags, mode) },
+            "failed to open file",
+        )?;
+
+        Ok(Self { handle, device })
+    }
+
+    /// Closes the file and then syncs the device, so that anything written
+    /// back as the file is closed also reaches the disk, returning any error
+    /// in doing so, which dropping the file would otherwise only log.
+    pub fn close(mut self) -> Result<(), Error> {
+        let handle = std::mem::replace(&mut self.handle, -1);
+
+        crate::ffi_utils::ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_close(handle) },
+            "failed to close file",
+        )?;
+
+        crate::ffi_utils::ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_sync(self.device.as_ptr(), 0) },
+            "failed to sync device",
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Converts the negative error number returned by a driver into an `Error`.
+fn io_error(result: i64) -> std::io::Error {
+    std::io::Error::from_raw_os_error(-result as i32)
+}
+
+impl std::io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = buf.len().min(i32::MAX as usize);
+
+        let result = unsafe {
+            ps2hdd_sys::iomanx_read(
+                self.handle,
+                buf.as_mut_ptr() as *mut core::ffi::c_void,
+                length as i32,
+            )
+        };
+
+        if result < 0 {
+            return Err(io_error(result as i64));
+        }
+
+        Ok(result as usize)
+    }
+}
+
+impl std::io::Write for File {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let length = buf.len().min(i32::MAX as usize);
+
+        let result = unsafe {
+            ps2hdd_sys::iomanx_write(
+                self.handle,
+                buf.as_ptr() as *mut core::ffi::c_void,
+                length as i32,
+            )
+        };
+
+        if result < 0 {
+            return Err(io_error(result as i64));
+        }
+
+        Ok(result as usize)
+    }
+
+    /// Writes go straight to the drivers, so this syncs the device, writing
+    /// out the drivers' own caches.
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = unsafe { ps2hdd_sys::iomanx_sync(self.device.as_ptr(), 0) };
+
+        if result < 0 {
+            return Err(io_error(result as i64));
+        }
+
+        Ok(())
+    }
+}
+
+impl std::io::Seek for File {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (offset, whence) = match pos {
+            std::io::SeekFrom::Start(offset) => (offset as i64, ps2hdd_sys::IOMANX_SEEK_SET),
+            std::io::SeekFrom::Current(offset) => (offset, ps2hdd_sys::IOMANX_SEEK_CUR),
+            std::io::SeekFrom::End(offset) => (offset, ps2hdd_sys::IOMANX_SEEK_END),
+        };
+
+        let result = unsafe { ps2hdd_sys::iomanx_lseek64(self.handle, offset, whence as i32) };
+
+        if result < 0 {
+            return Err(io_error(result));
+        }
+
+        Ok(result as u64)
+    }
+
+    /// Asks the driver for the current position, rather than tracking it
+    /// here, so it is accurate however the file has been read or written.
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        let result = unsafe {
+            ps2hdd_sys::iomanx_lseek64(self.handle, 0, ps2hdd_sys::IOMANX_SEEK_CUR as i32)
+        };
+
+        if result < 0 {
+            return Err(io_error(result));
+        }
+
+        Ok(result as u64)
+    }
+
+    fn rewind(&mut self) -> std::io::Result<()> {
+        self.seek(std::io::SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if self.handle < 0 {
+            return;
+        }
+
+        if unsafe { ps2hdd_sys::iomanx_close(self.handle) } != 0 {
+            log::warn!("Failed to close file handle {}", self.handle);
+        }
+    }
 }
 
 /// Represents a directory entry present on a partition
+///
+/// The path of the directory an entry was listed from, and the name of its
+/// partition, are shared with the other entries from the same listing, so
+/// each entry doesn't need its own copy of them.
 #[derive(Debug, PartialEq)]
 pub struct DirEntry {
     entry: ps2hdd_sys::iox_dirent_t,
-    root: std::path::PathBuf,
+    root: std::rc::Rc<std::path::Path>,
+    partition: Option<std::rc::Rc<str>>,
 }
 
 impl DirEntry {
-    pub fn new(entry: ps2hdd_sys::iox_dirent_t, root: std::path::PathBuf) -> Self {
-        Self { entry, root }
+    pub fn new<R: Into<std::rc::Rc<std::path::Path>>>(
+        entry: ps2hdd_sys::iox_dirent_t,
+        root: R,
+    ) -> Self {
+        Self {
+            entry,
+            root: root.into(),
+            partition: None,
+        }
     }
 
-    pub fn path(&self) -> &std::path::PathBuf {
-        unimplemented!()
+    /// Records the name of the partition this entry was listed from.
+    pub fn with_partition<S: Into<std::rc::Rc<str>>>(mut self, partition_name: S) -> Self {
+        self.partition = Some(partition_name.into());
+        self
+    }
+
+    /// Returns the name of the partition this entry was listed from, if
+    /// known. Entries listed by a [`Driver`] always know their partition.
+    ///
+    /// [`Driver`]: ../driver/trait.Driver.html
+    pub fn partition(&self) -> Option<&str> {
+        self.partition.as_deref()
+    }
+
+    /// Returns the path of the directory this entry was listed from.
+    pub fn root(&self) -> &std::path::Path {
+        &self.root
+    }
+
+    /// Returns the full path of this entry; its file name joined onto the
+    /// path of the directory it was listed from.
+    ///
+    /// As with `std::fs::DirEntry::path`, the path is relative to the same
+    /// place as the directory path given when listing, so can be passed
+    /// straight back to the same [`Driver`].
+    ///
+    /// [`Driver`]: ../driver/trait.Driver.html
+    pub fn path(&self) -> std::path::PathBuf {
+        self.root.join(self.file_name())
     }
 
     pub fn file_name(&self) -> std::ffi::OsString {
@@ -49,32 +879,177 @@ impl DirEntry {
         std::ffi::OsStr::from_bytes(self.name_bytes()).to_os_string()
     }
 
-    pub fn file_type(&self) -> Result<FileType, String> {
+    pub fn file_type(&self) -> Result<FileType, Error> {
         Ok(FileType { mode: self.entry.stat.mode })
     }
 
+    /// Returns the metadata for the file this entry points at, as embedded
+    /// within the entry itself.
+    pub fn metadata(&self) -> Result<Metadata, Error> {
+        Ok(Metadata::from(self))
+    }
+
+    /// Returns the creation time of the file this entry points at.
+    ///
+    /// Directory entries always carry their times, so this is only an
+    /// `Option` to match [`Metadata::created`](struct.Metadata.html#method.created).
+    pub fn created(&self) -> Option<DateTime> {
+        Some(DateTime::from(self.entry.stat.ctime))
+    }
+
+    /// Returns the last access time of the file this entry points at.
+    pub fn accessed(&self) -> Option<DateTime> {
+        Some(DateTime::from(self.entry.stat.atime))
+    }
+
+    /// Returns the last modification time of the file this entry points at.
+    pub fn modified(&self) -> Option<DateTime> {
+        Some(DateTime::from(self.entry.stat.mtime))
+    }
+
+    /// Consumes the entry, returning its file name and metadata, so that they
+    /// can be kept without also keeping the raw directory entry around.
+    ///
+    /// The metadata includes the entry's file type, length and timestamps.
+    pub fn into_owned_parts(self) -> (std::ffi::OsString, Metadata) {
+        (self.file_name(), Metadata::from(&self))
+    }
+
     fn name_bytes(&self) -> &[u8] {
         unsafe { std::ffi::CStr::from_ptr(self.entry.name.as_ptr()).to_bytes() }
     }
 }
 
+/// Formats a number of bytes using binary units, such as `"128 MiB"`.
+///
+/// Sizes which aren't a whole number of the largest unit that fits them are
+/// shown to one decimal place, such as `"1.5 GiB"`.
+///
+/// ```
+/// use ps2hdd::fs::human_size;
+///
+/// assert_eq!(human_size(128 * 1024 * 1024), "128 MiB");
+/// assert_eq!(human_size(1536 * 1024 * 1024), "1.5 GiB");
+/// ```
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut unit_size: u64 = 1024;
+    let mut unit = UNITS[0];
+
+    for next_unit in UNITS.iter().skip(1) {
+        // Round to the one decimal place shown before picking the unit, so
+        // sizes just short of the next unit aren't shown as 1024.0 of this one
+        if (bytes as f64 / unit_size as f64 * 10.0).round() < 1024.0 * 10.0 {
+            break;
+        }
+
+        unit_size *= 1024;
+        unit = next_unit;
+    }
+
+    if bytes % unit_size == 0 {
+        format!("{} {}", bytes / unit_size, unit)
+    } else {
+        format!("{:.1} {}", bytes as f64 / unit_size as f64, unit)
+    }
+}
+
 /// Represents a partition present on the disk
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PartEntry {
     /// The partition's name
+    ///
+    /// This is the only name a partition has; APA has no separate label, and
+    /// neither do the file systems within partitions. PFS records no volume
+    /// label in its superblock, and HDL partitions carry a game's title in
+    /// their game information, rather than a label for the partition.
     pub name: String,
     pub kind: Option<PartitionKind>,
+    /// The flags set on the partition's header
+    pub flags: PartitionFlags,
     /// The size of the partition, in bytes
     pub size: u64,
+    /// The sector of the disk the partition starts at; multiply by
+    /// [`apa::SECTOR_SIZE`] for its offset in bytes
+    ///
+    /// [`apa::SECTOR_SIZE`]: ../apa/constant.SECTOR_SIZE.html
+    pub start_sector: u64,
+}
+
+impl PartEntry {
+    /// Returns `true` if this is one of the system's own partitions, which
+    /// are named with a leading `__`, such as `__mbr` and `__system`.
+    pub fn is_system(&self) -> bool {
+        self.name.starts_with("__")
+    }
+
+    /// Formats the partition's size using binary units, such as `"128 MiB"`
+    /// or `"4 GiB"`, which partitions are always sized in.
+    pub fn human_size(&self) -> String {
+        human_size(self.size)
+    }
+
+    /// Checks whether this partition holds an HDLoader game, by checking that
+    /// it is an HDL partition and that its game information block carries
+    /// the HDL magic number.
+    ///
+    /// The game information itself is not read, which makes this a quick way
+    /// of filtering the partition list down to just games. Sub-partitions
+    /// never hold game information, so are never considered to be games.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition's header or game
+    /// information could not be read from `ps2hdd`.
+    pub fn contains_game(&self, ps2hdd: &crate::PS2HDD) -> Result<bool, Error> {
+        if self.kind != Some(PartitionKind::HDL) || self.flags.is_sub() {
+            return Ok(false);
+        }
+
+        ps2hdd.has_hdl_game_info(&self.name)
+    }
+
+    /// Returns the number of sub-partitions linked to this partition, as
+    /// recorded in its APA header, such as to describe a partition as being
+    /// "32 GiB, across 4 linked partitions".
+    ///
+    /// Partitions larger than the disk's maximum partition size are made up
+    /// of a main partition, followed by sub-partitions. Partitions which fit
+    /// in one piece, sub-partitions themselves, and free space all have none.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition's header could
+    /// not be read from `ps2hdd`.
+    pub fn sub_partition_count(&self, ps2hdd: &crate::PS2HDD) -> Result<u32, Error> {
+        if self.kind.is_none() || self.flags.is_sub() {
+            return Ok(0);
+        }
+
+        Ok(ps2hdd.read_apa_header(&self.name)?.0.nsub)
+    }
 }
 
 impl TryFrom<ps2hdd_sys::iox_dirent_t> for PartEntry {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(dirent: ps2hdd_sys::iox_dirent_t) -> std::result::Result<Self, Self::Error> {
+        Self::try_from(&dirent)
+    }
+}
+
+impl TryFrom<&ps2hdd_sys::iox_dirent_t> for PartEntry {
+    type Error = Error;
+
+    fn try_from(dirent: &ps2hdd_sys::iox_dirent_t) -> std::result::Result<Self, Self::Error> {
         let name = match unsafe { std::ffi::CStr::from_ptr(dirent.name.as_ptr()) }.to_str() {
             Ok(name) => name.to_owned(),
-            Err(error) => return Err(error.to_string()),
+            Err(error) => return Err(Error::Other(error.to_string())),
         };
 
         let kind = match dirent.stat.mode {
@@ -85,10 +1060,324 @@ impl TryFrom<ps2hdd_sys::iox_dirent_t> for PartEntry {
         Ok(Self {
             name,
             kind,
+            flags: PartitionFlags::from_bits(dirent.stat.attr),
             // stat size is in sectors, we want it in bytes
-            // notably, the sector size can be different per disk,
-            // but it's unclear whether the PS2 respects this
-            size: (dirent.stat.size as u64) * 512,
+            size: (dirent.stat.size as u64) * crate::apa::SECTOR_SIZE,
+            // The APA driver reports the start sector in the last private field
+            start_sector: dirent.stat.private_5 as u64,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_dirent(name: &str, mode: u32, size: u32) -> ps2hdd_sys::iox_dirent_t {
+        let mut dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
+
+        for (index, byte) in name.bytes().enumerate() {
+            dirent.name[index] = byte as std::os::raw::c_char;
+        }
+
+        dirent.stat.mode = mode;
+        dirent.stat.size = size;
+        // 2020-10-15 12:34:56 JST
+        dirent.stat.ctime = [0, 56, 34, 12, 15, 10, 0xe4, 0x07];
+        dirent.stat.atime = [0, 57, 34, 12, 15, 10, 0xe4, 0x07];
+        dirent.stat.mtime = [0, 58, 34, 12, 15, 10, 0xe4, 0x07];
+
+        dirent
+    }
+
+    #[test]
+    fn io_errors_have_kinds() {
+        assert_eq!(
+            io_error(-libc::ENOENT as i64).kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            io_error(-libc::EACCES as i64).kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+        assert_eq!(io_error(-libc::EIO as i64).raw_os_error(), Some(libc::EIO));
+    }
+
+    #[test]
+    fn dir_entry_metadata() {
+        let entry = DirEntry::new(
+            make_dirent("file.txt", ps2hdd_sys::FIO_S_IFREG | 0o644, 1234),
+            std::path::PathBuf::from("/"),
+        );
+
+        let metadata = entry.metadata().expect("couldn't get metadata");
+
+        assert_eq!(metadata.len(), 1234);
+        assert!(metadata.is_file());
+        assert!(!metadata.is_dir());
+        assert!(!metadata.is_partition());
+        assert_eq!(metadata.permissions(), 0o644);
+        assert_eq!(
+            metadata.created(),
+            Some(DateTime {
+                year: 2020,
+                month: 10,
+                day: 15,
+                hour: 12,
+                minute: 34,
+                second: 56
+            })
+        );
+        assert!(metadata.accessed() > metadata.created());
+        assert!(metadata.modified() > metadata.accessed());
+    }
+
+    #[test]
+    fn date_times_to_system_times() {
+        let entry = DirEntry::new(
+            make_dirent("file.txt", ps2hdd_sys::FIO_S_IFREG | 0o644, 1234),
+            std::path::PathBuf::from("/"),
+        );
+
+        assert_eq!(entry.created().map(|created| created.hour), Some(12));
+        assert_eq!(entry.created(), entry.metadata().unwrap().created());
+
+        // 2020-10-15 03:34:56 UTC
+        assert_eq!(
+            entry.created().and_then(|created| created.to_system_time()),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_602_732_896))
+        );
+
+        // Leap days, and the turn of the day in JST
+        let leap_day = DateTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 8,
+            minute: 59,
+            second: 59,
+        };
+        assert_eq!(
+            leap_day.to_system_time(),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_709_164_799))
+        );
+
+        assert_eq!(DateTime::from([0u8; 8]).to_system_time(), None);
+    }
+
+    #[test]
+    fn system_times_to_date_times() {
+        let raw = [0, 56, 34, 12, 15, 10, 0xe4, 0x07];
+        let date_time = DateTime::from(raw);
+
+        assert_eq!(<[u8; 8]>::from(date_time), raw);
+        assert_eq!(
+            DateTime::from_system_time(
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_602_732_896)
+            ),
+            Some(date_time)
+        );
+
+        // Leap days, and the turn of the day in JST
+        let leap_day = DateTime {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 8,
+            minute: 59,
+            second: 59,
+        };
+        assert_eq!(
+            leap_day
+                .to_system_time()
+                .and_then(DateTime::from_system_time),
+            Some(leap_day)
+        );
+
+        // Fractions of a second are dropped, even before the epoch
+        assert_eq!(
+            DateTime::from_system_time(
+                std::time::UNIX_EPOCH - std::time::Duration::from_millis(9 * 60 * 60 * 1000 + 500)
+            ),
+            Some(DateTime {
+                year: 1969,
+                month: 12,
+                day: 31,
+                hour: 23,
+                minute: 59,
+                second: 59,
+            })
+        );
+    }
+
+    #[test]
+    fn dir_entry_into_owned_parts() {
+        let entry = DirEntry::new(
+            make_dirent("file.txt", ps2hdd_sys::FIO_S_IFREG | 0o644, 1234),
+            std::path::PathBuf::from("/"),
+        );
+
+        let file_name = entry.file_name();
+        let metadata = entry.metadata().expect("couldn't get metadata");
+
+        let (owned_name, owned_metadata) = entry.into_owned_parts();
+
+        assert_eq!(owned_name, file_name);
+        assert_eq!(owned_name, "file.txt");
+        assert_eq!(owned_metadata, metadata);
+        assert_eq!(owned_metadata.len(), 1234);
+        assert!(owned_metadata.file_type().is_file());
+        assert_eq!(owned_metadata.modified(), metadata.modified());
+    }
+
+    #[test]
+    fn dir_entry_path() {
+        let entry = DirEntry::new(
+            make_dirent("b", ps2hdd_sys::FIO_S_IFDIR | 0o755, 0),
+            std::path::PathBuf::from("a"),
+        );
+        assert_eq!(entry.path(), std::path::PathBuf::from("a/b"));
+
+        let entry = DirEntry::new(
+            make_dirent("b", ps2hdd_sys::FIO_S_IFDIR | 0o755, 0),
+            std::path::PathBuf::from("/a"),
+        );
+        assert_eq!(entry.path(), std::path::PathBuf::from("/a/b"));
+
+        let entry = DirEntry::new(
+            make_dirent("file.txt", ps2hdd_sys::FIO_S_IFREG | 0o644, 0),
+            std::path::PathBuf::from("/"),
+        );
+        assert_eq!(entry.path(), std::path::PathBuf::from("/file.txt"));
+    }
+
+    #[test]
+    fn file_type_permissions() {
+        let file_type = FileType::from_mode(ps2hdd_sys::FIO_S_IFREG | 0o754);
+
+        assert!(file_type.is_file());
+        assert!(file_type.owner_can_read());
+        assert!(file_type.owner_can_write());
+        assert!(file_type.owner_can_execute());
+        assert!(file_type.group_can_read());
+        assert!(!file_type.group_can_write());
+        assert!(file_type.group_can_execute());
+        assert!(file_type.others_can_read());
+        assert!(!file_type.others_can_write());
+        assert!(!file_type.others_can_execute());
+
+        let file_type = FileType::from_mode(ps2hdd_sys::FIO_S_IFDIR | 0o023);
+
+        assert!(file_type.is_dir());
+        assert!(!file_type.owner_can_read());
+        assert!(!file_type.owner_can_write());
+        assert!(!file_type.owner_can_execute());
+        assert!(!file_type.group_can_read());
+        assert!(file_type.group_can_write());
+        assert!(!file_type.group_can_execute());
+        assert!(!file_type.others_can_read());
+        assert!(file_type.others_can_write());
+        assert!(file_type.others_can_execute());
+    }
+
+    #[test]
+    fn private_data_round_trip() {
+        let mut stat = make_dirent("file.txt", ps2hdd_sys::FIO_S_IFREG, 0).stat;
+        let mut bytes = [0u8; PRIVATE_DATA_SIZE];
+
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+
+        set_private_data(&mut stat, &bytes);
+
+        assert_eq!(stat.private_0, 0x0302_0100);
+        assert_eq!(stat.private_5, 0x1716_1514);
+        assert_eq!(private_data(&stat), bytes);
+    }
+
+    #[test]
+    fn part_entries_can_be_hashed() {
+        let entries: std::collections::HashSet<PartEntry> = vec![
+            PartEntry::try_from(make_dirent("TESTPART", 0x0100, 262144)),
+            PartEntry::try_from(make_dirent("TESTPART", 0x0100, 262144)),
+            PartEntry::try_from(make_dirent("TESTPART", 0x1337, 262144)),
+            PartEntry::try_from(make_dirent("OTHER", 0x0100, 262144)),
+        ]
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .expect("couldn't convert partition entries");
+
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn human_sizes() {
+        let entry = |size| PartEntry {
+            name: "TESTPART".to_string(),
+            kind: Some(PartitionKind::PFS),
+            flags: PartitionFlags::empty(),
+            size,
+            start_sector: 0,
+        };
+
+        assert_eq!(entry(128 * 1024 * 1024).human_size(), "128 MiB");
+        assert_eq!(entry(1024 * 1024 * 1024).human_size(), "1 GiB");
+        assert_eq!(entry(32 * 1024 * 1024 * 1024).human_size(), "32 GiB");
+        assert_eq!(entry(1023 * 1024 * 1024).human_size(), "1023 MiB");
+        assert_eq!(entry(2 * 1024 * 1024 * 1024 * 1024).human_size(), "2 TiB");
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(human_size(1024 * 1024 * 1024 - 1), "1.0 GiB");
+    }
+
+    #[test]
+    fn part_entry_metadata() {
+        let entry = PartEntry::try_from(make_dirent("TESTPART", 0x0100, 262144))
+            .expect("couldn't convert partition entry");
+
+        let mut dirent = make_dirent("TESTPART", 0x0100, 262144);
+        dirent.stat.private_5 = 1310720;
+
+        assert_eq!(
+            PartEntry::try_from(&dirent),
+            Ok(PartEntry {
+                name: "TESTPART".to_string(),
+                kind: Some(PartitionKind::PFS),
+                flags: PartitionFlags::empty(),
+                size: 128 * 1024 * 1024,
+                start_sector: 1310720
+            })
+        );
+
+        assert!(!entry.is_system());
+        assert!(PartEntry::try_from(make_dirent("__system", 0x0100, 262144))
+            .expect("couldn't convert partition entry")
+            .is_system());
+
+        let metadata = Metadata::from(&entry);
+
+        assert_eq!(metadata.len(), 128 * 1024 * 1024);
+        assert!(metadata.is_partition());
+        assert_eq!(metadata.partition_kind(), Some(PartitionKind::PFS));
+        assert!(!metadata.is_file());
+        assert!(!metadata.is_dir());
+        assert_eq!(metadata.created(), None);
+    }
+
+    #[test]
+    fn part_entry_kinds_from_mode() {
+        let kind = |mode| {
+            PartEntry::try_from(make_dirent("TESTPART", mode, 262144))
+                .expect("couldn't convert partition entry")
+                .kind
+        };
+
+        assert_eq!(kind(0x0000), None);
+        assert_eq!(kind(0x0083), Some(PartitionKind::EXT2));
+        assert_eq!(kind(0x4242), Some(PartitionKind::Unknown(0x4242)));
+        assert_eq!(kind(0xffff), Some(PartitionKind::Unknown(0xffff)));
+    }
+}
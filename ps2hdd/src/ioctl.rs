@@ -0,0 +1,88 @@
+//! Commands for the `ioctl2` interface of the APA and PFS drivers, which
+//! provide functionality not otherwise reachable through the file and
+//! directory functions
+
+use crate::error::Error;
+use crate::ffi_utils::ok_on_nonnegative_or_strerror;
+
+/// A command which may be issued via `ioctl2`.
+///
+/// Each command is only understood by one driver; the `Partition*` commands
+/// apply to a partition opened on the APA (`hdd0:`) device, and the others to
+/// a file opened on a PFS device.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ioctl2Command {
+    /// Adds a sub-partition to a partition (`HIOCADDSUB`, `0x6801`).
+    ///
+    /// Takes the sub-partition's size as a little-endian `u32`, in sectors.
+    PartitionAddSub,
+    /// Removes the last sub-partition from a partition (`HIOCDELSUB`,
+    /// `0x6802`).
+    PartitionDeleteSub,
+    /// Returns the number of sub-partitions a partition has (`HIOCNSUB`,
+    /// `0x6803`).
+    PartitionCountSubs,
+    /// Flushes the APA driver's cache to disk (`HIOCFLUSH`, `0x6804`).
+    PartitionFlush,
+    /// Pre-allocates space for a file (`PIOCALLOC`, `0x7001`).
+    ///
+    /// Takes the number of zones to allocate as a little-endian `u32`.
+    FileAllocate,
+    /// Frees pre-allocated space for a file (`PIOCFREE`, `0x7002`).
+    FileFree,
+    /// Adds an extended attribute to a file (`PIOCATTRADD`, `0x7003`).
+    FileAttributeAdd,
+    /// Deletes an extended attribute from a file (`PIOCATTRDEL`, `0x7004`).
+    FileAttributeDelete,
+    /// Looks up an extended attribute of a file (`PIOCATTRLOOKUP`, `0x7005`).
+    FileAttributeLookup,
+    /// Reads the extended attributes of a file (`PIOCATTRREAD`, `0x7006`).
+    FileAttributeRead,
+}
+
+impl Ioctl2Command {
+    /// Returns the numeric command code understood by the drivers.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::PartitionAddSub => 0x6801,
+            Self::PartitionDeleteSub => 0x6802,
+            Self::PartitionCountSubs => 0x6803,
+            Self::PartitionFlush => 0x6804,
+            Self::FileAllocate => 0x7001,
+            Self::FileFree => 0x7002,
+            Self::FileAttributeAdd => 0x7003,
+            Self::FileAttributeDelete => 0x7004,
+            Self::FileAttributeLookup => 0x7005,
+            Self::FileAttributeRead => 0x7006,
+        }
+    }
+}
+
+/// Issues an `ioctl2` command to an open file handle.
+///
+/// `arg` is passed to the driver as the command's input, and up to
+/// `buf.len()` bytes of output are written into `buf`. The driver's
+/// non-negative return value is passed through.
+pub(crate) fn ioctl2(
+    handle: std::os::raw::c_int,
+    command: Ioctl2Command,
+    arg: &[u8],
+    buf: &mut [u8],
+) -> Result<std::os::raw::c_int, Error> {
+    // The drivers never write through `arg`, but the signature isn't const
+    let mut arg = arg.to_vec();
+
+    ok_on_nonnegative_or_strerror(
+        unsafe {
+            ps2hdd_sys::iomanx_ioctl2(
+                handle,
+                command.code(),
+                arg.as_mut_ptr() as *mut core::ffi::c_void,
+                arg.len() as std::os::raw::c_uint,
+                buf.as_mut_ptr() as *mut core::ffi::c_void,
+                buf.len() as std::os::raw::c_uint,
+            )
+        },
+        &format!("ioctl2 command {:#06x} failed", command.code()),
+    )
+}
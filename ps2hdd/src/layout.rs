@@ -0,0 +1,176 @@
+//! Declarative descriptions of the partitions to create on a disk, as applied
+//! by [`PS2HDD::apply_layout`](../struct.PS2HDD.html#method.apply_layout)
+//!
+//! With the `serde` feature enabled, layouts can be read from JSON with
+//! [`PS2HDD::import_layout_json`](../struct.PS2HDD.html#method.import_layout_json),
+//! as an array of partitions:
+//!
+//! ```json
+//! [
+//!     { "name": "+OPL", "kind": "PFS", "size": 128 },
+//!     { "name": "PP.GAME", "kind": "HDL", "size": 4096 }
+//! ]
+//! ```
+
+use std::collections::HashSet;
+
+use crate::error::Error;
+use crate::partition_kind::FormattablePartitionKind;
+
+/// A single partition to be created as part of a layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct LayoutEntry {
+    pub name: String,
+    pub kind: FormattablePartitionKind,
+    /// The size of the partition in mebibytes, with the same valid sizes as
+    /// [`PS2HDD::create_partition`](../struct.PS2HDD.html#method.create_partition)
+    pub size: u64,
+}
+
+impl LayoutEntry {
+    pub fn new(name: &str, kind: FormattablePartitionKind, size: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            size,
+        }
+    }
+}
+
+/// Parses a layout from a JSON array of partitions.
+#[cfg(feature = "serde")]
+pub fn parse_json(json: &str) -> Result<Vec<LayoutEntry>, Error> {
+    match serde_json::from_str(json) {
+        Ok(layout) => Ok(layout),
+        Err(error) => Err(format!("couldn't parse layout: {}", error)),
+    }
+}
+
+/// Checks that every partition in `layout` has a valid, unique name, which
+/// isn't one of `existing_names`, and a valid size, and that together they
+/// fit within `free_space` mebibytes.
+pub(crate) fn validate(
+    layout: &[LayoutEntry],
+    existing_names: &[String],
+    free_space: u64,
+) -> Result<(), Error> {
+    let mut names = HashSet::new();
+    let mut total = 0;
+
+    for entry in layout {
+        if entry.name.is_empty() || entry.name.len() >= crate::apa::APA_ID_MAX {
+            return Err(Error::Other(format!(
+                "Partition name {:?} must be between 1 and {} bytes long",
+                entry.name,
+                crate::apa::APA_ID_MAX - 1
+            )));
+        }
+
+        if entry.name.contains(',') || entry.name.contains('\0') {
+            return Err(Error::Other(format!(
+                "Partition name {:?} may not contain commas or null characters",
+                entry.name
+            )));
+        }
+
+        if !names.insert(entry.name.as_str()) {
+            return Err(Error::Other(format!(
+                "Partition {} appears more than once in the layout",
+                entry.name
+            )));
+        }
+
+        if existing_names.contains(&entry.name) {
+            return Err(Error::Other(format!(
+                "Partition {} already exists on the disk",
+                entry.name
+            )));
+        }
+
+        if !entry.size.is_power_of_two()
+            || !(crate::MIN_PARTITION_SIZE..=crate::MAX_PARTITION_SIZE).contains(&entry.size)
+        {
+            return Err(Error::Other(format!(
+                "Partition {}: Partition size must be a power of 2 from {}MiB to {}MiB",
+                entry.name,
+                crate::MIN_PARTITION_SIZE,
+                crate::MAX_PARTITION_SIZE
+            )));
+        }
+
+        total += entry.size;
+
+        if total > free_space {
+            return Err(Error::Other(format!(
+                "Partition {} of {}MiB doesn't fit; the layout needs {}MiB more than the {}MiB free",
+                entry.name,
+                entry.size,
+                total - free_space,
+                free_space
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_layouts() {
+        let existing = vec!["__mbr".to_string(), "TAKEN".to_string()];
+        let entry = |name: &str, size| LayoutEntry::new(name, FormattablePartitionKind::PFS, size);
+
+        assert_eq!(
+            validate(&[entry("A", 128), entry("B", 256)], &existing, 384),
+            Ok(())
+        );
+
+        assert_eq!(
+            validate(
+                &[entry("A", 128), entry("B", 256), entry("C", 512)],
+                &existing,
+                512
+            ),
+            Err(Error::Other("Partition C of 512MiB doesn't fit; the layout needs 384MiB more than the 512MiB free"
+                    .to_string()))
+        );
+
+        assert_eq!(
+            validate(&[entry("A", 128), entry("A", 128)], &existing, 1024),
+            Err(Error::Other(
+                "Partition A appears more than once in the layout".to_string()
+            ))
+        );
+
+        assert_eq!(
+            validate(&[entry("TAKEN", 128)], &existing, 1024),
+            Err(Error::Other(
+                "Partition TAKEN already exists on the disk".to_string()
+            ))
+        );
+
+        assert!(validate(&[entry("A", 100)], &existing, 1024).is_err());
+        assert!(validate(&[entry("", 128)], &existing, 1024).is_err());
+        assert!(validate(&[entry(&"X".repeat(32), 128)], &existing, 1024).is_err());
+        assert!(validate(&[entry("A,B", 128)], &existing, 1024).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn parses_json_layouts() {
+        assert_eq!(
+            parse_json(r#"[{ "name": "+OPL", "kind": "PFS", "size": 128 }]"#),
+            Ok(vec![LayoutEntry::new(
+                "+OPL",
+                FormattablePartitionKind::PFS,
+                128
+            )])
+        );
+
+        assert!(parse_json(r#"[{ "name": "+OPL", "kind": "FAT", "size": 128 }]"#).is_err());
+    }
+}
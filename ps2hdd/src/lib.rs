@@ -2,18 +2,38 @@
 //! [`pfsshell`](https://github.com/ps2homebrew/pfsshell), providing utilities
 //! for reading and writing PlayStation®2 format hard disks and disk images.
 
-use std::convert::TryInto;
+use std::convert::TryFrom;
+use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 
+use log::{debug, error, warn};
+
+pub mod apa;
+
+pub mod digest;
+use crate::digest::{DigestAlgorithm, DigestRegion};
+
 pub mod driver;
-use crate::driver::{HDLFS, PFS};
+use crate::driver::{Driver, MountedDriver, HDLFS, PFS};
+
+pub mod error;
+pub use crate::error::Error;
 
 pub mod fs;
 use crate::fs::PartEntry;
 
+pub mod ioctl;
+use crate::ioctl::Ioctl2Command;
+
+pub mod layout;
+use crate::layout::LayoutEntry;
+
 pub mod partition_kind;
-use crate::partition_kind::{FormattablePartitionKind, PartitionKind};
+use crate::partition_kind::{DetectedFs, FormattablePartitionKind, PartitionKind};
+
+pub mod snapshot;
+use crate::snapshot::{FileSnapshot, Snapshot, SnapshotDiff};
 
 mod ffi_utils;
 use ffi_utils::{ok_on_nonnegative_or_strerror, ok_on_zero_or_strerror};
@@ -22,6 +42,315 @@ use ffi_utils::{ok_on_nonnegative_or_strerror, ok_on_zero_or_strerror};
 // so we keep track of it via this atomic boolean
 static IS_DEVICE_ACTIVE: AtomicBool = AtomicBool::new(false);
 
+/// Resets `IS_DEVICE_ACTIVE` when dropped, unless disarmed, so that opening a
+/// device which fails part way, whether by returning an error or panicking,
+/// doesn't stop any other device from being opened
+struct DeviceActiveGuard {
+    armed: bool,
+}
+
+impl DeviceActiveGuard {
+    fn new() -> Self {
+        Self { armed: true }
+    }
+
+    /// Leaves the device marked as active, for the opened `PS2HDD` to reset
+    /// once it's dropped.
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for DeviceActiveGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+/// When set, the next attempt to open a device panics part way through, to
+/// test that the device isn't left marked as active
+#[cfg(test)]
+static PANIC_WHILE_OPENING: AtomicBool = AtomicBool::new(false);
+
+/// The largest disk APA can address, in bytes; sector numbers are 32-bit, so
+/// only the first 2TiB of a disk can ever be used
+pub const MAX_DISK_SIZE: u64 = (1 << 32) * apa::SECTOR_SIZE;
+
+/// The partitions created by `initialize`, which must be present for the disk
+/// to be usable by the PlayStation®2
+const DEFAULT_PARTITIONS: [(&str, PartitionKind); 5] = [
+    ("__mbr", PartitionKind::MBR),
+    ("__net", PartitionKind::PFS),
+    ("__system", PartitionKind::PFS),
+    ("__sysconf", PartitionKind::PFS),
+    ("__common", PartitionKind::PFS),
+];
+
+/// Options which control the default partitions created by
+/// [`PS2HDD::initialize_with`].
+///
+/// By default, each of `__net`, `__system`, `__sysconf` and `__common` is
+/// created at 128MiB, as [`PS2HDD::initialize`] does. Any of them may be
+/// resized, or omitted by setting their size to `None`. `__mbr` is always
+/// created, and always 128MiB.
+///
+/// ```
+/// use ps2hdd::InitializeOptions;
+///
+/// let options = InitializeOptions::new().system(Some(256)).net(None);
+///
+/// assert_eq!(options.system_size(), Some(256));
+/// assert_eq!(options.net_size(), None);
+/// assert_eq!(options.common_size(), Some(128));
+/// ```
+///
+/// [`PS2HDD::initialize_with`]: struct.PS2HDD.html#method.initialize_with
+/// [`PS2HDD::initialize`]: struct.PS2HDD.html#method.initialize
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InitializeOptions {
+    /// The size of each default partition after `__mbr`, in mebibytes, in the
+    /// order they're created
+    sizes: [Option<u64>; 4],
+}
+
+impl Default for InitializeOptions {
+    fn default() -> Self {
+        Self {
+            sizes: [Some(MIN_PARTITION_SIZE); 4],
+        }
+    }
+}
+
+impl InitializeOptions {
+    /// Creates a set of options which creates the same partitions as
+    /// [`PS2HDD::initialize`](struct.PS2HDD.html#method.initialize).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the size of `__net`, in mebibytes, or omits it if `None`.
+    pub fn net(mut self, size: Option<u64>) -> Self {
+        self.sizes[0] = size;
+        self
+    }
+
+    pub fn net_size(&self) -> Option<u64> {
+        self.sizes[0]
+    }
+
+    /// Sets the size of `__system`, in mebibytes, or omits it if `None`.
+    pub fn system(mut self, size: Option<u64>) -> Self {
+        self.sizes[1] = size;
+        self
+    }
+
+    pub fn system_size(&self) -> Option<u64> {
+        self.sizes[1]
+    }
+
+    /// Sets the size of `__sysconf`, in mebibytes, or omits it if `None`.
+    pub fn sysconf(mut self, size: Option<u64>) -> Self {
+        self.sizes[2] = size;
+        self
+    }
+
+    pub fn sysconf_size(&self) -> Option<u64> {
+        self.sizes[2]
+    }
+
+    /// Sets the size of `__common`, in mebibytes, or omits it if `None`.
+    pub fn common(mut self, size: Option<u64>) -> Self {
+        self.sizes[3] = size;
+        self
+    }
+
+    pub fn common_size(&self) -> Option<u64> {
+        self.sizes[3]
+    }
+}
+
+/// Options for how [`PS2HDD::create_with`] allocates a new disk image.
+///
+/// By default, images are created sparse, as [`PS2HDD::create`] does, so
+/// that creating even a very large image is quick, and takes up no more space
+/// on the host than has been written to it, where the host file system
+/// supports sparse files.
+///
+/// [`PS2HDD::create_with`]: struct.PS2HDD.html#method.create_with
+/// [`PS2HDD::create`]: struct.PS2HDD.html#method.create
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct CreateOptions {
+    preallocate: bool,
+}
+
+impl CreateOptions {
+    /// Creates a set of options which creates a sparse image, as
+    /// [`PS2HDD::create`](struct.PS2HDD.html#method.create) does.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the entire image is written out with zeros when it's
+    /// created, so that all of its space is allocated on the host up front.
+    ///
+    /// This guarantees the space is available, and gives the host file
+    /// system the best chance to allocate it contiguously, but takes as long
+    /// as writing the whole image.
+    pub fn preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    pub fn preallocates(&self) -> bool {
+        self.preallocate
+    }
+}
+
+/// Options for how [`PS2HDD::format_partition`] formats a partition.
+///
+/// By default, partitions which already hold a file system or an installed
+/// game are left alone, and the result isn't verified.
+///
+/// [`PS2HDD::format_partition`]: struct.PS2HDD.html#method.format_partition
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct FormatOptions {
+    force: bool,
+    verify: bool,
+}
+
+impl FormatOptions {
+    /// Creates a set of options which refuses to format over existing data,
+    /// and doesn't verify the result.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the partition is formatted even if it already holds a
+    /// PFS file system or HDL game, destroying them.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn forces(&self) -> bool {
+        self.force
+    }
+
+    /// Sets whether the freshly-formatted file system is mounted and its root
+    /// directory read back, to ensure the format actually produced a usable
+    /// file system. Only PFS file systems can currently be verified; for
+    /// other kinds, this is ignored.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn verifies(&self) -> bool {
+        self.verify
+    }
+}
+
+/// The offset of the PFS superblock within a partition; sector 8192, after
+/// the space reserved for the APA header
+const PFS_SUPER_OFFSET: u64 = 8192 * apa::SECTOR_SIZE;
+
+/// The most sectors which
+/// [`PS2HDD::read_leading_sectors`](struct.PS2HDD.html#method.read_leading_sectors)
+/// will read; the space reserved for the `__mbr` partition's APA header,
+/// before its file system begins
+pub const MAX_LEADING_SECTORS: u64 = PFS_SUPER_OFFSET / apa::SECTOR_SIZE;
+
+/// The magic number at the start of a PFS superblock
+const PFS_SUPER_MAGIC: u32 = 0x5046_5300;
+
+/// The offset of the EXT2 magic number within a partition; 56 bytes into
+/// the superblock, which follows the reserved space after the APA header
+const EXT2_MAGIC_OFFSET: u64 = PFS_SUPER_OFFSET + 1024 + 56;
+
+/// The magic number found in an EXT2 superblock
+const EXT2_MAGIC: u32 = 0xef53;
+
+/// The size of the buffer used to write zeros over preallocated images
+const PREALLOCATE_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The most recent failure reported by the drivers, as returned by
+/// [`PS2HDD::last_error`](struct.PS2HDD.html#method.last_error).
+///
+/// Formats the same way as the [`Error::Io`](enum.Error.html#variant.Io) the
+/// failing call produced, even where that was reported as a more specific
+/// variant such as `NotFound`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastError {
+    operation: String,
+    code: i32,
+}
+
+impl LastError {
+    /// Describes the operation which failed, such as `"failed to open file"`.
+    pub fn operation(&self) -> &str {
+        &self.operation
+    }
+
+    /// Returns the negative error number the driver failed with, such as
+    /// `-2` for `ENOENT`.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+}
+
+impl std::fmt::Display for LastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match ffi_utils::strerror(self.code) {
+            Ok(message) => write!(f, "{}: {}, {}", self.operation, self.code, message),
+            Err(_) => write!(f, "{}: {}", self.operation, self.code),
+        }
+    }
+}
+
+/// A recoverable problem found while opening a disk with
+/// [`PS2HDD::open_with_warnings`], which didn't stop it from being opened.
+///
+/// Formats as a message suitable for showing to users.
+///
+/// [`PS2HDD::open_with_warnings`]: struct.PS2HDD.html#method.open_with_warnings
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpenWarning {
+    /// The disk had a pending APA journal, holding this many partition
+    /// headers, which was replayed as the disk was opened; the disk wasn't
+    /// cleanly unmounted
+    PendingJournal(usize),
+    /// The disk's APA journal could not be read before opening the disk
+    UnreadableJournal(Error),
+    /// A partition is placed inconsistently with the rest of the partition
+    /// map
+    PartitionConflict(apa::PartitionConflict),
+}
+
+impl std::fmt::Display for OpenWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PendingJournal(count) => write!(
+                f,
+                "The disk wasn't cleanly unmounted; {} partition header(s) were restored from its journal",
+                count
+            ),
+            Self::UnreadableJournal(message) => {
+                write!(f, "The disk's journal could not be read: {}", message)
+            }
+            Self::PartitionConflict(apa::PartitionConflict::Overlapping(a, b)) => {
+                write!(f, "Partitions {} and {} overlap", a.id, b.id)
+            }
+            Self::PartitionConflict(apa::PartitionConflict::OutOfBounds(header)) => write!(
+                f,
+                "Partition {} extends past the end of the disk",
+                header.id
+            ),
+        }
+    }
+}
+
 static PFS_ZONE_SIZE: i32 = 8192;
 static PFS_FRAGMENT: i32 = 0x0000_0000;
 
@@ -58,46 +387,244 @@ impl PS2HDD {
     /// this process, if `path` does not already exist or is not a file, if
     /// `path` is longer than 255 characters, or if there is any error
     /// initialising the subsystems which read and write the PS2 HDD.
-    pub fn open<P: std::fmt::Debug + AsRef<Path>>(path: P) -> Result<Self, String> {
+    pub fn open<P: std::fmt::Debug + AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::open_impl(path, true)
+    }
+
+    /// Opens the PS2 HDD at `path`, calls `f` with it, and then closes it
+    /// again, returning what `f` returned.
+    ///
+    /// As the drivers only support one disk at a time, this is the way to
+    /// work with several disks within one process: each in turn, with the
+    /// previous one fully closed before the next is opened. Anything mounted
+    /// is unmounted before the disk is closed, as it is whenever a `PS2HDD`
+    /// is dropped, so nothing carries over between disks. For example, to
+    /// copy a file from one disk to another:
+    ///
+    /// ```no_run
+    /// use ps2hdd::driver::Driver;
+    /// use ps2hdd::{Error, PS2HDD};
+    ///
+    /// let contents = PS2HDD::with_device("a.img", |ps2hdd| {
+    ///     ps2hdd.mount_pfs("+OPL")?.read_file("conf_opl.cfg")
+    /// })??;
+    ///
+    /// PS2HDD::with_device("b.img", |ps2hdd| {
+    ///     ps2hdd.mount_pfs("+OPL")?.write_file("conf_opl.cfg", &contents)
+    /// })??;
+    /// # Ok::<(), Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in any of the cases [`open`] does,
+    /// in which case `f` isn't called.
+    ///
+    /// [`open`]: #method.open
+    pub fn with_device<P, T, F>(path: P, f: F) -> Result<T, Error>
+    where
+        P: std::fmt::Debug + AsRef<Path>,
+        F: FnOnce(&mut PS2HDD) -> T,
+    {
+        let mut ps2hdd = Self::open(path)?;
+
+        Ok(f(&mut ps2hdd))
+    }
+
+    /// Attempts to open a PS2 HDD, as [`open`] does, also returning any
+    /// recoverable problems found with it, such as it not having been cleanly
+    /// unmounted.
+    ///
+    /// The APA journal is inspected before the disk is opened, as opening it
+    /// replays any pending journal, and the partition map is checked for
+    /// conflicts afterwards, as [`detect_overlaps`] does.
+    ///
+    /// [`open`]: #method.open
+    /// [`detect_overlaps`]: #method.detect_overlaps
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in any of the cases [`open`] does.
+    pub fn open_with_warnings<P: std::fmt::Debug + AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, Vec<OpenWarning>), Error> {
+        let mut warnings = Vec::new();
+
+        // Inspecting the journal fails if a disk is already open, in which
+        // case opening this one will fail too, with a clearer message
+        let journal = if IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            Ok(0)
+        } else {
+            Self::pending_journal_entries(&path)
+        };
+
+        match journal {
+            Ok(0) => (),
+            Ok(count) => warnings.push(OpenWarning::PendingJournal(count)),
+            Err(message) => warnings.push(OpenWarning::UnreadableJournal(message)),
+        }
+
+        let ps2hdd = Self::open(path)?;
+
+        match ps2hdd.detect_overlaps() {
+            Ok(conflicts) => {
+                warnings.extend(conflicts.into_iter().map(OpenWarning::PartitionConflict))
+            }
+            Err(message) => warn!(
+                "Could not check the partition map for conflicts: {}",
+                message
+            ),
+        }
+
+        Ok((ps2hdd, warnings))
+    }
+
+    /// Attempts to open a PS2 HDD, without first checking that `path` refers
+    /// to a regular file.
+    ///
+    /// This permits opening FIFOs, character devices, and other special files
+    /// which [`open`] rejects, relying on the underlying drivers to report
+    /// any problems with them.
+    ///
+    /// **Use with care!** The APA driver will exit the entire process, rather
+    /// than returning an error, if it is unable to open `path`. `path` is
+    /// still checked to exist, but nothing else is done to avoid this.
+    ///
+    /// [`open`]: #method.open
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a PS2 HDD is already open in
+    /// this process, if `path` does not exist, if `path` is longer than 255
+    /// characters, or if there is any error initialising the subsystems which
+    /// read and write the PS2 HDD.
+    pub fn open_unchecked<P: std::fmt::Debug + AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::open_impl(path, false)
+    }
+
+    /// Attempts to open a PS2 HDD, giving up if the drivers have not finished
+    /// initialising within `timeout`.
+    ///
+    /// This is intended for physical drives which may be slow, or fail to
+    /// respond at all; initialisation happens on a separate thread, so that
+    /// control is returned to the caller once `timeout` has elapsed.
+    ///
+    /// The drivers cannot be interrupted, so on timeout the initialisation
+    /// carries on in the background. Until it finishes, the device remains
+    /// active, and attempts to open another PS2 HDD will fail. If it does
+    /// eventually succeed, the device is closed again with `atad_close`,
+    /// just as if the `PS2HDD` had been dropped; if the drive never responds,
+    /// the device will never be released within this process.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if initialisation does not finish
+    /// within `timeout`, or in any of the cases [`open`] does.
+    ///
+    /// [`open`]: #method.open
+    pub fn open_with_timeout<P: AsRef<Path>>(
+        path: P,
+        timeout: std::time::Duration,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let display_path = path.display().to_string();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            // If the receiver has given up, the returned instance is dropped
+            // here instead, which closes the device
+            if sender.send(Self::open(path)).is_err() {
+                warn!("Opening PS2 HDD finished after timing out");
+            }
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                error!("Timed out opening PS2 HDD at {}", display_path);
+                Err(Error::Other(format!(
+                    "{}: Timed out after {:?} waiting for the drive to respond",
+                    display_path, timeout
+                )))
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err(Error::Other(format!(
+                "{}: Drive initialisation failed unexpectedly",
+                display_path
+            ))),
+        }
+    }
+
+    fn open_impl<P: std::fmt::Debug + AsRef<Path>>(
+        path: P,
+        check_is_file: bool,
+    ) -> Result<Self, Error> {
         if IS_DEVICE_ACTIVE.swap(true, std::sync::atomic::Ordering::Relaxed) {
-            return Err("Only one PS2HDD instance may be mounted at a time".to_string());
+            return Err(Error::DeviceAlreadyOpen);
         }
 
         // IMPORTANT: In every case that this function can return an Err or
-        // panic, EXCEPT the initial check, it MUST reset IS_DEVICE_ACTIVE
+        // panic, EXCEPT the initial check, IS_DEVICE_ACTIVE must be reset;
+        // this guard does so unless it's disarmed once opening has succeeded
+        let guard = DeviceActiveGuard::new();
 
-        if !path.as_ref().is_file() {
-            IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-            return Err(format!("{}: No such file", path.as_ref().display()));
+        debug!("Opening PS2 HDD at {}", path.as_ref().display());
+
+        if check_is_file && !path.as_ref().is_file() {
+            return Err(Error::NotFound {
+                name: path.as_ref().display().to_string(),
+                kind: "file",
+            });
         }
 
-        let path_str = match path.as_ref().to_str() {
-            Some(str) => str,
-            None => {
-                IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-                return Err("could not convert path to slice".to_string());
+        if !path.as_ref().exists() {
+            return Err(Error::NotFound {
+                name: path.as_ref().display().to_string(),
+                kind: "file or device",
+            });
+        }
+
+        #[cfg(test)]
+        {
+            if PANIC_WHILE_OPENING.swap(false, std::sync::atomic::Ordering::Relaxed) {
+                panic!("Panicking while opening, as requested");
             }
+        }
+
+        Self::init_drivers(path.as_ref())?;
+
+        guard.disarm();
+
+        Ok(PS2HDD {
+            path: path.as_ref().to_path_buf(),
+            pfs: None,
+            hdlfs: None,
+        })
+    }
+
+    /// Points the drivers at the disk at `path`, and initialises them.
+    ///
+    /// If the drivers fail to initialise, the disk is closed again, but
+    /// `IS_DEVICE_ACTIVE` is left for the caller to reset.
+    fn init_drivers(path: &Path) -> Result<(), Error> {
+        let path_str = match path.to_str() {
+            Some(str) => str,
+            None => return Err(Error::Other("could not convert path to slice".to_string())),
         };
 
         let name_slice = match std::ffi::CString::new(path_str) {
             Ok(name) => name,
             Err(error) => {
-                IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-                return Err(format!(
-                    "could not convert path slice to C String: {}",
-                    error.to_string()
-                ));
+                return Err(Error::NulByte {
+                    context: "could not convert path slice to C String",
+                    error,
+                })
             }
         };
 
         let length = name_slice.as_bytes().len();
 
         if length > 255 {
-            IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-            return Err(format!(
-                "Path of length {} is too long to be null-terminated",
-                length
-            ));
+            return Err(Error::PathTooLong(length));
         }
 
         name_slice
@@ -114,16 +641,16 @@ impl PS2HDD {
         match unsafe { std::ffi::CStr::from_ptr(ps2hdd_sys::atad_device_path.as_ptr()) }.to_str() {
             Ok(after_path) => {
                 if path_str != after_path {
-                    IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-                    return Err("updating the device path variable didn't work, weird!".to_string());
+                    return Err(Error::Other(
+                        "updating the device path variable didn't work, weird!".to_string(),
+                    ));
                 }
             }
             Err(error) => {
-                IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-                return Err(format!(
+                return Err(Error::Other(format!(
                     "could not convert the updated device path to a String: {}",
                     error
-                ));
+                )))
             }
         };
 
@@ -150,9 +677,9 @@ impl PS2HDD {
             unsafe { ps2hdd_sys::_init_apa(0, std::ptr::null_mut()) },
             "Unable to initialize APA partition driver",
         ) {
+            error!("{}", message);
             // We run atad_close to ensure no file is open if this fails
             unsafe { ps2hdd_sys::atad_close() };
-            IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
             return Err(message);
         };
 
@@ -177,398 +704,5653 @@ impl PS2HDD {
             unsafe { ps2hdd_sys::_init_pfs(0, std::ptr::null_mut()) },
             "Unable to initialize PFS filesystem driver",
         ) {
+            error!("{}", message);
             // We run atad_close to ensure no file is open if this fails
             unsafe { ps2hdd_sys::atad_close() };
-            IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
             return Err(message);
         };
 
         // TODO: _init_hdlfs
 
-        Ok(PS2HDD {
-            path: path.as_ref().to_path_buf(),
-            pfs: None,
-            hdlfs: None,
-        })
+        Ok(())
     }
 
-    /// Attempts to create and subsequently open a new PS2 HDD image file.
+    /// Checks whether the file at `path` looks like a PS2 HDD, by reading the
+    /// APA magic number from the first partition header.
     ///
-    /// # Errors
+    /// This reads the file directly, and does not touch the state shared with
+    /// an open `PS2HDD`, so it may be used whether or not one is open.
     ///
-    /// This function will return an error if a PS2 HDD is already open in
-    /// this process, if `path` already exists, if `path` is longer than 255
-    /// characters, if the file could not be extended to the desired size, or
-    /// if there is any error initialising the subsystems which read and write
-    /// the PS2 HDD image.
-    pub fn create<P: std::fmt::Debug + AsRef<Path>>(path: P, size: u64) -> Result<Self, String> {
-        match std::fs::File::create(&path) {
-            Err(error) => return Err(error.to_string()),
-            Ok(file) => {
-                if let Err(error) = file.set_len(size) {
-                    return Err(error.to_string());
-                }
-                drop(file);
-            }
-        }
-
-        Self::open(path)
+    /// Note that a `true` result does not guarantee the disk is usable, only
+    /// that it has been initialised as an APA partition mapped disk at some
+    /// point.
+    pub fn is_ps2_disk<P: AsRef<Path>>(path: P) -> bool {
+        apa::has_apa_magic(path)
     }
 
-    /// Format the entire disk, creating the APA partition map, and
-    /// default set of partitions `__mbr`, `__net`, `__system`, `__sysconf` and
-    /// `__common`.
+    /// Reads every partition header in the APA partition map, for later
+    /// restoration with [`restore_partition_table`].
     ///
-    /// Note that this is a destructive process, and data *will* be destroyed.
+    /// Only the partition map itself is included; the contents of the
+    /// partitions are not. The backup is the raw headers, in the order they
+    /// are linked together, each of which records its own location on disk.
+    ///
+    /// [`restore_partition_table`]: #method.restore_partition_table
     ///
     /// # Errors
     ///
-    /// This function will return an error if partitions could not be created.
-    pub fn initialize(&self) -> Result<(), String> {
-        let device = match std::ffi::CString::new("hdd0:") {
-            Ok(device_path) => device_path,
-            Err(error) => return Err(error.to_string()),
+    /// This function will return an error if the disk could not be read, or
+    /// if the partition map could not be followed.
+    pub fn backup_partition_table(&self) -> Result<Vec<u8>, Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
         };
 
-        ok_on_zero_or_strerror(
-            unsafe {
-                ps2hdd_sys::iomanx_format(
-                    device.as_ptr(),
-                    std::ptr::null_mut(),
-                    std::ptr::null_mut(),
-                    0,
-                )
-            },
-            "HDD formatting failed",
-        )?;
-
-        Ok(())
+        Ok(apa::read_partition_map(&mut file)?.concat())
     }
 
-    /// List all the partitions on the disk.
+    /// Writes a partition map previously read by [`backup_partition_table`]
+    /// back to the disk at `path`.
     ///
-    /// Note that unlike `std::fs::read_dir` or the like, which return an
-    /// iterator, all entries are fetched upfront, due to the underlying
-    /// driver involving internal state we can't fully rely on.
-    pub fn list_partitions(&self) -> Result<Vec<PartEntry>, String> {
-        let path = match std::ffi::CString::new("hdd0:") {
-            Ok(path) => path,
-            Err(error) => return Err(format!("couldn't convert string: {}", error)),
+    /// Because the drivers cache the partition map, this may only be done
+    /// while no PS2 HDD is open within this process.
+    ///
+    /// Note that only the partition map is restored. If the partitions have
+    /// been created, deleted or resized since the backup was taken, their
+    /// contents will no longer line up with the restored map, and accessing
+    /// them will corrupt data!
+    ///
+    /// [`backup_partition_table`]: #method.backup_partition_table
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a PS2 HDD is open in this
+    /// process, if `table` is not a valid partition map, if any partition
+    /// within it would extend past the end of the disk at `path`, or if the
+    /// disk could not be written to.
+    pub fn restore_partition_table<P: AsRef<Path>>(path: P, table: &[u8]) -> Result<(), Error> {
+        if IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Other(
+                "Partition tables may not be restored while a PS2HDD instance is mounted"
+                    .to_string(),
+            ));
+        }
+
+        if table.is_empty() || table.len() % apa::HEADER_SIZE != 0 {
+            return Err(Error::Other(format!(
+                "Partition table of length {} is not a whole number of headers",
+                table.len()
+            )));
+        }
+
+        let mut file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+        {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
         };
 
-        let mut temp_dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
-        let mut dirents = Vec::new();
+        let disk_sectors = apa::disk_sectors(&mut file)?;
 
-        let device_handle = ok_on_nonnegative_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_dopen(path.as_ptr()) },
-            "Failed to list partitions",
-        )?;
+        let headers: Vec<[u8; apa::HEADER_SIZE]> = table
+            .chunks_exact(apa::HEADER_SIZE)
+            .map(|chunk| {
+                let mut bytes = [0u8; apa::HEADER_SIZE];
+                bytes.copy_from_slice(chunk);
+                bytes
+            })
+            .collect();
 
-        while {
-            let result = unsafe { ps2hdd_sys::iomanx_dread(device_handle, &mut temp_dirent) };
+        for (index, bytes) in headers.iter().enumerate() {
+            let header = apa::ApaHeader::parse(bytes);
 
-            if result < 0 {
-                match unsafe { std::ffi::CStr::from_ptr(temp_dirent.name.as_ptr()) }.to_str() {
-                    Ok(name) => {
-                        return Err(format!("Failed to list partitions: {} {}", result, name))
-                    }
-                    Err(error) => {
-                        return Err(format!(
-                            "could not convert the partition name to a String: {}",
-                            error
-                        ))
-                    }
-                }
+            if header.magic != apa::APA_MAGIC {
+                return Err(Error::Other(format!(
+                    "Partition header {} has an invalid magic",
+                    index
+                )));
             }
 
-            result > 0
-        } {
-            dirents.push(temp_dirent.try_into()?);
+            if header.checksum != apa::checksum(bytes) {
+                return Err(Error::Other(format!(
+                    "Partition header {} has an invalid checksum",
+                    index
+                )));
+            }
+
+            if index == 0 && header.start != 0 {
+                return Err(Error::Other(
+                    "Partition table does not start at sector 0".to_string(),
+                ));
+            }
+
+            if header.start as u64 + header.length as u64 > disk_sectors {
+                return Err(Error::Other(format!(
+                    "Partition header {} extends past the end of the disk",
+                    index
+                )));
+            }
+
+            let expected_next = match headers.get(index + 1) {
+                Some(next) => apa::ApaHeader::parse(next).start,
+                None => 0,
+            };
+
+            if header.next != expected_next {
+                return Err(Error::Other(format!(
+                    "Partition header {} is not linked to the next header in the table",
+                    index
+                )));
+            }
         }
 
-        ok_on_zero_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_close(device_handle) },
-            "Failed to close root device handle",
-        )?;
+        warn!(
+            "Restoring a partition table of {} headers to {}",
+            headers.len(),
+            path.as_ref().display()
+        );
 
-        Ok(dirents)
+        for bytes in headers.iter() {
+            apa::write_header(&mut file, apa::ApaHeader::parse(bytes).start as u64, bytes)?;
+        }
+
+        if let Err(error) = file.sync_all() {
+            return Err(Error::Other(error.to_string()));
+        }
+
+        Ok(())
     }
 
-    /// Create a new, formatted partition within the APA partition map.
+    /// Returns the number of partition headers held by a pending APA journal
+    /// on the disk at `path`, which are left behind when a change to the
+    /// partition map is interrupted, such as by an unclean shutdown.
     ///
-    /// Partitions can be formatted from here using any `PartitionKind` for
-    /// which a suitable driver is available, except for [`MBR`]. The `MBR`
-    /// partition is initialised as [`PFS`], and treated specially.
+    /// Opening a disk replays any pending journal, so this reads the disk
+    /// directly, and may only be done while no PS2 HDD is open within this
+    /// process.
     ///
-    /// The size is specified in mebibytes, and must be a power of two.
-    /// Valid partition sizes are 128MiB, 256MiB, 512MiB, 1GiB, 2GiB, 4GiB,
-    /// 8GiB, 16GiB, and 32GiB.
+    /// # Errors
     ///
-    /// The maximum partition size is determined by the number of sectors on
-    /// the disk. For disks below roughly 6GiB in size, this means that
-    /// creating any properly-sized partitions is impossible. For reference,
-    /// the smallest hard drive released for the PlayStation®2 was 20GiB, so
-    /// it's probably not unexpected that smaller sizes cause odd behaviour!
+    /// This function will return an error if a PS2 HDD is open in this
+    /// process, if the disk could not be read, or if the journal is corrupt.
+    pub fn pending_journal_entries<P: AsRef<Path>>(path: P) -> Result<usize, Error> {
+        if IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Other(
+                "Journals may not be inspected while a PS2HDD instance is mounted".to_string(),
+            ));
+        }
+
+        let mut file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        Ok(apa::read_journal(&mut file)?.len())
+    }
+
+    /// Resolves a pending APA journal on the disk at `path`, either replaying
+    /// it to revert the interrupted change, or discarding it to keep the
+    /// partition map as the change left it.
     ///
-    /// [`MBR`]: enum.PartitionKind.html#variant.MBR
-    /// [`PFS`]: enum.PartitionKind.html#variant.PFS
+    /// Opening a disk always replays a pending journal, so this must be done
+    /// beforehand, while no PS2 HDD is open within this process, to choose
+    /// otherwise. Returns what was done, and to how many headers.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the specified partition size is
-    /// invalid, the requested format type is invalid (`MBR`), or if the
-    /// partition creation otherwise failed.
-    pub fn create_partition(
-        &self,
-        partition_name: &str,
-        kind: FormattablePartitionKind,
-        size: u64,
-    ) -> Result<(), String> {
-        if !size.is_power_of_two() {
-            return Err("Partition size must be a power of 2".to_string());
+    /// This function will return an error if a PS2 HDD is open in this
+    /// process, if the journal is corrupt or refers to sectors past the end of
+    /// the disk, or if the disk could not be read or written to.
+    pub fn repair_journal<P: AsRef<Path>>(
+        path: P,
+        action: apa::JournalAction,
+    ) -> Result<apa::JournalRepair, Error> {
+        if IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Other(
+                "Journals may not be repaired while a PS2HDD instance is mounted".to_string(),
+            ));
         }
 
-        let partition_kind: PartitionKind = match kind {
-            FormattablePartitionKind::MBR => FormattablePartitionKind::PFS,
-            v => v,
+        let mut file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+        {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let entries = apa::read_journal(&mut file)?;
+
+        if entries.is_empty() {
+            return Ok(apa::JournalRepair::Clean);
         }
-        .into();
 
-        let size_str = match size {
-            mb if mb >= 1024 => format!("{}G", mb / 1024),
-            mb => format!("{}M", mb),
+        let outcome = match action {
+            apa::JournalAction::Replay => {
+                let disk_sectors = apa::disk_sectors(&mut file)?;
+
+                if let Some((sector, _)) =
+                    entries.iter().find(|(sector, _)| *sector >= disk_sectors)
+                {
+                    return Err(Error::Other(format!(
+                        "Journal refers to sector {}, past the end of the disk",
+                        sector
+                    )));
+                }
+
+                for (sector, bytes) in entries.iter() {
+                    apa::write_header(&mut file, *sector, bytes)?;
+                }
+
+                apa::JournalRepair::Replayed(entries.len())
+            }
+            apa::JournalAction::Discard => apa::JournalRepair::Discarded(entries.len()),
         };
 
-        let mkpart_strpath = format!(
-            "hdd0:{},,,{},{}",
-            partition_name,
-            size_str,
-            partition_kind.as_apa_fs_type()
+        warn!(
+            "Resolved a journal of {} headers on {}: {:?}",
+            entries.len(),
+            path.as_ref().display(),
+            outcome
         );
 
-        let mkpart_path = match std::ffi::CString::new(mkpart_strpath) {
-            Ok(path) => path,
-            Err(error) => return Err(format!("couldn't convert string: {}", error)),
+        apa::clear_journal(&mut file)?;
+
+        if let Err(error) = file.sync_all() {
+            return Err(Error::Other(error.to_string()));
+        }
+
+        Ok(outcome)
+    }
+
+    /// Shrinks the disk image at `path` to end with its last partition, as
+    /// given by [`logical_size`], returning the number of bytes removed.
+    ///
+    /// Any free space partitions at the end of the partition map are unlinked
+    /// from it first, as they would otherwise refer to sectors past the new
+    /// end of the disk. APA doesn't record the size of the disk, as the
+    /// drivers read it from the device, so nothing else needs updating, and
+    /// the shrunk image can be opened as usual.
+    ///
+    /// The drivers cache the partition map, so this must be done while no
+    /// PS2 HDD is open within this process.
+    ///
+    /// [`logical_size`]: #method.logical_size
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a PS2 HDD is open in this
+    /// process, if the disk has a pending journal, if the partition map could
+    /// not be followed, or if the disk could not be written to or truncated,
+    /// as is the case for block devices.
+    pub fn trim_trailing_free_space<P: AsRef<Path>>(path: P) -> Result<u64, Error> {
+        if IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(Error::Other(
+                "Disks may not be shrunk while a PS2HDD instance is mounted".to_string(),
+            ));
+        }
+
+        let mut file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path.as_ref())
+        {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
         };
 
-        let open_flags = ps2hdd_sys::IOMANX_O_RDWR as i32 | ps2hdd_sys::IOMANX_O_CREAT as i32;
+        if !apa::read_journal(&mut file)?.is_empty() {
+            return Err(Error::Other(
+                "Disks with a pending journal may not be shrunk; repair the journal first"
+                    .to_string(),
+            ));
+        }
 
-        let partition_handle = ok_on_nonnegative_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_open(mkpart_path.as_ptr(), open_flags) },
-            "Partition creation failed",
-        )?;
+        let disk_sectors = apa::disk_sectors(&mut file)?;
+        let mut headers = apa::read_partition_map(&mut file)?;
 
-        ok_on_zero_or_strerror(
-            unsafe { ps2hdd_sys::iomanx_close(partition_handle) },
-            "Failed to close partition handle",
-        )?;
+        // `__mbr` is never free space, so at least one header is always kept
+        let kept = headers
+            .iter()
+            .rposition(|bytes| apa::ApaHeader::parse(bytes).kind().is_some())
+            .map_or(1, |index| index + 1);
+
+        let logical_sectors = headers[..kept]
+            .iter()
+            .map(|bytes| {
+                let header = apa::ApaHeader::parse(bytes);
+                header.start as u64 + header.length as u64
+            })
+            .max()
+            .unwrap_or(0);
+
+        if logical_sectors >= disk_sectors {
+            return Ok(0);
+        }
+
+        if kept < headers.len() {
+            let last = apa::ApaHeader::parse(&headers[kept - 1]);
+
+            // The last partition links back around to `__mbr`, and vice versa
+            if kept == 1 {
+                apa::set_links(&mut headers[0], 0, 0);
+            } else {
+                let mbr = apa::ApaHeader::parse(&headers[0]);
+
+                apa::set_links(&mut headers[kept - 1], 0, last.prev);
+                apa::set_links(&mut headers[0], mbr.next, last.start);
+                apa::write_header(&mut file, last.start as u64, &headers[kept - 1])?;
+            }
+
+            apa::write_header(&mut file, 0, &headers[0])?;
+
+            debug!("Unlinked {} trailing free partitions", headers.len() - kept);
+        }
+
+        let removed = (disk_sectors - logical_sectors) * apa::SECTOR_SIZE;
+
+        warn!("Shrinking {} by {} bytes", path.as_ref().display(), removed);
+
+        if let Err(error) = file.set_len(logical_sectors * apa::SECTOR_SIZE) {
+            return Err(Error::Other(format!("couldn't truncate disk: {}", error)));
+        }
 
-        self.format_partition(partition_name, kind)
+        if let Err(error) = file.sync_all() {
+            return Err(Error::Other(error.to_string()));
+        }
+
+        Ok(removed)
     }
 
-    /// Initialise a file system on a given partition.
+    /// Reads the APA partition header of the named partition directly from
+    /// the disk, returning both its parsed fields and its raw bytes.
+    ///
+    /// For partitions with sub-partitions, this is the main partition's
+    /// header.
     ///
     /// # Errors
     ///
-    /// This function will return an error if the partition does not already
-    /// exist, the partition name is invalid, or if the format process fails.
-    pub fn format_partition(
+    /// This function will return an error if the disk could not be read, the
+    /// partition does not exist, or if its header's checksum or flags are
+    /// invalid.
+    pub fn read_apa_header(
         &self,
         partition_name: &str,
-        kind: FormattablePartitionKind,
-    ) -> Result<(), String> {
-        let kind: PartitionKind = match kind {
-            FormattablePartitionKind::MBR => FormattablePartitionKind::PFS,
-            v => v,
-        }
-        .into();
+    ) -> Result<(apa::ApaHeader, Vec<u8>), Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
 
-        // TODO: How do we check encoding? What encoding is the target?
-        // JIS X 0201? US ASCII?
+        for bytes in apa::read_partition_map(&mut file)? {
+            let header = apa::ApaHeader::parse(&bytes);
 
-        let device =
-            match std::ffi::CString::new(format!("{}0:", kind.as_apa_fs_type().to_lowercase())) {
-                Ok(device_path) => device_path,
-                Err(error) => return Err(error.to_string()),
-            };
+            if header.id == partition_name && header.flags.is_main() {
+                header.validate(&bytes)?;
+                return Ok((header, bytes.to_vec()));
+            }
+        }
 
-        // TODO: Ensure path does not contain invalid characters?
+        Err(Error::NotFound {
+            name: partition_name.to_string(),
+            kind: "partition",
+        })
+    }
 
-        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
-            Ok(partition_path) => partition_path,
-            Err(error) => return Err(error.to_string()),
+    /// Checks that the disk can be written to, so that a long write doesn't
+    /// discover it's read-only part way through.
+    ///
+    /// The disk is checked for read-only permissions, and then opened for
+    /// writing, which fails if it's on a read-only file system. Nothing is
+    /// actually written to the disk.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error describing why the disk is not
+    /// writable, if it isn't.
+    pub fn verify_writable(&self) -> Result<(), Error> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(error) => return Err(Error::Other(format!("{}: {}", self.path.display(), error))),
         };
 
-        let mut format_arg: [i32; 3] = [PFS_ZONE_SIZE, 0x2d66, PFS_FRAGMENT];
+        if metadata.permissions().readonly() {
+            return Err(Error::Other(format!(
+                "{}: Disk is read-only",
+                self.path.display()
+            )));
+        }
 
-        ok_on_zero_or_strerror(
-            unsafe {
-                ps2hdd_sys::iomanx_format(
-                    device.as_ptr(),
-                    partition.as_ptr(),
-                    format_arg.as_mut_ptr() as *mut core::ffi::c_void,
-                    std::mem::size_of_val(&format_arg) as u64,
-                )
-            },
-            "PFS partition formatting failed",
-        )?;
+        if let Err(error) = std::fs::OpenOptions::new().write(true).open(&self.path) {
+            return Err(Error::Other(format!(
+                "{}: Disk is not writable: {}",
+                self.path.display(),
+                error
+            )));
+        }
 
         Ok(())
     }
 
-    /// Acquire a file I/O object bound to the specified `pfs` partition.
-    pub fn mount_pfs(&mut self, partition_name: &str) -> Result<&PFS, String> {
-        if self.pfs.is_some() {
-            return Err("A PFS partition is already mounted".to_string());
+    /// Reads the first `count` sectors of the disk as raw bytes, such as to
+    /// fingerprint the disk by its `__mbr` header, or to attach to a bug
+    /// report.
+    ///
+    /// The disk is only read, through a separate read-only handle, so this
+    /// can't modify it, and exactly `count` sectors of [`apa::SECTOR_SIZE`]
+    /// bytes are returned.
+    ///
+    /// [`apa::SECTOR_SIZE`]: apa/constant.SECTOR_SIZE.html
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be opened or
+    /// read, if it has fewer than `count` sectors, or if `count` is more than
+    /// [`MAX_LEADING_SECTORS`](constant.MAX_LEADING_SECTORS.html).
+    pub fn read_leading_sectors(&self, count: u64) -> Result<Vec<u8>, Error> {
+        use std::io::{Read, Seek};
+
+        if count > MAX_LEADING_SECTORS {
+            return Err(Error::Other(format!(
+                "Cannot read more than {} leading sectors",
+                MAX_LEADING_SECTORS
+            )));
         }
 
-        self.mount("pfs0:", partition_name)?;
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
 
-        self.pfs = Some(PFS {
-            partition_name: partition_name.to_string(),
-        });
+        let disk_sectors = apa::disk_sectors(&mut file)?;
 
-        self.pfs
-            .as_ref()
-            .ok_or_else(|| "Failed to retrieve reference".to_string())
+        if count > disk_sectors {
+            return Err(Error::Other(format!(
+                "Cannot read {} sectors from a disk of {} sectors",
+                count, disk_sectors
+            )));
+        }
+
+        let mut bytes = vec![0u8; (count * apa::SECTOR_SIZE) as usize];
+
+        // Finding the disk's size leaves the file at its end
+        if let Err(error) = file.seek(std::io::SeekFrom::Start(0)) {
+            return Err(Error::Other(format!(
+                "couldn't seek to the start of the disk: {}",
+                error
+            )));
+        }
+
+        if let Err(error) = file.read_exact(&mut bytes) {
+            return Err(Error::Other(format!("couldn't read sectors: {}", error)));
+        }
+
+        Ok(bytes)
+    }
+
+    /// Discards the contents of every free partition on the disk, so that
+    /// the underlying storage can reclaim the space.
+    ///
+    /// This is best-effort, and only does anything on Linux, where it punches
+    /// holes in the free partitions using `fallocate`. For disk image files,
+    /// this makes the file sparse, and for block devices on SSDs, this issues
+    /// a discard (TRIM) for the sectors. The partition headers themselves are
+    /// left intact. On other platforms, or if the file system or device does
+    /// not support it, nothing is discarded.
+    ///
+    /// Returns the number of bytes discarded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be opened for
+    /// writing, if the partition map could not be followed, or if a discard
+    /// supported by the device failed.
+    pub fn trim(&self) -> Result<u64, Error> {
+        let mut file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+        {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let sector_size = apa::SECTOR_SIZE;
+        let mut discarded = 0;
+
+        for bytes in apa::read_partition_map(&mut file)? {
+            let header = apa::ApaHeader::parse(&bytes);
+
+            if header.kind().is_some() {
+                continue;
+            }
+
+            let header_sectors = (apa::HEADER_SIZE as u64) / sector_size;
+
+            if (header.length as u64) <= header_sectors {
+                continue;
+            }
+
+            let offset = (header.start as u64 + header_sectors) * sector_size;
+            let length = (header.length as u64 - header_sectors) * sector_size;
+
+            if !discard(&file, offset, length)? {
+                warn!(
+                    "Discarding sectors is not supported by {}",
+                    self.path.display()
+                );
+                break;
+            }
+
+            debug!("Discarded {} bytes at offset {}", length, offset);
+
+            discarded += length;
+        }
+
+        Ok(discarded)
+    }
+
+    /// Returns the number of bytes of the disk in use by partitions; the end
+    /// of the last partition which isn't free space.
+    ///
+    /// Everything after this point is unused, so a disk image can be
+    /// truncated to this size without losing data, as long as it's extended
+    /// back to its full size before being used again.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be opened, or
+    /// if the partition map could not be followed.
+    pub fn logical_size(&self) -> Result<u64, Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let sector_size = apa::SECTOR_SIZE;
+
+        Ok(apa::read_partition_map(&mut file)?
+            .iter()
+            .map(apa::ApaHeader::parse)
+            .filter(|header| header.kind().is_some())
+            .map(|header| (header.start as u64 + header.length as u64) * sector_size)
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Returns the regions of the disk not in use by any partition, as
+    /// `(start_sector, length_sectors)` pairs in the order they appear on the
+    /// disk, in units of [`apa::SECTOR_SIZE`].
+    ///
+    /// This includes both partitions which have been removed and left as free
+    /// space, with adjacent ones merged into a single region, and any space
+    /// after the last partition which hasn't been allocated at all.
+    ///
+    /// [`apa::SECTOR_SIZE`]: apa/constant.SECTOR_SIZE.html
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be opened, or
+    /// if the partition map could not be followed.
+    pub fn free_regions(&self) -> Result<Vec<(u64, u64)>, Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let disk_sectors = apa::disk_sectors(&mut file)?;
+        let mut headers: Vec<apa::ApaHeader> = apa::read_partition_map(&mut file)?
+            .iter()
+            .map(apa::ApaHeader::parse)
+            .collect();
+
+        headers.sort_by_key(|header| header.start);
+
+        let mut regions: Vec<(u64, u64)> = Vec::new();
+        let mut allocated_end = 0;
+
+        for header in &headers {
+            let start = header.start as u64;
+            let length = header.length as u64;
+
+            allocated_end = allocated_end.max(start + length);
+
+            if header.kind().is_some() {
+                continue;
+            }
+
+            match regions.last_mut() {
+                Some((last_start, last_length)) if *last_start + *last_length == start => {
+                    *last_length += length
+                }
+                _ => regions.push((start, length)),
+            }
+        }
+
+        if allocated_end < disk_sectors {
+            match regions.last_mut() {
+                Some((last_start, last_length)) if *last_start + *last_length == allocated_end => {
+                    *last_length += disk_sectors - allocated_end
+                }
+                _ => regions.push((allocated_end, disk_sectors - allocated_end)),
+            }
+        }
+
+        Ok(regions)
     }
 
-    /// Unmount the currently-mounted PFS device.
-    ///
-    /// Should not generally be called by user code.
-    /// Used internally to keep track of state.
-    pub fn umount_pfs(&mut self) -> Result<(), String> {
-        if self.pfs.is_none() {
-            return Err("No PFS partition is mounted; nothing to unmount".to_string());
+    /// Returns the free regions of the disk which could hold a new partition
+    /// of `size` mebibytes, as `(offset, length)` pairs in bytes, in the order
+    /// they appear on the disk.
+    ///
+    /// APA partitions must start at a multiple of their own size, so each
+    /// region is trimmed to start at the first suitably-aligned offset within
+    /// it, and regions too small to hold the partition once aligned are left
+    /// out. When creating a partition, the driver reuses free space in the
+    /// middle of the disk where it can, only appending to the end otherwise.
+    ///
+    /// See [`free_regions`] for all of the disk's free space.
+    ///
+    /// [`free_regions`]: #method.free_regions
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `size` is not a valid partition
+    /// size, as given by [`create_partition`], or if the partition map could
+    /// not be read.
+    ///
+    /// [`create_partition`]: #method.create_partition
+    pub fn partition_free_gaps(&self, size: u64) -> Result<Vec<(u64, u64)>, Error> {
+        if !size.is_power_of_two() || !(MIN_PARTITION_SIZE..=MAX_PARTITION_SIZE).contains(&size) {
+            return Err(Error::Other(format!(
+                "Partition size must be a power of 2 between {}MiB and {}MiB",
+                MIN_PARTITION_SIZE, MAX_PARTITION_SIZE
+            )));
+        }
+
+        let size_sectors = size * 1024 * 1024 / apa::SECTOR_SIZE;
+
+        Ok(self
+            .free_regions()?
+            .into_iter()
+            .filter_map(|(start, length)| {
+                let aligned_start = start.div_ceil(size_sectors) * size_sectors;
+                let end = start + length;
+
+                if aligned_start + size_sectors > end {
+                    return None;
+                }
+
+                Some((
+                    aligned_start * apa::SECTOR_SIZE,
+                    (end - aligned_start) * apa::SECTOR_SIZE,
+                ))
+            })
+            .collect())
+    }
+
+    /// Checksums the disk with `algorithm`, returning the digest as a
+    /// lowercase hexadecimal string, so that it can be recorded and later
+    /// used to verify that the disk hasn't changed or become corrupted.
+    ///
+    /// `region` chooses whether the whole disk is checksummed, or only the
+    /// part of it in use by partitions, as given by [`logical_size`]. The
+    /// disk is read in chunks, rather than all at once.
+    ///
+    /// Changes made by the drivers may be held in their caches until a
+    /// partition is unmounted, so partitions should be unmounted first.
+    ///
+    /// [`logical_size`]: #method.logical_size
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be opened or
+    /// read, or if `region` is `LogicalSize` and the partition map could not
+    /// be followed.
+    pub fn image_digest(
+        &self,
+        algorithm: DigestAlgorithm,
+        region: DigestRegion,
+    ) -> Result<String, Error> {
+        let limit = match region {
+            DigestRegion::WholeImage => u64::MAX,
+            DigestRegion::LogicalSize => self.logical_size()?,
+        };
+
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        debug!("Checksumming {} with {:?}", self.path.display(), algorithm);
+
+        match digest::digest_reader(file, algorithm, limit) {
+            Ok(digest) => Ok(digest),
+            Err(error) => Err(Error::Other(format!("couldn't read disk: {}", error))),
+        }
+    }
+
+    /// Estimates the size of the HDL partition needed to hold the game in the
+    /// ISO image at `iso_path`, in mebibytes, as would be passed to
+    /// [`create_partition`].
+    ///
+    /// The game's data follows the HDL game information, which starts
+    /// `HDL_GAME_DATA_OFFSET` bytes into the partition, and the partition is
+    /// then rounded up to the next valid partition size.
+    ///
+    /// [`create_partition`]: #method.create_partition
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the size of the file at
+    /// `iso_path` could not be read, or if the game is too large to fit in
+    /// the largest possible partition.
+    pub fn estimate_install_size<P: AsRef<Path>>(iso_path: P) -> Result<u64, Error> {
+        match std::fs::metadata(iso_path.as_ref()) {
+            Ok(metadata) => hdl_partition_size(metadata.len()),
+            Err(error) => Err(Error::Other(format!(
+                "{}: {}",
+                iso_path.as_ref().display(),
+                error
+            ))),
+        }
+    }
+
+    /// Checks whether the named partition's HDL game information block starts
+    /// with the HDL magic number.
+    pub(crate) fn has_hdl_game_info(&self, partition_name: &str) -> Result<bool, Error> {
+        let (header, _) = self.read_apa_header(partition_name)?;
+        let sector_size = apa::SECTOR_SIZE;
+
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let offset = header.start as u64 * sector_size + ps2hdd_sys::HDL_GAME_DATA_OFFSET as u64;
+
+        if let Err(error) = file.seek(std::io::SeekFrom::Start(offset)) {
+            return Err(Error::Other(format!(
+                "couldn't seek to game information: {}",
+                error
+            )));
+        }
+
+        let mut magic = [0u8; 4];
+
+        if let Err(error) = file.read_exact(&mut magic) {
+            return Err(Error::Other(format!(
+                "couldn't read game information: {}",
+                error
+            )));
+        }
+
+        Ok(u32::from_le_bytes(magic) == ps2hdd_sys::HDL_INFO_MAGIC)
+    }
+
+    /// Determines which file system a partition actually contains, by
+    /// looking for each file system's magic number on the disk, rather than
+    /// trusting the kind recorded in its APA header.
+    ///
+    /// Compare the result against the partition's kind with
+    /// [`DetectedFs::matches`] to find partitions whose headers have been
+    /// tampered with, or which were never formatted.
+    ///
+    /// [`DetectedFs::matches`]: partition_kind/enum.DetectedFs.html#method.matches
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition's header could
+    /// not be read, or if the disk could not be read.
+    pub fn detect_filesystem(&self, partition_name: &str) -> Result<DetectedFs, Error> {
+        let (header, _) = self.read_apa_header(partition_name)?;
+        let start = header.start as u64 * apa::SECTOR_SIZE;
+
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let mut read_magic = |offset: u64| -> Result<u32, Error> {
+            if let Err(error) = file.seek(std::io::SeekFrom::Start(start + offset)) {
+                return Err(Error::Other(format!(
+                    "couldn't seek to file system: {}",
+                    error
+                )));
+            }
+
+            let mut magic = [0u8; 4];
+
+            if let Err(error) = file.read_exact(&mut magic) {
+                return Err(Error::Other(format!(
+                    "couldn't read file system: {}",
+                    error
+                )));
+            }
+
+            Ok(u32::from_le_bytes(magic))
+        };
+
+        if read_magic(PFS_SUPER_OFFSET)? == PFS_SUPER_MAGIC {
+            return Ok(DetectedFs::PFS);
+        }
+
+        if read_magic(ps2hdd_sys::HDL_GAME_DATA_OFFSET as u64)? == ps2hdd_sys::HDL_INFO_MAGIC {
+            return Ok(DetectedFs::HDL);
+        }
+
+        if read_magic(EXT2_MAGIC_OFFSET)? & 0xffff == EXT2_MAGIC {
+            return Ok(DetectedFs::EXT2);
+        }
+
+        Ok(DetectedFs::Unformatted)
+    }
+
+    /// Reads every partition header from the disk, and reports any partitions
+    /// whose sectors overlap each other, or which extend past the end of the
+    /// disk; signs of a corrupt partition map.
+    ///
+    /// This complements the checksums of the individual headers, which can't
+    /// detect headers which are intact, but inconsistent with each other.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the disk could not be read, or
+    /// if the partition map could not be followed.
+    pub fn detect_overlaps(&self) -> Result<Vec<apa::PartitionConflict>, Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let disk_sectors = apa::disk_sectors(&mut file)?;
+
+        let headers: Vec<apa::ApaHeader> = apa::read_partition_map(&mut file)?
+            .iter()
+            .map(apa::ApaHeader::parse)
+            .collect();
+
+        Ok(apa::find_conflicts(&headers, disk_sectors))
+    }
+
+    /// Attempts to create and subsequently open a new PS2 HDD image file.
+    ///
+    /// The image is created sparse where the host file system supports it,
+    /// so its space is only allocated as it's written to. See
+    /// [`create_with`] to allocate it all up front instead.
+    ///
+    /// [`create_with`]: #method.create_with
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a PS2 HDD is already open in
+    /// this process, if `path` already exists, if `path` is longer than 255
+    /// characters, if `size` is larger than [`MAX_DISK_SIZE`], if the file
+    /// could not be extended to the desired size, or if there is any error
+    /// initialising the subsystems which read and write the PS2 HDD image.
+    ///
+    /// [`MAX_DISK_SIZE`]: constant.MAX_DISK_SIZE.html
+    pub fn create<P: std::fmt::Debug + AsRef<Path>>(path: P, size: u64) -> Result<Self, Error> {
+        Self::create_with(path, size, &CreateOptions::new())
+    }
+
+    /// Attempts to create and subsequently open a new PS2 HDD image file, as
+    /// [`create`] does, but with its allocation controlled by `options`.
+    ///
+    /// [`create`]: #method.create
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error in any of the cases [`create`]
+    /// does, or if the image could not be preallocated.
+    pub fn create_with<P: std::fmt::Debug + AsRef<Path>>(
+        path: P,
+        size: u64,
+        options: &CreateOptions,
+    ) -> Result<Self, Error> {
+        if size > MAX_DISK_SIZE {
+            return Err(Error::Other(format!(
+                "Disk size of {} bytes is larger than the {} bytes APA can address",
+                size, MAX_DISK_SIZE
+            )));
+        }
+
+        match std::fs::File::create(&path) {
+            Err(error) => return Err(Error::Other(error.to_string())),
+            Ok(file) => {
+                if options.preallocates() {
+                    if let Err(error) = preallocate_file(&file, size) {
+                        return Err(Error::Other(format!(
+                            "Could not preallocate disk image: {}",
+                            error
+                        )));
+                    }
+                } else if let Err(error) = file.set_len(size) {
+                    return Err(Error::Other(error.to_string()));
+                }
+                drop(file);
+            }
+        }
+
+        Self::open(path)
+    }
+
+    /// Creates a new PS2 HDD image file with the same size and partition
+    /// layout as `template`, but none of its data.
+    ///
+    /// The disk is initialised, and then each of the template's partitions,
+    /// other than the default system partitions, is created with the same
+    /// name, kind and size, in the same order. As only one PS2 HDD may be open
+    /// at a time, `template`'s layout is read, and then it is closed before
+    /// the new disk is created.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `template`'s partition map could
+    /// not be read, if it contains partitions of a kind which can't be
+    /// created, or in any of the cases [`create`], [`initialize`] or
+    /// [`create_partition`] do.
+    ///
+    /// [`create`]: #method.create
+    /// [`initialize`]: #method.initialize
+    /// [`create_partition`]: #method.create_partition
+    pub fn new_from_template<P: std::fmt::Debug + AsRef<Path>>(
+        template: PS2HDD,
+        path: P,
+    ) -> Result<Self, Error> {
+        let mut file = match std::fs::File::open(&template.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let sector_size = apa::SECTOR_SIZE;
+        let size = apa::disk_sectors(&mut file)? * sector_size;
+        let mut layout = Vec::new();
+
+        for bytes in apa::read_partition_map(&mut file)? {
+            let header = apa::ApaHeader::parse(&bytes);
+
+            // Initialising the new disk creates the default partitions, but
+            // any others named like system partitions, such as __.POPS, are
+            // the template's own, so are kept
+            if header.flags.is_sub()
+                || DEFAULT_PARTITIONS
+                    .iter()
+                    .any(|(name, _)| *name == header.id)
+            {
+                continue;
+            }
+
+            let kind = match header.kind() {
+                Some(PartitionKind::PFS) => FormattablePartitionKind::PFS,
+                Some(PartitionKind::HDL) => FormattablePartitionKind::HDL,
+                Some(kind) => {
+                    return Err(Error::Other(format!(
+                        "{}: Cannot create {} partitions",
+                        header.id,
+                        kind.as_apa_fs_type()
+                    )))
+                }
+                // Free space
+                None => continue,
+            };
+
+            let sectors = header.length as u64
+                + header
+                    .subs
+                    .iter()
+                    .map(|(_, length)| *length as u64)
+                    .sum::<u64>();
+
+            layout.push((header.id, kind, sectors * sector_size / (1024 * 1024)));
+        }
+
+        drop(file);
+        drop(template);
+
+        let ps2hdd = Self::create(path, size)?;
+        ps2hdd.initialize()?;
+
+        for (name, kind, size) in layout {
+            ps2hdd.create_partition(&name, kind, size)?;
+        }
+
+        Ok(ps2hdd)
+    }
+
+    /// Format the entire disk, creating the APA partition map, and
+    /// default set of partitions `__mbr`, `__net`, `__system`, `__sysconf` and
+    /// `__common`.
+    ///
+    /// Note that this is a destructive process, and data *will* be destroyed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if partitions could not be created.
+    ///
+    /// The partition map and every default partition are created by the
+    /// driver in a single step, so if it fails, the disk is checked to find
+    /// how far it got, and the error names the first default partition which
+    /// is missing.
+    pub fn initialize(&self) -> Result<(), Error> {
+        warn!(
+            "Formatting the APA partition map of {}",
+            self.path.display()
+        );
+
+        let device = match std::ffi::CString::new("hdd0:") {
+            Ok(device_path) => device_path,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let message = match ok_on_zero_or_strerror(
+            unsafe {
+                ps2hdd_sys::iomanx_format(
+                    device.as_ptr(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    0,
+                )
+            },
+            "HDD formatting failed",
+        ) {
+            Ok(_) => return Ok(()),
+            Err(message) => message,
+        };
+
+        let missing = match self.defaults_present() {
+            Ok(missing) => missing,
+            Err(error) => {
+                warn!(
+                    "Couldn't check which default partitions were created: {}",
+                    error
+                );
+                return Err(message);
+            }
+        };
+
+        error!("{}; missing default partitions: {:?}", message, missing);
+
+        Err(match missing.first() {
+            None => message,
+            Some(_) if missing.len() == DEFAULT_PARTITIONS.len() => Error::Other(format!(
+                "{}; the APA partition map could not be written",
+                message
+            )),
+            Some(first) => Error::Other(format!(
+                "{}; could not create default partition {} (missing {})",
+                message,
+                first,
+                missing.join(", ")
+            )),
+        })
+    }
+
+    /// Format the entire disk as [`initialize`] does, but with the sizes of
+    /// the default partitions after `__mbr` controlled by `options`,
+    /// returning the default partitions which were created.
+    ///
+    /// Sizes are in mebibytes, with the same valid sizes as
+    /// [`create_partition`]. As with `initialize`, this is a destructive
+    /// process, and data *will* be destroyed.
+    ///
+    /// [`initialize`]: #method.initialize
+    /// [`create_partition`]: #method.create_partition
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any of the sizes are invalid,
+    /// in which case the disk is left untouched, or if partitions could not
+    /// be created.
+    pub fn initialize_with(&self, options: &InitializeOptions) -> Result<Vec<PartEntry>, Error> {
+        let optional_partitions = &DEFAULT_PARTITIONS[1..];
+
+        for ((name, _), size) in optional_partitions.iter().zip(options.sizes.iter()) {
+            match size {
+                Some(size)
+                    if !size.is_power_of_two()
+                        || *size < MIN_PARTITION_SIZE
+                        || *size > MAX_PARTITION_SIZE =>
+                {
+                    return Err(Error::Other(format!(
+                        "{}: Partition size must be a power of 2 from {}MiB to {}MiB",
+                        name, MIN_PARTITION_SIZE, MAX_PARTITION_SIZE
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        self.initialize()?;
+
+        // The drivers always create every default partition at the minimum
+        // size, so everything from the first partition which differs is
+        // removed, and recreated in order so they're still laid out in order
+        let defaults = InitializeOptions::default();
+        let first_changed = (0..options.sizes.len())
+            .find(|&index| options.sizes[index] != defaults.sizes[index])
+            .unwrap_or(options.sizes.len());
+
+        for (name, _) in optional_partitions[first_changed..].iter().rev() {
+            let partition = match std::ffi::CString::new(format!("hdd0:{}", name)) {
+                Ok(partition_path) => partition_path,
+                Err(error) => return Err(Error::Other(error.to_string())),
+            };
+
+            debug!("Removing default partition {}", name);
+
+            ok_on_zero_or_strerror(
+                unsafe { ps2hdd_sys::iomanx_remove(partition.as_ptr()) },
+                "Failed to remove default partition",
+            )?;
+        }
+
+        for ((name, _), size) in optional_partitions
+            .iter()
+            .zip(options.sizes.iter())
+            .skip(first_changed)
+        {
+            if let Some(size) = size {
+                self.create_partition(name, FormattablePartitionKind::PFS, *size)?;
+            }
+        }
+
+        Ok(self
+            .list_partitions()?
+            .into_iter()
+            .filter(|partition| partition.is_system() && partition.flags.is_main())
+            .collect())
+    }
+
+    /// Checks whether the default partitions created by [`initialize`] are
+    /// all present, and of the correct kinds, returning the names of any
+    /// which are missing or of the wrong kind.
+    ///
+    /// An empty result means the disk has been properly initialised. A disk
+    /// which has not been initialised at all will be missing all of them.
+    ///
+    /// [`initialize`]: #method.initialize
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partitions of an initialised
+    /// disk could not be listed.
+    pub fn defaults_present(&self) -> Result<Vec<&'static str>, Error> {
+        if !apa::has_apa_magic(&self.path) {
+            return Ok(DEFAULT_PARTITIONS.iter().map(|(name, _)| *name).collect());
+        }
+
+        let system_partitions: Vec<PartEntry> = self
+            .list_partitions()?
+            .into_iter()
+            .filter(|partition| partition.is_system() && partition.flags.is_main())
+            .collect();
+
+        Ok(DEFAULT_PARTITIONS
+            .iter()
+            .filter(|(name, kind)| {
+                !system_partitions
+                    .iter()
+                    .any(|partition| partition.name == *name && partition.kind == Some(*kind))
+            })
+            .map(|(name, _)| *name)
+            .collect())
+    }
+
+    /// List all the partitions on the disk.
+    ///
+    /// Partitions are listed in the order they appear in the APA partition
+    /// map, which is the order of their start sectors on disk, beginning with
+    /// `__mbr`. Sub-partitions are listed in place, and free space shows up as
+    /// partitions with no kind. This order is stable for a given disk layout;
+    /// see [`list_partitions_sorted`] for an order which doesn't depend on
+    /// where partitions were allocated.
+    ///
+    /// Note that unlike `std::fs::read_dir` or the like, which return an
+    /// iterator, all entries are fetched upfront, due to the underlying
+    /// driver involving internal state we can't fully rely on.
+    ///
+    /// [`list_partitions_sorted`]: #method.list_partitions_sorted
+    pub fn list_partitions(&self) -> Result<Vec<PartEntry>, Error> {
+        let path = match std::ffi::CString::new("hdd0:") {
+            Ok(path) => path,
+            Err(error) => {
+                return Err(Error::NulByte {
+                    context: "couldn't convert string",
+                    error,
+                })
+            }
+        };
+
+        let mut temp_dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
+        let mut dirents = Vec::new();
+
+        let device_handle = ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_dopen(path.as_ptr()) },
+            "Failed to list partitions",
+        )?;
+
+        while {
+            let result = unsafe { ps2hdd_sys::iomanx_dread(device_handle, &mut temp_dirent) };
+
+            if result < 0 {
+                match unsafe { std::ffi::CStr::from_ptr(temp_dirent.name.as_ptr()) }.to_str() {
+                    Ok(name) => {
+                        return Err(Error::Other(format!(
+                            "Failed to list partitions: {} {}",
+                            result, name
+                        )))
+                    }
+                    Err(error) => {
+                        return Err(Error::Other(format!(
+                            "could not convert the partition name to a String: {}",
+                            error
+                        )))
+                    }
+                }
+            }
+
+            result > 0
+        } {
+            dirents.push(PartEntry::try_from(&temp_dirent)?);
+        }
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_close(device_handle) },
+            "Failed to close root device handle",
+        )?;
+
+        Ok(dirents)
+    }
+
+    /// List all the partitions on the disk, sorted by name.
+    ///
+    /// Partitions with the same name (such as a partition and its
+    /// sub-partitions, or multiple areas of free space) keep the order they
+    /// were listed in by [`list_partitions`].
+    ///
+    /// [`list_partitions`]: #method.list_partitions
+    pub fn list_partitions_sorted(&self) -> Result<Vec<PartEntry>, Error> {
+        let mut partitions = self.list_partitions()?;
+        partitions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(partitions)
+    }
+
+    /// Checks whether a partition with the given name exists on the disk.
+    ///
+    /// This queries the partition directly, rather than listing every
+    /// partition.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition could not be
+    /// queried for any reason other than it not existing, such as the disk
+    /// not being readable.
+    pub fn partition_exists(&self, partition_name: &str) -> Result<bool, Error> {
+        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
+            Ok(partition_path) => partition_path,
+            Err(error) => {
+                return Err(Error::Other(format!(
+                    "couldn't convert partition name: {}",
+                    error
+                )))
+            }
+        };
+
+        let mut stat: ps2hdd_sys::iox_stat_t = unsafe { std::mem::zeroed() };
+
+        let result = unsafe { ps2hdd_sys::iomanx_getstat(partition.as_ptr(), &mut stat) };
+
+        if result == -libc::ENOENT {
+            return Ok(false);
+        }
+
+        ok_on_nonnegative_or_strerror(result, "failed to stat partition")?;
+
+        Ok(true)
+    }
+
+    /// Issues an `ioctl2` command to the named partition, passing `arg` as
+    /// the command's input and receiving its output into `buf`.
+    ///
+    /// Only the `Partition*` commands of [`Ioctl2Command`] are understood by
+    /// partitions.
+    ///
+    /// [`Ioctl2Command`]: ioctl/enum.Ioctl2Command.html
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition could not be
+    /// opened, or if the driver rejects the command.
+    pub fn partition_ioctl2(
+        &self,
+        partition_name: &str,
+        command: Ioctl2Command,
+        arg: &[u8],
+        buf: &mut [u8],
+    ) -> Result<i32, Error> {
+        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
+            Ok(partition_path) => partition_path,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let partition_handle = ok_on_nonnegative_or_strerror(
+            unsafe {
+                ps2hdd_sys::iomanx_open(partition.as_ptr(), ps2hdd_sys::IOMANX_O_RDWR as i32)
+            },
+            "Failed to open partition",
+        )?;
+
+        let result = ioctl::ioctl2(partition_handle, command, arg, buf);
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_close(partition_handle) },
+            "Failed to close partition handle",
+        )?;
+
+        result
+    }
+
+    /// Create a new, formatted partition within the APA partition map.
+    ///
+    /// Partitions can be formatted from here using any `PartitionKind` for
+    /// which a suitable driver is available, except for [`MBR`]. The `MBR`
+    /// partition is initialised as [`PFS`], and treated specially.
+    ///
+    /// The size is specified in mebibytes, and must be a power of two.
+    /// Valid partition sizes are 128MiB, 256MiB, 512MiB, 1GiB, 2GiB, 4GiB,
+    /// 8GiB, 16GiB, and 32GiB.
+    ///
+    /// The maximum partition size is determined by the number of sectors on
+    /// the disk. For disks below roughly 6GiB in size, this means that
+    /// creating any properly-sized partitions is impossible. For reference,
+    /// the smallest hard drive released for the PlayStation®2 was 20GiB, so
+    /// it's probably not unexpected that smaller sizes cause odd behaviour!
+    ///
+    /// [`MBR`]: enum.PartitionKind.html#variant.MBR
+    /// [`PFS`]: enum.PartitionKind.html#variant.PFS
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the specified partition size is
+    /// invalid, the requested format type is invalid (`MBR`), or if the
+    /// partition creation otherwise failed.
+    pub fn create_partition(
+        &self,
+        partition_name: &str,
+        kind: FormattablePartitionKind,
+        size: u64,
+    ) -> Result<(), Error> {
+        if !size.is_power_of_two() {
+            return Err(Error::InvalidPartitionSize(size));
+        }
+
+        let partition_kind: PartitionKind = match kind {
+            FormattablePartitionKind::MBR => FormattablePartitionKind::PFS,
+            v => v,
+        }
+        .into();
+
+        let size_str = match size {
+            mb if mb >= 1024 => format!("{}G", mb / 1024),
+            mb => format!("{}M", mb),
+        };
+
+        let mkpart_strpath = format!(
+            "hdd0:{},,,{},{}",
+            partition_name,
+            size_str,
+            partition_kind.as_apa_fs_type()
+        );
+
+        let mkpart_path = match std::ffi::CString::new(mkpart_strpath) {
+            Ok(path) => path,
+            Err(error) => {
+                return Err(Error::NulByte {
+                    context: "couldn't convert string",
+                    error,
+                })
+            }
+        };
+
+        debug!(
+            "Creating {} partition {} of {}",
+            partition_kind.as_apa_fs_type(),
+            partition_name,
+            size_str
+        );
+
+        let open_flags = ps2hdd_sys::IOMANX_O_RDWR as i32 | ps2hdd_sys::IOMANX_O_CREAT as i32;
+
+        let partition_handle = ok_on_nonnegative_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_open(mkpart_path.as_ptr(), open_flags) },
+            "Partition creation failed",
+        )?;
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_close(partition_handle) },
+            "Failed to close partition handle",
+        )?;
+
+        // The partition was only just created, so there's nothing to lose
+        self.format_partition(partition_name, kind, &FormatOptions::new().force(true))
+    }
+
+    /// Removes a partition, along with any sub-partitions, from the disk,
+    /// freeing its space. Everything within it is lost.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition is one of the
+    /// default system partitions created by [`initialize`], if it is
+    /// currently mounted, or if it could not be removed, such as because it
+    /// does not exist.
+    ///
+    /// [`initialize`]: #method.initialize
+    pub fn delete_partition(&self, partition_name: &str) -> Result<(), Error> {
+        if DEFAULT_PARTITIONS
+            .iter()
+            .any(|(name, _)| *name == partition_name)
+        {
+            return Err(Error::Other(format!(
+                "{} is a reserved system partition, and can't be deleted",
+                partition_name
+            )));
+        }
+
+        let mounted = [
+            self.pfs.as_ref().map(|pfs| pfs.get_partition_name()),
+            self.hdlfs.as_ref().map(|hdlfs| hdlfs.get_partition_name()),
+        ];
+
+        if mounted.contains(&Some(partition_name)) {
+            return Err(Error::Other(format!(
+                "{} is mounted, and can't be deleted",
+                partition_name
+            )));
+        }
+
+        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
+            Ok(partition_path) => partition_path,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        debug!("Deleting partition {}", partition_name);
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_remove(partition.as_ptr()) },
+            "Failed to delete partition",
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates every partition in `layout`, in order, once checking that they
+    /// can all be created.
+    ///
+    /// Each partition must have a valid name which isn't already in use, and
+    /// a valid size, as given by [`create_partition`], and together they must
+    /// fit in the disk's free space, as given by [`free_regions`].
+    ///
+    /// [`create_partition`]: #method.create_partition
+    /// [`free_regions`]: #method.free_regions
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the layout is invalid or
+    /// doesn't fit, naming the partition at fault, and by how much the layout
+    /// is too large, in which case nothing is created.
+    ///
+    /// Fitting in the free space doesn't guarantee that there's somewhere to
+    /// put each partition, as partitions must be aligned to their own size,
+    /// so this function will also return an error if any partition could not
+    /// be created, in which case the partitions before it are deleted again.
+    pub fn apply_layout(&self, layout: &[LayoutEntry]) -> Result<(), Error> {
+        let existing_names: Vec<String> = self
+            .list_partitions()?
+            .into_iter()
+            .filter(|partition| partition.kind.is_some())
+            .map(|partition| partition.name)
+            .collect();
+
+        let free_space = self
+            .free_regions()?
+            .iter()
+            .map(|(_, length)| length * apa::SECTOR_SIZE / (1024 * 1024))
+            .sum();
+
+        layout::validate(layout, &existing_names, free_space)?;
+
+        for (index, entry) in layout.iter().enumerate() {
+            if let Err(message) = self.create_partition(&entry.name, entry.kind, entry.size) {
+                for created in layout[..index].iter().rev() {
+                    if let Err(message) = self.delete_partition(&created.name) {
+                        warn!("Failed to delete partition {}: {}", created.name, message);
+                    }
+                }
+
+                return Err(Error::Other(format!(
+                    "Partition {}: {}",
+                    entry.name, message
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a layout from JSON, as described in the [`layout`] module, and
+    /// creates its partitions with [`apply_layout`].
+    ///
+    /// [`layout`]: layout/index.html
+    /// [`apply_layout`]: #method.apply_layout
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the JSON is not a valid layout,
+    /// or in any of the cases `apply_layout` does.
+    #[cfg(feature = "serde")]
+    pub fn import_layout_json(&self, json: &str) -> Result<(), Error> {
+        self.apply_layout(&layout::parse_json(json)?)
+    }
+
+    /// Create a new partition the same size and kind as an existing one, and
+    /// copy the existing partition's contents into it.
+    ///
+    /// Only one PFS partition can be mounted at a time, so the source and new
+    /// partitions are mounted in turn, copying up to
+    /// [`CLONE_BUFFER_SIZE`] bytes of file contents each time. If anything
+    /// fails, the new partition is deleted again.
+    ///
+    /// HDL partitions can't be cloned, as the game's location on the disk is
+    /// recorded within the partition itself, by absolute sector, so a raw
+    /// copy would still refer to the source partition's sectors.
+    ///
+    /// [`CLONE_BUFFER_SIZE`]: constant.CLONE_BUFFER_SIZE.html
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a PFS partition is mounted, if
+    /// the source partition is not a PFS partition, or contains symlinks, if
+    /// the new partition could not be created, or if any of the contents could
+    /// not be copied.
+    pub fn clone_partition(
+        &self,
+        source_partition_name: &str,
+        destination_partition_name: &str,
+    ) -> Result<(), Error> {
+        if self.pfs.is_some() {
+            return Err(Error::Other(
+                "Cannot clone partitions while a PFS partition is mounted".to_string(),
+            ));
+        }
+
+        let (header, _) = self.read_apa_header(source_partition_name)?;
+
+        match header.kind() {
+            Some(PartitionKind::PFS) => {}
+            Some(PartitionKind::HDL) => {
+                return Err(
+                    Error::Other("Cloning HDL partitions is not supported, as their games are located by absolute sector"
+                        .to_string()),
+                )
+            }
+            Some(kind) => {
+                return Err(Error::Other(format!(
+                    "Cloning {} partitions is not supported",
+                    kind.as_apa_fs_type()
+                )))
+            }
+            None => return Err(Error::Other("Cannot clone an unformatted partition".to_string())),
+        }
+
+        let sectors = header.length as u64
+            + header
+                .subs
+                .iter()
+                .map(|(_, length)| *length as u64)
+                .sum::<u64>();
+
+        let sector_size = apa::SECTOR_SIZE;
+
+        debug!(
+            "Cloning partition {} to {}",
+            source_partition_name, destination_partition_name
+        );
+
+        let entries = self.with_pfs_mounted(source_partition_name, read_pfs_entries)?;
+
+        self.create_partition(
+            destination_partition_name,
+            FormattablePartitionKind::PFS,
+            sectors * sector_size / (1024 * 1024),
+        )?;
+
+        let result =
+            self.copy_pfs_entries(source_partition_name, destination_partition_name, &entries);
+
+        if result.is_err() {
+            if let Err(message) = self.delete_partition(destination_partition_name) {
+                warn!(
+                    "Failed to delete {} after a failed clone: {}",
+                    destination_partition_name, message
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Copies the directories and files listed by [`read_pfs_entries`] from
+    /// one PFS partition to another, mounting each in turn.
+    ///
+    /// [`read_pfs_entries`]: fn.read_pfs_entries.html
+    fn copy_pfs_entries(
+        &self,
+        source_partition_name: &str,
+        destination_partition_name: &str,
+        entries: &[PfsEntry],
+    ) -> Result<(), Error> {
+        self.with_pfs_mounted(destination_partition_name, |pfs| {
+            for entry in entries.iter().filter(|entry| entry.len.is_none()) {
+                debug!("Creating {}", entry.path.display());
+                pfs.create_dir(entry.path.to_string_lossy().into_owned())?;
+            }
+
+            Ok(())
+        })?;
+
+        let files: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| entry.len.map(|len| (&entry.path, len)))
+            .collect();
+
+        // The next file to copy, and how far into it the copy has got
+        let mut position = (0, 0);
+
+        while position.0 < files.len() {
+            let (chunks, next) = self.with_pfs_mounted(source_partition_name, |pfs| {
+                read_pfs_chunks(pfs, &files, position)
+            })?;
+
+            self.with_pfs_mounted(destination_partition_name, |pfs| {
+                write_pfs_chunks(pfs, &files, chunks)
+            })?;
+
+            position = next;
+        }
+
+        Ok(())
+    }
+
+    /// Mounts a PFS partition at `pfs0:` just for the duration of `f`.
+    fn with_pfs_mounted<T, F>(&self, partition_name: &str, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&PFS) -> Result<T, Error>,
+    {
+        self.mount("pfs0:", partition_name)?;
+
+        let result = f(&PFS {
+            partition_name: partition_name.to_string(),
+        });
+
+        self.umount("pfs0:")?;
+
+        result
+    }
+
+    /// Checks that at least `extra` bytes can be written to the named PFS
+    /// partition, so that a later operation can fail early, rather than
+    /// running out of space part way through.
+    ///
+    /// PFS has no way to reserve space ahead of time, so this is only an
+    /// approximation; a placeholder file of `extra` bytes is written to the
+    /// partition, and then removed again. Writing a file of the same size
+    /// immediately afterwards will succeed, but anything else written in the
+    /// meantime uses up the same space.
+    ///
+    /// If the partition is already mounted, it is used as-is; otherwise, it
+    /// is mounted just for the check.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there isn't enough space, if a
+    /// different PFS partition is mounted, or if the partition could not be
+    /// mounted, or the placeholder file written or removed.
+    pub fn reserve_space(&self, partition_name: &str, extra: u64) -> Result<(), Error> {
+        match &self.pfs {
+            Some(pfs) if pfs.partition_name == partition_name => {
+                return write_placeholder(pfs, extra)
+            }
+            Some(pfs) => {
+                return Err(Error::Other(format!(
+                    "Cannot reserve space on {} while {} is mounted",
+                    partition_name, pfs.partition_name
+                )))
+            }
+            None => {}
+        }
+
+        self.mount("pfs0:", partition_name)?;
+
+        let result = write_placeholder(
+            &PFS {
+                partition_name: partition_name.to_string(),
+            },
+            extra,
+        );
+
+        self.umount("pfs0:")?;
+
+        result
+    }
+
+    /// Records the disk's partitions and, if `include_files` is `true`, the
+    /// metadata of every file and directory within its PFS partitions, for
+    /// comparison with [`Snapshot::diff`].
+    ///
+    /// Free space is not recorded. Each PFS partition is mounted in turn to
+    /// read its files, so no PFS partition may already be mounted.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partitions could not be
+    /// listed, if `include_files` is `true` and a PFS partition is already
+    /// mounted, or if a PFS partition could not be mounted or read.
+    ///
+    /// [`Snapshot::diff`]: snapshot/struct.Snapshot.html#method.diff
+    pub fn snapshot(&self, include_files: bool) -> Result<Snapshot, Error> {
+        if include_files && self.pfs.is_some() {
+            return Err(Error::Other(
+                "Cannot snapshot files while a PFS partition is mounted".to_string(),
+            ));
+        }
+
+        let mut snapshot = Snapshot::new();
+
+        for partition in self.list_partitions()? {
+            if partition.kind.is_none() {
+                continue;
+            }
+
+            snapshot
+                .partitions
+                .entry(partition.name.clone())
+                .or_default()
+                .push(partition);
+        }
+
+        if !include_files {
+            return Ok(snapshot);
+        }
+
+        for (name, entries) in &snapshot.partitions {
+            if entries[0].kind != Some(PartitionKind::PFS) {
+                continue;
+            }
+
+            self.mount("pfs0:", name)?;
+
+            let files = read_pfs_metadata(&PFS {
+                partition_name: name.clone(),
+            });
+
+            self.umount("pfs0:")?;
+
+            for (path, file) in files? {
+                snapshot.files.insert((name.clone(), path), file);
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Compares the disks at `earlier_path` and `later_path`, reporting the
+    /// partitions and, if `include_files` is `true`, the files which were
+    /// added, removed or changed between them.
+    ///
+    /// As only one disk may be open at a time, each is opened, recorded with
+    /// [`snapshot`] and closed again in turn.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either disk could not be opened,
+    /// or in any of the cases [`snapshot`] does.
+    ///
+    /// [`snapshot`]: #method.snapshot
+    pub fn diff_images<P: std::fmt::Debug + AsRef<Path>>(
+        earlier_path: P,
+        later_path: P,
+        include_files: bool,
+    ) -> Result<SnapshotDiff, Error> {
+        let earlier = Self::open(earlier_path)?.snapshot(include_files)?;
+        let later = Self::open(later_path)?.snapshot(include_files)?;
+
+        Ok(earlier.diff(&later))
+    }
+
+    /// Initialise a file system on a given partition.
+    ///
+    /// Formatting destroys anything already on the partition, so unless
+    /// `options` forces it, the partition is first mounted read-only, and
+    /// checked for HDL game information, and if either is found, it is left
+    /// alone and an error is returned instead.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the partition does not already
+    /// exist, the partition name is invalid, or if the format process fails.
+    ///
+    /// Unless forced, this function will also return an error if a PFS
+    /// partition is already mounted, or if the partition already contains a
+    /// PFS file system or an HDL game.
+    ///
+    /// If verifying, this function will also return an error if a PFS
+    /// partition is already mounted, or if the formatted partition could not
+    /// be mounted and read.
+    pub fn format_partition(
+        &self,
+        partition_name: &str,
+        kind: FormattablePartitionKind,
+        options: &FormatOptions,
+    ) -> Result<(), Error> {
+        if !options.forces() {
+            if self.pfs.is_some() {
+                return Err(Error::Other(
+                    "Cannot check for an existing file system while a PFS partition is mounted"
+                        .to_string(),
+                ));
+            }
+
+            if self.has_pfs(partition_name) {
+                return Err(Error::Other(format!(
+                    "Partition {} is already formatted",
+                    partition_name
+                )));
+            }
+
+            if self.has_hdl_game_info(partition_name)? {
+                return Err(Error::Other(format!(
+                    "Partition {} already contains an HDL game",
+                    partition_name
+                )));
+            }
+        }
+
+        if options.verifies() && kind != FormattablePartitionKind::HDL && self.pfs.is_some() {
+            return Err(Error::Other(
+                "Cannot verify formatting while a PFS partition is mounted".to_string(),
+            ));
+        }
+
+        let kind: PartitionKind = match kind {
+            FormattablePartitionKind::MBR => FormattablePartitionKind::PFS,
+            v => v,
+        }
+        .into();
+
+        // TODO: How do we check encoding? What encoding is the target?
+        // JIS X 0201? US ASCII?
+
+        let device =
+            match std::ffi::CString::new(format!("{}0:", kind.as_apa_fs_type().to_lowercase())) {
+                Ok(device_path) => device_path,
+                Err(error) => return Err(Error::Other(error.to_string())),
+            };
+
+        // TODO: Ensure path does not contain invalid characters?
+
+        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
+            Ok(partition_path) => partition_path,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        debug!(
+            "Formatting partition {} as {}",
+            partition_name,
+            kind.as_apa_fs_type()
+        );
+
+        let mut format_arg: [i32; 3] = [PFS_ZONE_SIZE, 0x2d66, PFS_FRAGMENT];
+
+        ok_on_zero_or_strerror(
+            unsafe {
+                ps2hdd_sys::iomanx_format(
+                    device.as_ptr(),
+                    partition.as_ptr(),
+                    format_arg.as_mut_ptr() as *mut core::ffi::c_void,
+                    std::mem::size_of_val(&format_arg) as u64,
+                )
+            },
+            "PFS partition formatting failed",
+        )?;
+
+        if options.verifies() && kind == PartitionKind::PFS {
+            self.verify_pfs_format(partition_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Acquire a file I/O object bound to the specified `pfs` partition.
+    pub fn mount_pfs(&mut self, partition_name: &str) -> Result<&PFS, Error> {
+        if self.pfs.is_some() {
+            return Err(Error::Other(
+                "A PFS partition is already mounted".to_string(),
+            ));
+        }
+
+        self.mount("pfs0:", partition_name)?;
+
+        self.pfs = Some(PFS {
+            partition_name: partition_name.to_string(),
+        });
+
+        self.pfs
+            .as_ref()
+            .ok_or_else(|| Error::Other("Failed to retrieve reference".to_string()))
+    }
+
+    /// Mounts the PFS partition at `index` within the partition map, which
+    /// is the order [`list_partitions`] lists them in, for tools which
+    /// address partitions by position rather than by name.
+    ///
+    /// The partition is mounted using its name exactly as stored in its
+    /// header, so this works even if the name is corrupt, or not valid
+    /// UTF-8. The mounted driver's partition name has any invalid UTF-8
+    /// replaced.
+    ///
+    /// [`list_partitions`]: #method.list_partitions
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `index` is out of range, or
+    /// refers to free space or a sub-partition, or in any of the cases
+    /// [`mount_pfs`](#method.mount_pfs) does.
+    pub fn mount_pfs_by_index(&mut self, index: usize) -> Result<&PFS, Error> {
+        if self.pfs.is_some() {
+            return Err(Error::Other(
+                "A PFS partition is already mounted".to_string(),
+            ));
+        }
+
+        let partition_id = self.partition_id_at(index)?;
+
+        self.mount_id("pfs0:", &partition_id, 0)?;
+
+        self.pfs = Some(PFS {
+            partition_name: String::from_utf8_lossy(&partition_id).into_owned(),
+        });
+
+        self.pfs
+            .as_ref()
+            .ok_or_else(|| Error::Other("Failed to retrieve reference".to_string()))
+    }
+
+    /// Unmount the currently-mounted PFS device.
+    ///
+    /// The driver's `pfs0:` mount point is unmounted too, so that the
+    /// partition is released, and can be mounted again or deleted.
+    pub fn umount_pfs(&mut self) -> Result<(), Error> {
+        if self.pfs.is_none() {
+            return Err(Error::Other(
+                "No PFS partition is mounted; nothing to unmount".to_string(),
+            ));
+        }
+
+        self.umount("pfs0:")?;
+        self.pfs = None;
+
+        Ok(())
+    }
+
+    /// Acquire a file I/O object bound to the specified `hdlfs` partition.
+    pub fn mount_hdlfs(&mut self, partition_name: &str) -> Result<&HDLFS, Error> {
+        if self.hdlfs.is_some() {
+            return Err(Error::Other(
+                "An HDLFS partition is already mounted".to_string(),
+            ));
+        }
+
+        self.mount("hdl0:", partition_name)?;
+
+        self.hdlfs = Some(HDLFS {
+            partition_name: partition_name.to_string(),
+        });
+
+        self.hdlfs
+            .as_ref()
+            .ok_or_else(|| Error::Other("Failed to retrieve reference".to_string()))
+    }
+
+    /// Mounts the HDLFS partition at `index` within the partition map, as
+    /// [`mount_pfs_by_index`] does for PFS partitions.
+    ///
+    /// [`mount_pfs_by_index`]: #method.mount_pfs_by_index
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `index` is out of range, or
+    /// refers to free space or a sub-partition, or in any of the cases
+    /// [`mount_hdlfs`](#method.mount_hdlfs) does.
+    pub fn mount_hdlfs_by_index(&mut self, index: usize) -> Result<&HDLFS, Error> {
+        if self.hdlfs.is_some() {
+            return Err(Error::Other(
+                "An HDLFS partition is already mounted".to_string(),
+            ));
+        }
+
+        let partition_id = self.partition_id_at(index)?;
+
+        self.mount_id("hdl0:", &partition_id, 0)?;
+
+        self.hdlfs = Some(HDLFS {
+            partition_name: String::from_utf8_lossy(&partition_id).into_owned(),
+        });
+
+        self.hdlfs
+            .as_ref()
+            .ok_or_else(|| Error::Other("Failed to retrieve reference".to_string()))
+    }
+
+    /// Unmount the currently-mounted HDLFS device.
+    ///
+    /// The driver's `hdl0:` mount point is unmounted too, so that the
+    /// partition is released, and can be mounted again or deleted.
+    pub fn umount_hdlfs(&mut self) -> Result<(), Error> {
+        if self.hdlfs.is_none() {
+            return Err(Error::Other(
+                "No HDLFS partition is mounted; nothing to unmount".to_string(),
+            ));
+        }
+
+        self.umount("hdl0:")?;
+        self.hdlfs = None;
+
+        Ok(())
+    }
+
+    /// Closes the disk and shuts down the drivers, then opens the same disk
+    /// and initialises them again, to recover from the drivers getting into
+    /// a bad state, without dropping this `PS2HDD`.
+    ///
+    /// Any mounted partitions are unmounted first, as far as the drivers
+    /// allow; they're always considered unmounted afterwards. This instance
+    /// keeps its place as the single open PS2 HDD throughout, so another
+    /// can't be opened in the meantime.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the drivers could not be
+    /// initialised again, in which case the disk is left closed, and this
+    /// `PS2HDD` should be dropped.
+    pub fn close_and_reopen(&mut self) -> Result<(), Error> {
+        warn!("Closing and reopening PS2 HDD at {}", self.path.display());
+
+        if let Err(message) = self.umount_all() {
+            warn!("Failed to unmount before reopening: {}", message);
+        }
+
+        self.pfs = None;
+        self.hdlfs = None;
+
+        unsafe { ps2hdd_sys::atad_close() };
+
+        Self::init_drivers(&self.path)
+    }
+
+    /// Checks whether either a PFS or an HDLFS partition is mounted.
+    pub fn is_mounted_anywhere(&self) -> bool {
+        self.pfs.is_some() || self.hdlfs.is_some()
+    }
+
+    /// Unmounts whichever of the PFS and HDLFS devices are mounted, such as
+    /// before an operation which needs nothing to be mounted. Does nothing if
+    /// neither is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either device could not be
+    /// unmounted. Both are attempted regardless, and the first error is
+    /// returned.
+    pub fn umount_all(&mut self) -> Result<(), Error> {
+        let pfs_result = match self.pfs {
+            Some(_) => self.umount_pfs(),
+            None => Ok(()),
+        };
+
+        let hdlfs_result = match self.hdlfs {
+            Some(_) => self.umount_hdlfs(),
+            None => Ok(()),
+        };
+
+        pfs_result.and(hdlfs_result)
+    }
+
+    /// Returns whichever driver is currently mounted, for code which works
+    /// the same with either kind of file system, or `None` if neither is.
+    ///
+    /// One PFS and one HDLFS partition may be mounted at the same time; if
+    /// both are, the PFS driver is returned.
+    pub fn mounted_driver(&self) -> Option<MountedDriver<'_>> {
+        match (&self.pfs, &self.hdlfs) {
+            (Some(pfs), _) => Some(MountedDriver::PFS(pfs)),
+            (None, Some(hdlfs)) => Some(MountedDriver::HDLFS(hdlfs)),
+            (None, None) => None,
+        }
+    }
+
+    /// Returns the most recent failure reported by the drivers on this
+    /// thread, including those of any partition drivers, or `None` if there
+    /// haven't been any.
+    ///
+    /// The drivers only report an error number, so this records which
+    /// operation failed alongside it, which can help to track down where an
+    /// otherwise opaque error came from. Some failures are expected, and
+    /// handled internally, such as when creating a directory whose parent
+    /// doesn't exist yet, so this is not necessarily the cause of the most
+    /// recent error returned.
+    pub fn last_error(&self) -> Option<LastError> {
+        ffi_utils::last_error().map(|(operation, code)| LastError { operation, code })
+    }
+
+    /// Returns the raw name of the main partition at `index` within the
+    /// partition map, read directly from its header, rather than through the
+    /// drivers, which can't list partitions whose names aren't valid UTF-8.
+    fn partition_id_at(&self, index: usize) -> Result<Vec<u8>, Error> {
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        let headers = apa::read_partition_map(&mut file)?;
+
+        let bytes = match headers.get(index) {
+            Some(bytes) => bytes,
+            None => {
+                return Err(Error::Other(format!(
+                    "Partition index {} is out of range; the disk has {} partitions",
+                    index,
+                    headers.len()
+                )))
+            }
+        };
+
+        let header = apa::ApaHeader::parse(bytes);
+
+        if header.kind().is_none() {
+            return Err(Error::Other(format!(
+                "Partition index {} is free space",
+                index
+            )));
+        }
+
+        if header.flags.is_sub() {
+            return Err(Error::Other(format!(
+                "Partition index {} is a sub-partition of {}",
+                index, header.id
+            )));
+        }
+
+        Ok(apa::raw_id(bytes).to_vec())
+    }
+
+    /// Returns `true` if the partition holds a PFS file system which can be
+    /// mounted, without modifying it.
+    fn has_pfs(&self, partition_name: &str) -> bool {
+        if self
+            .mount_with_flags("pfs0:", partition_name, ps2hdd_sys::FIO_MT_RDONLY as i32)
+            .is_err()
+        {
+            return false;
+        }
+
+        if let Err(message) = self.umount("pfs0:") {
+            warn!("Failed to unmount {}: {}", partition_name, message);
+        }
+
+        true
+    }
+
+    /// Mounts a freshly-formatted PFS partition, and checks that its root
+    /// directory contains only the `.` and `..` entries.
+    fn verify_pfs_format(&self, partition_name: &str) -> Result<(), Error> {
+        if let Err(message) = self.mount("pfs0:", partition_name) {
+            return Err(Error::Other(format!(
+                "Formatted partition could not be mounted: {}",
+                message
+            )));
+        }
+
+        let result = list_raw_dir_names("pfs0:/");
+
+        self.umount("pfs0:")?;
+
+        match result {
+            Ok(names) if names == [".", ".."] => Ok(()),
+            Ok(names) => Err(Error::Other(format!(
+                "Formatted partition has unexpected root directory entries: {:?}",
+                names
+            ))),
+            Err(message) => Err(Error::Other(format!(
+                "Formatted partition's root directory could not be read: {}",
+                message
+            ))),
+        }
+    }
+
+    fn mount(&self, mount_point: &str, partition_name: &str) -> Result<(), Error> {
+        self.mount_with_flags(mount_point, partition_name, 0)
+    }
+
+    fn mount_with_flags(
+        &self,
+        mount_point: &str,
+        partition_name: &str,
+        flags: i32,
+    ) -> Result<(), Error> {
+        self.mount_id(mount_point, partition_name.as_bytes(), flags)
+    }
+
+    /// Mounts a partition by its name as raw bytes, which needn't be valid
+    /// UTF-8.
+    fn mount_id(&self, mount_point: &str, partition_id: &[u8], flags: i32) -> Result<(), Error> {
+        // Each kind of file system has its own mount point, so a PFS and an
+        // HDLFS partition can be mounted together, but mounting over either
+        // one would leave its driver pointing at the wrong partition
+        let in_use = match mount_point {
+            "pfs0:" => self.pfs.is_some(),
+            "hdl0:" => self.hdlfs.is_some(),
+            _ => false,
+        };
+
+        if in_use {
+            return Err(Error::Other(format!("{} is already in use", mount_point)));
+        }
+
+        let mount_path = match std::ffi::CString::new(mount_point) {
+            Ok(mount) => mount,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        // TODO: Ensure path does not contain invalid characters?
+
+        let partition = match std::ffi::CString::new([b"hdd0:", partition_id].concat()) {
+            Ok(partition_path) => partition_path,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        debug!(
+            "Mounting partition {} at {}",
+            String::from_utf8_lossy(partition_id),
+            mount_point
+        );
+
+        ok_on_zero_or_strerror(
+            unsafe {
+                ps2hdd_sys::iomanx_mount(
+                    mount_path.as_ptr(),
+                    partition.as_ptr(),
+                    flags,
+                    std::ptr::null_mut(),
+                    0,
+                )
+            },
+            "Mounting failed",
+        )
+        .map_err(|error| error.or_not_found(String::from_utf8_lossy(partition_id), "partition"))?;
+
+        Ok(())
+    }
+
+    fn umount(&self, mount_point: &str) -> Result<(), Error> {
+        let mount_path = match std::ffi::CString::new(mount_point) {
+            Ok(mount) => mount,
+            Err(error) => return Err(Error::Other(error.to_string())),
+        };
+
+        debug!("Unmounting {}", mount_point);
+
+        ok_on_zero_or_strerror(
+            unsafe { ps2hdd_sys::iomanx_umount(mount_path.as_ptr()) },
+            "Unmounting failed",
+        )?;
+
+        Ok(())
+    }
+}
+
+/// The smallest and largest partitions which may be created, in mebibytes
+const MIN_PARTITION_SIZE: u64 = 128;
+const MAX_PARTITION_SIZE: u64 = 32 * 1024;
+
+/// Returns the size of partition, in mebibytes, needed to hold an HDL game of
+/// `iso_size` bytes.
+fn hdl_partition_size(iso_size: u64) -> Result<u64, Error> {
+    let required = iso_size + ps2hdd_sys::HDL_GAME_DATA_OFFSET as u64;
+    let mebibytes = required.div_ceil(1024 * 1024);
+    let size = mebibytes.next_power_of_two().max(MIN_PARTITION_SIZE);
+
+    if size > MAX_PARTITION_SIZE {
+        return Err(Error::Other(format!(
+            "Game of {} bytes is too large for a {}MiB partition",
+            iso_size, MAX_PARTITION_SIZE
+        )));
+    }
+
+    Ok(size)
+}
+
+/// Punches a hole in `file`, returning `false` if that isn't supported.
+#[cfg(target_os = "linux")]
+fn discard(file: &std::fs::File, offset: u64, length: u64) -> Result<bool, Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let result = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            offset as libc::off_t,
+            length as libc::off_t,
+        )
+    };
+
+    if result == 0 {
+        return Ok(true);
+    }
+
+    let error = std::io::Error::last_os_error();
+
+    match error.raw_os_error() {
+        Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => Ok(false),
+        _ => Err(Error::Other(format!("couldn't discard sectors: {}", error))),
+    }
+}
+
+/// Punches a hole in `file`, returning `false` if that isn't supported.
+#[cfg(not(target_os = "linux"))]
+fn discard(_file: &std::fs::File, _offset: u64, _length: u64) -> Result<bool, Error> {
+    Ok(false)
+}
+
+/// Allocates all `size` bytes of `file` on the host, falling back to writing
+/// zeros if the file system can't allocate them directly.
+#[cfg(target_os = "linux")]
+fn preallocate_file(file: &std::fs::File, size: u64) -> Result<(), Error> {
+    use std::os::unix::io::AsRawFd;
+
+    let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size as libc::off_t) };
+
+    if result == 0 {
+        return Ok(());
+    }
+
+    let error = std::io::Error::last_os_error();
+
+    match error.raw_os_error() {
+        Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => write_zeros(file, size),
+        _ => Err(Error::Other(error.to_string())),
+    }
+}
+
+/// Allocates all `size` bytes of `file` on the host by writing zeros.
+#[cfg(not(target_os = "linux"))]
+fn preallocate_file(file: &std::fs::File, size: u64) -> Result<(), Error> {
+    write_zeros(file, size)
+}
+
+/// Writes `size` bytes of zeros to the start of `file`.
+fn write_zeros(mut file: &std::fs::File, size: u64) -> Result<(), Error> {
+    use std::io::Write;
+
+    let zeros = vec![0u8; PREALLOCATE_CHUNK_SIZE];
+    let mut remaining = size;
+
+    while remaining > 0 {
+        let length = remaining.min(PREALLOCATE_CHUNK_SIZE as u64) as usize;
+
+        if let Err(error) = file.write_all(&zeros[..length]) {
+            return Err(Error::Other(error.to_string()));
+        }
+
+        remaining -= length as u64;
+    }
+
+    match file.sync_all() {
+        Ok(()) => Ok(()),
+        Err(error) => Err(Error::Other(error.to_string())),
+    }
+}
+
+/// The most file contents held in memory at once by
+/// [`PS2HDD::clone_partition`](struct.PS2HDD.html#method.clone_partition)
+pub const CLONE_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+/// A directory or file within a PFS partition being cloned
+struct PfsEntry {
+    path: PathBuf,
+    /// The length of a file, or `None` for a directory
+    len: Option<u64>,
+}
+
+/// Lists every directory and file within a PFS partition, in an order
+/// suitable for recreating them.
+fn read_pfs_entries(pfs: &PFS) -> Result<Vec<PfsEntry>, Error> {
+    let mut entries = Vec::new();
+
+    for entry in pfs.walk_dir("/")? {
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            entries.push(PfsEntry { path, len: None });
+        } else if metadata.is_file() {
+            entries.push(PfsEntry {
+                path,
+                len: Some(metadata.len()),
+            });
+        } else {
+            return Err(Error::Other(format!(
+                "{}: Cannot copy symlinks",
+                path.display()
+            )));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// A piece of a file being cloned; the index of the file, the offset of the
+/// piece within it, and its contents
+type PfsChunk = (usize, u64, Vec<u8>);
+
+/// Reads up to [`CLONE_BUFFER_SIZE`] bytes of `files`, starting `position.1`
+/// bytes into the file at index `position.0`, returning the pieces read and
+/// the position to continue from.
+///
+/// Every file gets at least one piece, even if it's empty, so that it's
+/// created by [`write_pfs_chunks`].
+fn read_pfs_chunks(
+    pfs: &PFS,
+    files: &[(&PathBuf, u64)],
+    mut position: (usize, u64),
+) -> Result<(Vec<PfsChunk>, (usize, u64)), Error> {
+    use std::io::SeekFrom;
+
+    let mut chunks = Vec::new();
+    let mut buffered = 0;
+
+    while position.0 < files.len() && buffered < CLONE_BUFFER_SIZE {
+        let (path, len) = files[position.0];
+        let length = (len - position.1).min((CLONE_BUFFER_SIZE - buffered) as u64);
+
+        let mut file = pfs.open_file(path)?;
+
+        if let Err(error) = file.seek(SeekFrom::Start(position.1)) {
+            return Err(Error::Other(format!(
+                "{}: failed to seek file: {}",
+                path.display(),
+                error
+            )));
+        }
+
+        let mut data = Vec::with_capacity(length as usize);
+
+        match file.take(length).read_to_end(&mut data) {
+            Ok(read) if read as u64 == length => {}
+            Ok(read) => {
+                return Err(Error::Other(format!(
+                    "{}: only read {} of {} bytes",
+                    path.display(),
+                    read,
+                    length
+                )))
+            }
+            Err(error) => {
+                return Err(Error::Other(format!(
+                    "{}: failed to read file: {}",
+                    path.display(),
+                    error
+                )))
+            }
+        }
+
+        chunks.push((position.0, position.1, data));
+        buffered += length as usize;
+
+        position.1 += length;
+
+        if position.1 == len {
+            position = (position.0 + 1, 0);
+        }
+    }
+
+    Ok((chunks, position))
+}
+
+/// Writes the pieces of files read by [`read_pfs_chunks`], creating each
+/// file with its first piece, and appending the rest.
+fn write_pfs_chunks(
+    pfs: &PFS,
+    files: &[(&PathBuf, u64)],
+    chunks: Vec<PfsChunk>,
+) -> Result<(), Error> {
+    use std::io::{SeekFrom, Write};
+
+    for (index, offset, data) in chunks {
+        let path = files[index].0;
+
+        debug!(
+            "Copying {} bytes of {} at {}",
+            data.len(),
+            path.display(),
+            offset
+        );
+
+        let mut file = if offset == 0 {
+            pfs.create_file(path)?
+        } else {
+            let device_path = driver::device_path(pfs.get_device_root(), path)?;
+            let mut file = fs::File::open(&device_path, ps2hdd_sys::IOMANX_O_WRONLY as i32, 0)?;
+
+            if let Err(error) = file.seek(SeekFrom::Start(offset)) {
+                return Err(Error::Other(format!(
+                    "{}: failed to seek file: {}",
+                    path.display(),
+                    error
+                )));
+            }
+
+            file
+        };
+
+        if let Err(error) = file.write_all(&data) {
+            return Err(Error::Other(format!(
+                "{}: failed to write file: {}",
+                path.display(),
+                error
+            )));
+        }
+
+        file.close()?;
+    }
+
+    Ok(())
+}
+
+/// The file written to check that space is available by `reserve_space`
+const PLACEHOLDER_PATH: &str = ".reserve_space.tmp";
+
+/// Writes, and then removes, a placeholder file of `length` bytes.
+fn write_placeholder(pfs: &PFS, length: u64) -> Result<(), Error> {
+    use std::io::Write;
+
+    debug!(
+        "Writing {} byte placeholder to {}",
+        length, pfs.partition_name
+    );
+
+    let mut file = pfs.create_file(PLACEHOLDER_PATH)?;
+    let zeroes = vec![0u8; (length as usize).min(1024 * 1024)];
+    let mut remaining = length;
+    let mut result = Ok(());
+
+    while remaining > 0 {
+        let chunk = &zeroes[..(remaining as usize).min(zeroes.len())];
+
+        if let Err(error) = file.write_all(chunk) {
+            result = Err(Error::Other(format!(
+                "Not enough space to reserve {} bytes on {}: {}",
+                length, pfs.partition_name, error
+            )));
+            break;
+        }
+
+        remaining -= chunk.len() as u64;
+    }
+
+    drop(file);
+
+    let placeholder = match std::ffi::CString::new(format!("pfs0:/{}", PLACEHOLDER_PATH)) {
+        Ok(path) => path,
+        Err(error) => {
+            return Err(Error::NulByte {
+                context: "couldn't convert path",
+                error,
+            })
+        }
+    };
+
+    ok_on_zero_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_remove(placeholder.as_ptr()) },
+        "Failed to remove placeholder file",
+    )?;
+
+    result
+}
+
+/// Reads the metadata of every directory and file within a PFS partition.
+fn read_pfs_metadata(pfs: &PFS) -> Result<Vec<(PathBuf, FileSnapshot)>, Error> {
+    let mut files = Vec::new();
+
+    for entry in pfs.walk_dir("/")? {
+        let metadata = entry.metadata()?;
+
+        files.push((
+            entry.root().join(entry.file_name()),
+            FileSnapshot {
+                len: metadata.len(),
+                file_type: metadata.file_type(),
+                modified: metadata.modified(),
+            },
+        ));
+    }
+
+    Ok(files)
+}
+
+/// Reads the names of every entry within a directory, including `.` and `..`.
+fn list_raw_dir_names(path: &str) -> Result<Vec<String>, Error> {
+    let path = match std::ffi::CString::new(path) {
+        Ok(path) => path,
+        Err(error) => {
+            return Err(Error::NulByte {
+                context: "couldn't convert path",
+                error,
+            })
+        }
+    };
+
+    let mut temp_dirent: ps2hdd_sys::iox_dirent_t = unsafe { std::mem::zeroed() };
+    let mut names = Vec::new();
+
+    let directory_handle = ok_on_nonnegative_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_dopen(path.as_ptr()) },
+        "Failed to list directory",
+    )?;
+
+    loop {
+        let result = unsafe { ps2hdd_sys::iomanx_dread(directory_handle, &mut temp_dirent) };
+
+        if result < 0 {
+            unsafe { ps2hdd_sys::iomanx_close(directory_handle) };
+            return Err(Error::Other(format!(
+                "Failed to list directory: {}",
+                result
+            )));
+        }
+
+        if result == 0 {
+            break;
+        }
+
+        match unsafe { std::ffi::CStr::from_ptr(temp_dirent.name.as_ptr()) }.to_str() {
+            Ok(name) => names.push(name.to_owned()),
+            Err(error) => {
+                unsafe { ps2hdd_sys::iomanx_close(directory_handle) };
+                return Err(Error::Other(format!(
+                    "could not convert the directory name to a String: {}",
+                    error
+                )));
+            }
+        }
+    }
+
+    ok_on_zero_or_strerror(
+        unsafe { ps2hdd_sys::iomanx_close(directory_handle) },
+        "Failed to close directory handle",
+    )?;
+
+    Ok(names)
+}
+
+impl Drop for PS2HDD {
+    fn drop(&mut self) {
+        // Leave nothing mounted in the drivers' global state for the next
+        // PS2 HDD opened in this process
+        if let Err(message) = self.umount_all() {
+            warn!("Failed to unmount before closing: {}", message);
+        }
+
+        let was_active = IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
+        assert!(was_active, "PS2HDD dropped while not active");
+        debug!("Closing PS2 HDD at {}", self.path.display());
+        unsafe { ps2hdd_sys::atad_close() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partition_kind::PartitionFlags;
+    use serial_test::serial;
+    use std::io::Write;
+
+    // A disk image needs to be at least 6GB in size for APA to work
+    // more is better, and 20GB is the "normal" minimum, but this is
+    // just enough  to fit any of the minimum-size 128MB partitions in
+    static DEMO_FILE_SIZE: u64 = 6 * 1024 * 1024 * 1024;
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn uses_devices_in_turn() {
+        let first_path = "hdd.img";
+        let second_path = "hdd2.img";
+
+        for path in &[first_path, second_path] {
+            let ps2hdd = match PS2HDD::create(path, DEMO_FILE_SIZE) {
+                Ok(ps2hdd) => ps2hdd,
+                Err(message) => panic!(message),
+            };
+
+            if let Err(message) = ps2hdd.initialize() {
+                panic!(message);
+            }
+
+            if let Err(message) =
+                ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        let result = PS2HDD::with_device(first_path, |ps2hdd| {
+            let pfs = ps2hdd.mount_pfs("TESTPART")?;
+            pfs.write_file("source.txt", b"from the first disk")?;
+            pfs.read_file("source.txt")
+        });
+
+        let contents = match result {
+            Ok(Ok(contents)) => contents,
+            Ok(Err(message)) | Err(message) => panic!(message),
+        };
+
+        assert!(
+            !IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed),
+            "device was left active"
+        );
+
+        // The first disk was left mounted, which mustn't stop the second
+        // from mounting its own partition
+        let result = PS2HDD::with_device(second_path, |ps2hdd| {
+            ps2hdd
+                .mount_pfs("TESTPART")?
+                .write_file("copied.txt", &contents)
+        });
+
+        if let Ok(Err(message)) | Err(message) = result {
+            panic!(message);
+        }
+
+        let result = PS2HDD::with_device(second_path, |ps2hdd| {
+            let pfs = ps2hdd.mount_pfs("TESTPART")?;
+            Ok::<_, Error>((
+                pfs.read_file("copied.txt")?,
+                pfs.read_file("source.txt").is_err(),
+            ))
+        });
+
+        assert_eq!(result, Ok(Ok((b"from the first disk".to_vec(), true))));
+
+        let first = match PS2HDD::open(first_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert!(
+            PS2HDD::with_device(second_path, |_| ()).is_err(),
+            "opened a second device while one was open"
+        );
+
+        drop(first);
+
+        std::fs::remove_file(first_path).expect("could not delete demo file");
+        std::fs::remove_file(second_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn recovers_from_panics_while_opening() {
+        let demo_file_path = "hdd.img";
+
+        drop(std::fs::File::create(demo_file_path).expect("couldn't create demo file"));
+
+        PANIC_WHILE_OPENING.store(true, std::sync::atomic::Ordering::Relaxed);
+
+        assert!(
+            std::panic::catch_unwind(|| PS2HDD::open(demo_file_path)).is_err(),
+            "opening didn't panic"
+        );
+
+        assert!(
+            !IS_DEVICE_ACTIVE.load(std::sync::atomic::Ordering::Relaxed),
+            "device was left active after panicking"
+        );
+
+        drop(match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        });
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn only_one_instance_allowed() {
+        let demo_file_path = "hdd.img";
+
+        let instance_1 = PS2HDD::create(demo_file_path, DEMO_FILE_SIZE);
+        let instance_2 = PS2HDD::open(demo_file_path);
+        let instance_3 = PS2HDD::open(demo_file_path);
+
+        let instance = match instance_1 {
+            Ok(instance) => instance,
+            Err(message) => panic!(format!(
+                "First construction should've been okay, instead got {:?}",
+                message
+            )),
+        };
+
+        assert_eq!(
+            instance_2.unwrap_err(),
+            Error::DeviceAlreadyOpen,
+            "Second construction didn't return an error"
+        );
+        assert_eq!(
+            instance_3.unwrap_err(),
+            Error::DeviceAlreadyOpen,
+            "Third construction didn't return an error"
+        );
+
+        drop(instance);
+
+        let instance_4 = PS2HDD::open(demo_file_path);
+
+        assert!(
+            instance_4.is_ok(),
+            "Fourth construction should've been okay"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn initializes_disks_and_lists_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            partitions,
+            vec![
+                PartEntry {
+                    name: "__mbr".to_string(),
+                    kind: Some(PartitionKind::MBR),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 0
+                },
+                PartEntry {
+                    name: "__net".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 262144
+                },
+                PartEntry {
+                    name: "__system".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 524288
+                },
+                PartEntry {
+                    name: "__sysconf".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 786432
+                },
+                PartEntry {
+                    name: "__common".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 1048576
+                }
+            ],
+            "unexpected partition list"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn initializes_disks_creates_and_formats_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            partitions,
+            vec![
+                PartEntry {
+                    name: "__mbr".to_string(),
+                    kind: Some(PartitionKind::MBR),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 0
+                },
+                PartEntry {
+                    name: "__net".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 262144
+                },
+                PartEntry {
+                    name: "__system".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 524288
+                },
+                PartEntry {
+                    name: "__sysconf".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 786432
+                },
+                PartEntry {
+                    name: "__common".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 1048576
+                },
+                PartEntry {
+                    name: "TESTPART".to_string(),
+                    kind: Some(PartitionKind::PFS),
+                    flags: PartitionFlags::empty(),
+                    size: 128 * 1024 * 1024,
+                    start_sector: 1310720
+                }
+            ],
+            "unexpected partition list"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn refuses_to_reformat_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs
+                .write_file("keep.txt", b"keep me")
+                .expect("Could not write keep.txt"),
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            ps2hdd
+                .format_partition(
+                    "TESTPART",
+                    FormattablePartitionKind::PFS,
+                    &FormatOptions::new(),
+                )
+                .unwrap_err(),
+            Error::Other(
+                "Cannot check for an existing file system while a PFS partition is mounted"
+                    .to_string()
+            ),
+            "Formatting while mounted didn't return an error"
+        );
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd
+                .format_partition(
+                    "TESTPART",
+                    FormattablePartitionKind::PFS,
+                    &FormatOptions::new(),
+                )
+                .unwrap_err(),
+            Error::Other("Partition TESTPART is already formatted".to_string()),
+            "Formatting a populated partition didn't return an error"
+        );
+
+        match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => assert_eq!(
+                pfs.read_file("keep.txt").expect("Could not read keep.txt"),
+                b"keep me",
+                "Refused format changed the partition's contents"
+            ),
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.format_partition(
+            "TESTPART",
+            FormattablePartitionKind::PFS,
+            &FormatOptions::new().force(true).verify(true),
+        ) {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.create_partition("GAME", FormattablePartitionKind::HDL, 128) {
+            panic!(message);
+        }
+
+        let (header, _) = match ps2hdd.read_apa_header("GAME") {
+            Ok(header) => header,
+            Err(message) => panic!(message),
+        };
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        file.seek(std::io::SeekFrom::Start(
+            header.start as u64 * apa::SECTOR_SIZE + ps2hdd_sys::HDL_GAME_DATA_OFFSET as u64,
+        ))
+        .expect("couldn't seek to game information");
+
+        file.write_all(&ps2hdd_sys::HDL_INFO_MAGIC.to_le_bytes())
+            .expect("couldn't write game information");
+
+        drop(file);
+
+        assert_eq!(
+            ps2hdd
+                .format_partition("GAME", FormattablePartitionKind::HDL, &FormatOptions::new())
+                .unwrap_err(),
+            Error::Other("Partition GAME already contains an HDL game".to_string()),
+            "Formatting a partition with a game didn't return an error"
+        );
+
+        assert_eq!(
+            ps2hdd.has_hdl_game_info("GAME"),
+            Ok(true),
+            "Refused format removed the game information"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn verifies_formatted_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.format_partition(
+            "TESTPART",
+            FormattablePartitionKind::PFS,
+            &FormatOptions::new().force(true).verify(true),
+        ) {
+            panic!(message);
+        }
+
+        // Verification unmounts again afterwards, so this remains possible
+        if let Err(message) = ps2hdd.mount_pfs("TESTPART") {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd
+                .format_partition(
+                    "__common",
+                    FormattablePartitionKind::PFS,
+                    &FormatOptions::new().force(true).verify(true),
+                )
+                .unwrap_err(),
+            Error::Other("Cannot verify formatting while a PFS partition is mounted".to_string()),
+            "Verifying while mounted didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn lists_partitions_of_unknown_kinds() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        drop(ps2hdd);
+
+        // Rewrite the new partition's type code to one we don't know about
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let mut header = *apa::read_partition_map(&mut file)
+            .expect("couldn't read partition map")
+            .last()
+            .expect("partition map was empty");
+
+        header[0x48..0x4a].copy_from_slice(&0x4242u16.to_le_bytes());
+        let checksum = apa::checksum(&header);
+        header[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+        apa::write_header(
+            &mut file,
+            apa::ApaHeader::parse(&header).start as u64,
+            &header,
+        )
+        .expect("couldn't write partition header");
+
+        drop(file);
+
+        let ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            partitions.last(),
+            Some(&PartEntry {
+                name: "TESTPART".to_string(),
+                kind: Some(PartitionKind::Unknown(0x4242)),
+                flags: PartitionFlags::empty(),
+                size: 128 * 1024 * 1024,
+                start_sector: 1310720
+            }),
+            "unexpected partition entry"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn digests_images() {
+        let demo_file_path = "hdd.img";
+        let copy_file_path = "hdd2.img";
+
+        let digests = |path: &str| {
+            let ps2hdd = match PS2HDD::open(path) {
+                Ok(ps2hdd) => ps2hdd,
+                Err(message) => panic!(message),
+            };
+
+            let whole = ps2hdd.image_digest(DigestAlgorithm::Sha256, DigestRegion::WholeImage);
+            let logical = ps2hdd.image_digest(DigestAlgorithm::Crc32, DigestRegion::LogicalSize);
+
+            match (whole, logical) {
+                (Ok(whole), Ok(logical)) => (whole, logical),
+                (Err(message), _) | (_, Err(message)) => panic!(message),
+            }
+        };
+
+        let change_byte = |path: &str, offset: u64| {
+            use std::io::{SeekFrom, Write};
+
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .expect("couldn't open demo file");
+
+            file.seek(SeekFrom::Start(offset))
+                .expect("couldn't seek demo file");
+            file.write_all(&[0xff]).expect("couldn't write demo file");
+        };
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let logical_size = match ps2hdd.logical_size() {
+            Ok(size) => size,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            logical_size,
+            6 * 128 * 1024 * 1024,
+            "logical size didn't end with the last partition"
+        );
+
+        drop(ps2hdd);
+
+        let (whole, logical) = digests(demo_file_path);
+
+        assert_eq!(whole.len(), 64, "SHA-256 digest wasn't 32 bytes of hex");
+        assert_eq!(logical.len(), 8, "CRC-32 digest wasn't 4 bytes of hex");
+
+        std::fs::copy(demo_file_path, copy_file_path).expect("couldn't copy demo file");
+
+        assert_eq!(
+            digests(copy_file_path),
+            (whole.clone(), logical.clone()),
+            "identical images had different digests"
+        );
+
+        change_byte(copy_file_path, DEMO_FILE_SIZE - 1);
+
+        let (changed_whole, unchanged_logical) = digests(copy_file_path);
+
+        assert_ne!(changed_whole, whole, "changed image had the same digest");
+        assert_eq!(
+            unchanged_logical, logical,
+            "change past the logical size affected its digest"
+        );
+
+        change_byte(copy_file_path, logical_size - 1);
+
+        assert_ne!(
+            digests(copy_file_path).1,
+            logical,
+            "changed partition had the same digest"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+        std::fs::remove_file(copy_file_path).expect("could not delete copied demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn trims_free_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        drop(ps2hdd);
+
+        // Mark the new partition as free space
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let mut header = *apa::read_partition_map(&mut file)
+            .expect("couldn't read partition map")
+            .last()
+            .expect("partition map was empty");
+
+        header[0x48..0x4a].copy_from_slice(&0u16.to_le_bytes());
+        let checksum = apa::checksum(&header);
+        header[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+        let start = apa::ApaHeader::parse(&header).start as u64;
+
+        apa::write_header(&mut file, start, &header).expect("couldn't write partition header");
+
+        // Leave something in the freed space for the trim to discard
+        let data_offset = start * apa::SECTOR_SIZE + apa::HEADER_SIZE as u64;
+
+        {
+            use std::io::{SeekFrom, Write};
+
+            file.seek(SeekFrom::Start(data_offset))
+                .expect("couldn't seek demo file");
+            file.write_all(&[0xAA; 4096])
+                .expect("couldn't write demo file");
+        }
+
+        drop(file);
+
+        let ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        let discarded = match ps2hdd.trim() {
+            Ok(discarded) => discarded,
+            Err(message) => panic!(message),
+        };
+
+        // Holes can only be punched on Linux, and only on host file systems
+        // which support it; elsewhere, nothing is discarded
+        let probe_file_path = "trim_probe.img";
+        let probe = std::fs::File::create(probe_file_path).expect("couldn't create probe file");
+        probe.set_len(4096).expect("couldn't size probe file");
+        let punches_holes = discard(&probe, 0, 4096).expect("couldn't probe for hole punching");
+        drop(probe);
+        std::fs::remove_file(probe_file_path).expect("could not delete probe file");
+
+        if punches_holes {
+            assert_eq!(
+                discarded,
+                128 * 1024 * 1024 - apa::HEADER_SIZE as u64,
+                "unexpected number of bytes discarded"
+            );
+
+            let mut file = std::fs::File::open(demo_file_path).expect("couldn't open demo file");
+            let mut bytes = [0xFFu8; 4096];
+
+            file.seek(std::io::SeekFrom::Start(data_offset))
+                .expect("couldn't seek demo file");
+            file.read_exact(&mut bytes)
+                .expect("couldn't read demo file");
+
+            assert!(
+                bytes.iter().all(|&byte| byte == 0),
+                "discarded space wasn't zeroed"
+            );
+        } else {
+            assert_eq!(discarded, 0, "unexpected number of bytes discarded");
+        }
+
+        assert_eq!(
+            std::fs::metadata(demo_file_path)
+                .expect("couldn't read demo file metadata")
+                .len(),
+            DEMO_FILE_SIZE,
+            "trimming changed the disk size"
+        );
+
+        let mut file = std::fs::File::open(demo_file_path).expect("couldn't open demo file");
+        let bytes = apa::read_header(&mut file, start).expect("couldn't read partition header");
+
+        assert_eq!(bytes, header, "trimming changed the partition header");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn lists_free_regions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for name in &["FIRST", "MIDDLE", "LAST"] {
+            if let Err(message) = ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        let disk_sectors = DEMO_FILE_SIZE / apa::SECTOR_SIZE;
+        let partition_sectors = 128 * 1024 * 1024 / apa::SECTOR_SIZE;
+        let logical_sectors = 8 * partition_sectors;
+
+        assert_eq!(
+            ps2hdd.free_regions(),
+            Ok(vec![(logical_sectors, disk_sectors - logical_sectors)]),
+            "unallocated space wasn't listed as free"
+        );
+
+        let middle_start = match ps2hdd.read_apa_header("MIDDLE") {
+            Ok((header, _)) => header.start as u64,
+            Err(message) => panic!(message),
+        };
+
+        let middle = std::ffi::CString::new("hdd0:MIDDLE").unwrap();
+
+        assert_eq!(
+            unsafe { ps2hdd_sys::iomanx_remove(middle.as_ptr()) },
+            0,
+            "couldn't remove partition"
+        );
+
+        assert_eq!(
+            ps2hdd.free_regions(),
+            Ok(vec![
+                (middle_start, partition_sectors),
+                (logical_sectors, disk_sectors - logical_sectors)
+            ]),
+            "removed partition wasn't listed as free"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn finds_partition_free_gaps() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for name in &["FIRST", "MIDDLE", "LAST"] {
+            if let Err(message) = ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        let middle_start = match ps2hdd.read_apa_header("MIDDLE") {
+            Ok((header, _)) => header.start as u64,
+            Err(message) => panic!(message),
+        };
+
+        let middle = std::ffi::CString::new("hdd0:MIDDLE").unwrap();
+
+        assert_eq!(
+            unsafe { ps2hdd_sys::iomanx_remove(middle.as_ptr()) },
+            0,
+            "couldn't remove partition"
+        );
+
+        let partition_size = 128 * 1024 * 1024;
+        let logical_size = 8 * partition_size;
+
+        assert_eq!(
+            ps2hdd.partition_free_gaps(128),
+            Ok(vec![
+                (middle_start * apa::SECTOR_SIZE, partition_size),
+                (logical_size, DEMO_FILE_SIZE - logical_size)
+            ]),
+            "unexpected gaps for a partition which fits the removed one"
+        );
+
+        assert_eq!(
+            ps2hdd.partition_free_gaps(256),
+            Ok(vec![(logical_size, DEMO_FILE_SIZE - logical_size)]),
+            "the removed partition's space was listed for a larger partition"
+        );
+
+        assert!(
+            ps2hdd.partition_free_gaps(100).is_err(),
+            "an invalid size didn't return an error"
+        );
+
+        if let Err(message) = ps2hdd.create_partition("GAME", FormattablePartitionKind::HDL, 128) {
+            panic!(message);
+        }
+
+        match ps2hdd.read_apa_header("GAME") {
+            Ok((header, _)) => assert_eq!(
+                header.start as u64, middle_start,
+                "new partition didn't reuse the gap"
+            ),
+            Err(message) => panic!(message),
+        }
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn detects_filesystems() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for (name, kind) in &[
+            ("TESTPART", FormattablePartitionKind::PFS),
+            ("WIPED", FormattablePartitionKind::PFS),
+            ("GAME", FormattablePartitionKind::HDL),
+        ] {
+            if let Err(message) = ps2hdd.create_partition(name, *kind, 128) {
+                panic!(message);
+            }
+        }
+
+        assert_eq!(ps2hdd.detect_filesystem("TESTPART"), Ok(DetectedFs::PFS));
+        assert!(DetectedFs::PFS.matches(PartitionKind::PFS));
+
+        // Nothing is written to HDL partitions until a game is installed
+        assert_eq!(
+            ps2hdd.detect_filesystem("GAME"),
+            Ok(DetectedFs::Unformatted)
+        );
+
+        // Wipe out the superblock of a partition which still claims to be PFS
+        let (wiped, _) = match ps2hdd.read_apa_header("WIPED") {
+            Ok(header) => header,
+            Err(message) => panic!(message),
+        };
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(demo_file_path)
+            .expect("could not open demo file");
+
+        file.seek(std::io::SeekFrom::Start(
+            wiped.start as u64 * apa::SECTOR_SIZE + PFS_SUPER_OFFSET,
+        ))
+        .expect("could not seek to superblock");
+        file.write_all(&[0u8; 512])
+            .expect("could not wipe superblock");
+        drop(file);
+
+        assert_eq!(
+            ps2hdd.detect_filesystem("WIPED"),
+            Ok(DetectedFs::Unformatted)
+        );
+        assert_eq!(wiped.kind(), Some(PartitionKind::PFS));
+        assert!(!DetectedFs::Unformatted.matches(PartitionKind::PFS));
+
+        assert!(ps2hdd.detect_filesystem("MISSING").is_err());
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn applies_layouts() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        // 6GiB, less the 640MiB used by the default partitions
+        let oversized = [
+            LayoutEntry::new("A", FormattablePartitionKind::PFS, 2048),
+            LayoutEntry::new("B", FormattablePartitionKind::PFS, 2048),
+            LayoutEntry::new("C", FormattablePartitionKind::HDL, 2048),
+        ];
+
+        assert_eq!(
+            ps2hdd.apply_layout(&oversized),
+            Err(Error::Other("Partition C of 2048MiB doesn't fit; the layout needs 640MiB more than the 5504MiB free"
+                    .to_string()))
+        );
+        assert!(
+            ps2hdd.partition_exists("A") == Ok(false),
+            "partitions were created from an oversized layout"
+        );
+
+        // This fits in the free space, but the 4GiB partition would have to
+        // start at 4GiB to be aligned, and there's only 2GiB after that
+        let misaligned = [
+            LayoutEntry::new("A", FormattablePartitionKind::PFS, 128),
+            LayoutEntry::new("B", FormattablePartitionKind::PFS, 4096),
+        ];
+
+        assert!(
+            ps2hdd.apply_layout(&misaligned).is_err(),
+            "applying an unplaceable layout didn't return an error"
+        );
+        assert!(
+            ps2hdd.partition_exists("A") == Ok(false),
+            "partitions from a failed layout weren't deleted"
+        );
+
+        let fitting = [
+            LayoutEntry::new("A", FormattablePartitionKind::PFS, 128),
+            LayoutEntry::new("B", FormattablePartitionKind::PFS, 256),
+            LayoutEntry::new("C", FormattablePartitionKind::HDL, 512),
+        ];
+
+        if let Err(message) = ps2hdd.apply_layout(&fitting) {
+            panic!(message);
+        }
+
+        for entry in &fitting {
+            assert!(
+                ps2hdd.partition_exists(&entry.name) == Ok(true),
+                "partition {} wasn't created",
+                entry.name
+            );
+        }
+
+        assert_eq!(
+            ps2hdd.apply_layout(&fitting[..1]),
+            Err(Error::Other(
+                "Partition A already exists on the disk".to_string()
+            ))
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[serial(atad_device_path)]
+    fn imports_layout_json() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd
+                .import_layout_json(r#"[{ "name": "HUGE", "kind": "HDL", "size": 8192 }]"#)
+                .is_err(),
+            "an oversized layout was imported"
+        );
+
+        if let Err(message) = ps2hdd.import_layout_json(
+            r#"[
+                { "name": "+OPL", "kind": "PFS", "size": 128 },
+                { "name": "PP.GAME", "kind": "HDL", "size": 256 }
+            ]"#,
+        ) {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.partition_exists("+OPL") == Ok(true),
+            "+OPL wasn't created"
+        );
+        assert!(
+            ps2hdd.partition_exists("PP.GAME") == Ok(true),
+            "PP.GAME wasn't created"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn trims_trailing_free_space() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for name in &["TESTPART", "TAIL"] {
+            if let Err(message) = ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        assert!(
+            PS2HDD::trim_trailing_free_space(demo_file_path).is_err(),
+            "disk was shrunk while open"
+        );
+
+        let mut partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        drop(ps2hdd);
+
+        // Mark the last partition as free space
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let mut header = *apa::read_partition_map(&mut file)
+            .expect("couldn't read partition map")
+            .last()
+            .expect("partition map was empty");
+
+        header[0x48..0x4a].copy_from_slice(&0u16.to_le_bytes());
+        let checksum = apa::checksum(&header);
+        header[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+        let start = apa::ApaHeader::parse(&header).start as u64;
+
+        apa::write_header(&mut file, start, &header).expect("couldn't write partition header");
+
+        drop(file);
+
+        let logical_size = 6 * 128 * 1024 * 1024;
+
+        assert_eq!(
+            PS2HDD::trim_trailing_free_space(demo_file_path),
+            Ok(DEMO_FILE_SIZE - logical_size),
+            "unexpected number of bytes removed"
+        );
+
+        assert_eq!(
+            std::fs::metadata(demo_file_path)
+                .expect("couldn't read demo file metadata")
+                .len(),
+            logical_size,
+            "disk wasn't shrunk to its last partition"
+        );
+
+        assert_eq!(
+            PS2HDD::trim_trailing_free_space(demo_file_path),
+            Ok(0),
+            "shrinking a shrunk disk removed more bytes"
+        );
+
+        let ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        partitions.pop();
+
+        assert_eq!(
+            ps2hdd.list_partitions(),
+            Ok(partitions),
+            "shrunk disk's partitions didn't match"
+        );
+
+        drop(ps2hdd);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn detects_game_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for name in &["PP.GAME", "PP.EMPTY", "PFSPART"] {
+            if let Err(message) = ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        drop(ps2hdd);
+
+        // Turn the first two partitions into HDL partitions, and give only
+        // the first of them game information
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let headers = apa::read_partition_map(&mut file).expect("couldn't read partition map");
+
+        for mut header in headers {
+            let parsed = apa::ApaHeader::parse(&header);
+
+            if parsed.id != "PP.GAME" && parsed.id != "PP.EMPTY" {
+                continue;
+            }
+
+            header[0x48..0x4a].copy_from_slice(&0x1337u16.to_le_bytes());
+            let checksum = apa::checksum(&header);
+            header[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+            apa::write_header(&mut file, parsed.start as u64, &header)
+                .expect("couldn't write partition header");
+
+            if parsed.id == "PP.GAME" {
+                file.seek(std::io::SeekFrom::Start(
+                    parsed.start as u64 * apa::SECTOR_SIZE
+                        + ps2hdd_sys::HDL_GAME_DATA_OFFSET as u64,
+                ))
+                .expect("couldn't seek to game information");
+
+                file.write_all(&ps2hdd_sys::HDL_INFO_MAGIC.to_le_bytes())
+                    .expect("couldn't write game information");
+            }
+        }
+
+        drop(file);
+
+        let ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        let games: Vec<&str> = partitions
+            .iter()
+            .filter(|partition| {
+                partition
+                    .contains_game(&ps2hdd)
+                    .expect("couldn't check for game")
+            })
+            .map(|partition| partition.name.as_str())
+            .collect();
+
+        assert_eq!(games, vec!["PP.GAME"], "unexpected game partitions");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn repairs_journals() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        assert!(
+            PS2HDD::pending_journal_entries(demo_file_path).is_err(),
+            "Inspected a journal while mounted"
+        );
+
+        drop(ps2hdd);
+
+        assert_eq!(PS2HDD::pending_journal_entries(demo_file_path), Ok(0));
+        assert_eq!(
+            PS2HDD::repair_journal(demo_file_path, apa::JournalAction::Replay),
+            Ok(apa::JournalRepair::Clean)
+        );
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let header = *apa::read_partition_map(&mut file)
+            .expect("couldn't read partition map")
+            .last()
+            .expect("partition map was empty");
+        let start = apa::ApaHeader::parse(&header).start;
+
+        // Journal a version of the header with a different type, as if the
+        // partition's type was being changed when the disk was interrupted
+        let mut journaled = header;
+        journaled[0x48..0x4a].copy_from_slice(&0x4242u16.to_le_bytes());
+        let checksum = apa::checksum(&journaled);
+        journaled[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+        let mut journal = [0u8; 512];
+        journal[0..4].copy_from_slice(b"APAL");
+        journal[4..8].copy_from_slice(&1u32.to_le_bytes());
+        journal[8..12].copy_from_slice(&start.to_le_bytes());
+
+        let write_journal = |file: &mut std::fs::File| {
+            file.seek(std::io::SeekFrom::Start(8 * 512))
+                .expect("couldn't seek to journal");
+            file.write_all(&journal).expect("couldn't write journal");
+            apa::write_header(file, 10, &journaled).expect("couldn't write journal header");
+        };
+
+        write_journal(&mut file);
+
+        assert_eq!(PS2HDD::pending_journal_entries(demo_file_path), Ok(1));
+        assert_eq!(
+            PS2HDD::repair_journal(demo_file_path, apa::JournalAction::Discard),
+            Ok(apa::JournalRepair::Discarded(1))
+        );
+        assert_eq!(PS2HDD::pending_journal_entries(demo_file_path), Ok(0));
+        assert_eq!(
+            apa::read_header(&mut file, start as u64).expect("couldn't read header"),
+            header,
+            "Discarding the journal changed the partition header"
+        );
+
+        write_journal(&mut file);
+
+        assert_eq!(
+            PS2HDD::repair_journal(demo_file_path, apa::JournalAction::Replay),
+            Ok(apa::JournalRepair::Replayed(1))
+        );
+        assert_eq!(PS2HDD::pending_journal_entries(demo_file_path), Ok(0));
+        assert_eq!(
+            apa::read_header(&mut file, start as u64).expect("couldn't read header"),
+            journaled,
+            "Replaying the journal didn't restore the partition header"
+        );
+
+        drop(file);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn opens_with_warnings() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        drop(ps2hdd);
+
+        match PS2HDD::open_with_warnings(demo_file_path) {
+            Ok((_, warnings)) => assert_eq!(warnings, vec![]),
+            Err(message) => panic!(message),
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        let header = *apa::read_partition_map(&mut file)
+            .expect("couldn't read partition map")
+            .last()
+            .expect("partition map was empty");
+        let start = apa::ApaHeader::parse(&header).start;
+
+        // Journal the header unchanged, as if the disk was interrupted part
+        // way through changing it
+        let mut journal = [0u8; 512];
+        journal[0..4].copy_from_slice(b"APAL");
+        journal[4..8].copy_from_slice(&1u32.to_le_bytes());
+        journal[8..12].copy_from_slice(&start.to_le_bytes());
+
+        file.seek(std::io::SeekFrom::Start(8 * 512))
+            .expect("couldn't seek to journal");
+        file.write_all(&journal).expect("couldn't write journal");
+        apa::write_header(&mut file, 10, &header).expect("couldn't write journal header");
+        drop(file);
+
+        let (ps2hdd, warnings) = match PS2HDD::open_with_warnings(demo_file_path) {
+            Ok(result) => result,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(warnings, vec![OpenWarning::PendingJournal(1)]);
+        assert_eq!(
+            warnings[0].to_string(),
+            "The disk wasn't cleanly unmounted; 1 partition header(s) were restored from its journal"
+        );
+
+        assert!(
+            ps2hdd.partition_exists("__common") == Ok(true),
+            "the disk wasn't usable after opening"
+        );
+
+        drop(ps2hdd);
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn verifies_writable() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(ps2hdd.verify_writable(), Ok(()));
+
+        let permissions = std::fs::metadata(demo_file_path)
+            .expect("couldn't read demo file metadata")
+            .permissions();
+
+        let mut read_only = permissions.clone();
+        read_only.set_readonly(true);
+        std::fs::set_permissions(demo_file_path, read_only)
+            .expect("couldn't make demo file read-only");
+
+        assert_eq!(
+            ps2hdd.verify_writable(),
+            Err(Error::Other("hdd.img: Disk is read-only".to_string()))
+        );
+
+        std::fs::set_permissions(demo_file_path, permissions)
+            .expect("couldn't make demo file writable");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn reads_leading_sectors() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        let sectors = match ps2hdd.read_leading_sectors(4) {
+            Ok(sectors) => sectors,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(sectors.len(), 4 * 512, "unexpected number of bytes");
+        assert_eq!(
+            sectors[4..8],
+            apa::APA_MAGIC.to_le_bytes(),
+            "APA magic missing from __mbr header"
+        );
+
+        assert_eq!(ps2hdd.read_leading_sectors(0), Ok(Vec::new()));
+        assert_eq!(
+            ps2hdd
+                .read_leading_sectors(MAX_LEADING_SECTORS)
+                .map(|sectors| sectors.len() as u64),
+            Ok(MAX_LEADING_SECTORS * 512),
+            "couldn't read the whole header area"
+        );
+        assert_eq!(
+            ps2hdd.read_leading_sectors(MAX_LEADING_SECTORS + 1),
+            Err(Error::Other(
+                "Cannot read more than 8192 leading sectors".to_string()
+            )),
+            "reading past the header area didn't return an error"
+        );
+        assert!(
+            ps2hdd
+                .read_leading_sectors(DEMO_FILE_SIZE / 512 + 1)
+                .is_err(),
+            "reading past the end of the disk didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn counts_sub_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        // The largest partition on this disk is 128MiB, so this is split into
+        // a main partition and three sub-partitions
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 512)
+        {
+            panic!(message);
+        }
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(partitions) => partitions,
+            Err(message) => panic!(message),
+        };
+
+        let count = |predicate: &dyn Fn(&PartEntry) -> bool| match partitions
+            .iter()
+            .find(|partition| predicate(partition))
+        {
+            Some(partition) => partition.sub_partition_count(&ps2hdd),
+            None => panic!("partition not found"),
+        };
+
+        assert_eq!(
+            count(&|partition| partition.name == "TESTPART" && partition.flags.is_main()),
+            Ok(3)
+        );
+        assert_eq!(
+            count(&|partition| partition.name == "TESTPART" && partition.flags.is_sub()),
+            Ok(0)
+        );
+        assert_eq!(count(&|partition| partition.name == "__mbr"), Ok(0));
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn reads_apa_headers() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 256)
+        {
+            panic!(message);
+        }
+
+        let (mbr, mbr_bytes) = match ps2hdd.read_apa_header("__mbr") {
+            Ok(header) => header,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(mbr_bytes.len(), apa::HEADER_SIZE, "unexpected header size");
+        assert_eq!(mbr.magic, apa::APA_MAGIC, "unexpected magic");
+        assert_eq!(mbr.start, 0, "unexpected __mbr start sector");
+        assert_eq!(
+            mbr.kind(),
+            Some(PartitionKind::MBR),
+            "unexpected __mbr kind"
+        );
+
+        let (testpart, _) = match ps2hdd.read_apa_header("TESTPART") {
+            Ok(header) => header,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(testpart.id, "TESTPART", "unexpected partition name");
+        assert_eq!(testpart.kind(), Some(PartitionKind::PFS), "unexpected kind");
+        assert!(testpart.flags.is_main(), "expected a main partition");
+        assert_eq!(testpart.nsub, 1, "expected one sub-partition");
+
+        assert_eq!(
+            ps2hdd.detect_overlaps(),
+            Ok(vec![]),
+            "unexpected partition conflicts"
+        );
+        assert_eq!(
+            (testpart.length + testpart.subs[0].1) as u64 * apa::SECTOR_SIZE,
+            256 * 1024 * 1024,
+            "partition size doesn't match its sectors"
+        );
+
+        assert_eq!(
+            ps2hdd.read_apa_header("NOTAPART").unwrap_err(),
+            Error::NotFound {
+                name: "NOTAPART".to_string(),
+                kind: "partition"
+            },
+            "Reading a missing partition didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn checks_partitions_exist() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.partition_exists("__system") == Ok(true),
+            "Default partition reported missing"
+        );
+
+        assert!(
+            ps2hdd.partition_exists("TESTPART") == Ok(false),
+            "Missing partition reported as existing"
+        );
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.partition_exists("TESTPART") == Ok(true),
+            "New partition reported missing"
+        );
+
+        // Failures other than the partition not existing are reported
+        assert!(
+            ps2hdd.partition_exists("BAD\0NAME").is_err(),
+            "Unqueryable partition reported as missing"
+        );
+
+        // The driver rejects a name with nothing before the first comma
+        // outright, rather than looking for it
+        assert_eq!(
+            ps2hdd.partition_exists(",NONAME"),
+            Err(Error::Io {
+                errno: -libc::EINVAL,
+                context: "failed to stat partition".to_string()
+            }),
+            "Invalid partition name reported as missing"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn counts_sub_partitions_via_ioctl2() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 256)
+        {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd.partition_ioctl2("__system", Ioctl2Command::PartitionCountSubs, &[], &mut []),
+            Ok(0),
+            "unexpected sub-partition count"
+        );
+
+        assert_eq!(
+            ps2hdd.partition_ioctl2("TESTPART", Ioctl2Command::PartitionCountSubs, &[], &mut []),
+            Ok(1),
+            "unexpected sub-partition count"
+        );
+
+        assert!(
+            ps2hdd
+                .partition_ioctl2("NOTAPART", Ioctl2Command::PartitionCountSubs, &[], &mut [])
+                .is_err(),
+            "ioctl2 on a missing partition didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    fn estimates_hdl_partition_sizes() {
+        assert_eq!(hdl_partition_size(0), Ok(128));
+        assert_eq!(hdl_partition_size(127 * 1024 * 1024), Ok(128));
+        // The game information pushes an exactly 128MiB game over the edge
+        assert_eq!(hdl_partition_size(128 * 1024 * 1024), Ok(256));
+        assert_eq!(hdl_partition_size(700 * 1024 * 1024), Ok(1024));
+        // A full single-layer DVD
+        assert_eq!(hdl_partition_size(4_700_372_992), Ok(8192));
+        assert!(hdl_partition_size(32 * 1024 * 1024 * 1024).is_err());
+
+        let iso_path = "estimate.iso";
+
+        std::fs::File::create(iso_path)
+            .and_then(|file| file.set_len(700 * 1024 * 1024))
+            .expect("couldn't create ISO file");
+
+        assert_eq!(PS2HDD::estimate_install_size(iso_path), Ok(1024));
+
+        std::fs::remove_file(iso_path).expect("could not delete ISO file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn creates_disks_from_templates() {
+        let template_file_path = "hdd.img";
+        let demo_file_path = "hdd2.img";
+
+        let template = match PS2HDD::create(template_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = template.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) = template.create_partition("TESTA", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        if let Err(message) = template.create_partition("TESTB", FormattablePartitionKind::PFS, 256)
+        {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            template.create_partition("__.POPS", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let template_partitions = match template.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        let ps2hdd = match PS2HDD::new_from_template(template, demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            ps2hdd.list_partitions(),
+            Ok(template_partitions),
+            "partition layout didn't match the template"
+        );
+
+        assert_eq!(
+            std::fs::metadata(demo_file_path)
+                .expect("couldn't read demo file metadata")
+                .len(),
+            DEMO_FILE_SIZE,
+            "disk size didn't match the template"
+        );
+
+        drop(ps2hdd);
+
+        std::fs::remove_file(template_file_path).expect("could not delete template file");
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn diffs_images() {
+        let earlier_file_path = "hdd.img";
+        let later_file_path = "hdd2.img";
+
+        let make_disk = |path: &str, partition_name: &str, files: &[(&str, &[u8])]| {
+            let mut ps2hdd = match PS2HDD::create(path, DEMO_FILE_SIZE) {
+                Ok(ps2hdd) => ps2hdd,
+                Err(message) => panic!(message),
+            };
+
+            if let Err(message) = ps2hdd.initialize() {
+                panic!(message);
+            }
+
+            for name in &["KEPT", partition_name] {
+                if let Err(message) =
+                    ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+                {
+                    panic!(message);
+                }
+            }
+
+            let pfs = match ps2hdd.mount_pfs("KEPT") {
+                Ok(pfs) => pfs,
+                Err(message) => panic!(message),
+            };
+
+            for (file_path, contents) in files {
+                pfs.write_file(file_path, contents)
+                    .expect("Could not write file");
+            }
+
+            if let Err(message) = ps2hdd.umount_pfs() {
+                panic!(message);
+            }
+        };
+
+        make_disk(
+            earlier_file_path,
+            "REMOVED",
+            &[
+                ("same.txt", b"same"),
+                ("old.txt", b"old"),
+                ("grown.txt", b"a"),
+            ],
+        );
+        make_disk(
+            later_file_path,
+            "ADDED",
+            &[
+                ("same.txt", b"same"),
+                ("new.txt", b"new"),
+                ("grown.txt", b"ab"),
+            ],
+        );
+
+        let diff = match PS2HDD::diff_images(earlier_file_path, later_file_path, true) {
+            Ok(diff) => diff,
+            Err(message) => panic!(message),
+        };
+
+        let key = |path: &str| ("KEPT".to_string(), PathBuf::from(path));
+
+        assert_eq!(diff.added_partitions, vec!["ADDED".to_string()]);
+        assert_eq!(diff.removed_partitions, vec!["REMOVED".to_string()]);
+        assert!(diff.changed_partitions.is_empty());
+        assert_eq!(diff.added_files, vec![key("/new.txt")]);
+        assert_eq!(diff.removed_files, vec![key("/old.txt")]);
+
+        // same.txt is written to each disk at a different time, so it may be
+        // listed as changed for its modification time alone; only compare
+        // what was written
+        let rewritten: Vec<_> = diff
+            .changed_files
+            .iter()
+            .filter(|(_, earlier, later)| {
+                earlier.len != later.len || earlier.file_type != later.file_type
+            })
+            .collect();
+
+        assert_eq!(rewritten.len(), 1);
+        assert_eq!(rewritten[0].0, key("/grown.txt"));
+        assert_eq!(rewritten[0].1.len, 1);
+        assert_eq!(rewritten[0].2.len, 2);
+        assert!(
+            diff.changed_files
+                .iter()
+                .all(|(path, ..)| *path == key("/grown.txt") || *path == key("/same.txt")),
+            "unchanged files were listed as changed"
+        );
+
+        let diff = match PS2HDD::diff_images(earlier_file_path, later_file_path, false) {
+            Ok(diff) => diff,
+            Err(message) => panic!(message),
+        };
+
+        assert!(
+            diff.added_files.is_empty() && diff.changed_files.is_empty(),
+            "files were compared when they weren't requested"
+        );
+
+        std::fs::remove_file(earlier_file_path).expect("could not delete demo file");
+        std::fs::remove_file(later_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn rejects_oversized_disks() {
+        let demo_file_path = "hdd.img";
+
+        assert_eq!(
+            PS2HDD::create(demo_file_path, MAX_DISK_SIZE + 1).unwrap_err(),
+            Error::Other("Disk size of 2199023255553 bytes is larger than the 2199023255552 bytes APA can address".to_string()),
+            "Creating an oversized disk didn't return an error"
+        );
+
+        assert!(
+            !Path::new(demo_file_path).exists(),
+            "Creating an oversized disk left a file behind"
+        );
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn deletes_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        let main_partition_names = |ps2hdd: &PS2HDD| match ps2hdd.list_partitions() {
+            Ok(partitions) => partitions
+                .into_iter()
+                .filter(|partition| partition.kind.is_some() && partition.flags.is_main())
+                .map(|partition| partition.name)
+                .collect::<Vec<_>>(),
+            Err(message) => panic!(message),
+        };
+
+        let defaults = main_partition_names(&ps2hdd);
+        assert_eq!(
+            defaults,
+            vec!["__mbr", "__net", "__system", "__sysconf", "__common"]
+        );
+
+        // Large enough to have sub-partitions, which go along with it
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 512)
+        {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.mount_pfs("TESTPART") {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd.delete_partition("TESTPART"),
+            Err(Error::Other(
+                "TESTPART is mounted, and can't be deleted".to_string()
+            ))
+        );
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.delete_partition("TESTPART") {
+            panic!(message);
+        }
+
+        assert_eq!(main_partition_names(&ps2hdd), defaults);
+        assert!(
+            ps2hdd.partition_exists("TESTPART") == Ok(false),
+            "deleted partition still exists"
+        );
+
+        for name in &defaults {
+            assert_eq!(
+                ps2hdd.delete_partition(name),
+                Err(Error::Other(format!(
+                    "{} is a reserved system partition, and can't be deleted",
+                    name
+                )))
+            );
+        }
+
+        assert!(
+            ps2hdd.delete_partition("TESTPART").is_err(),
+            "deleting a missing partition didn't return an error"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn checks_default_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            ps2hdd.defaults_present(),
+            Ok(vec!["__mbr", "__net", "__system", "__sysconf", "__common"]),
+            "uninitialised disk had default partitions"
+        );
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd.defaults_present(),
+            Ok(vec![]),
+            "initialised disk was missing default partitions"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn creates_sparse_images() {
+        let demo_file_path = "hdd.img";
+
+        let started = std::time::Instant::now();
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        // Writing out 6GiB would take far longer than this
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(5),
+            "Creating the image took {:?}",
+            started.elapsed()
+        );
+
+        drop(ps2hdd);
+
+        let metadata = std::fs::metadata(demo_file_path).expect("could not stat demo file");
+        assert_eq!(metadata.len(), DEMO_FILE_SIZE);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            assert!(
+                metadata.blocks() * 512 < DEMO_FILE_SIZE / 2,
+                "Image wasn't sparse; {} bytes allocated",
+                metadata.blocks() * 512
+            );
+        }
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn creates_preallocated_images() {
+        let demo_file_path = "hdd.img";
+        let size = 256 * 1024 * 1024;
+
+        let ps2hdd = match PS2HDD::create_with(
+            demo_file_path,
+            size,
+            &CreateOptions::new().preallocate(true),
+        ) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        drop(ps2hdd);
+
+        let metadata = std::fs::metadata(demo_file_path).expect("could not stat demo file");
+        assert_eq!(metadata.len(), size);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            assert!(
+                metadata.blocks() * 512 >= size,
+                "Image wasn't preallocated; {} bytes allocated",
+                metadata.blocks() * 512
+            );
+        }
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn reports_initialize_failures() {
+        let demo_file_path = "hdd.img";
+
+        // Only large enough for __mbr and __net
+        let ps2hdd = match PS2HDD::create(demo_file_path, 256 * 1024 * 1024) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        let message = ps2hdd
+            .initialize()
+            .expect_err("Initializing a too-small disk didn't return an error")
+            .to_string();
+
+        assert!(
+            message.starts_with("HDD formatting failed"),
+            "unexpected message: {}",
+            message
+        );
+        assert!(
+            message.contains("could not create default partition"),
+            "failed partition wasn't named: {}",
+            message
+        );
+        assert!(
+            message.contains("__common"),
+            "missing partitions weren't listed: {}",
+            message
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn initializes_with_options() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            ps2hdd.initialize_with(&InitializeOptions::new().sysconf(Some(100))),
+            Err(Error::Other(
+                "__sysconf: Partition size must be a power of 2 from 128MiB to 32768MiB"
+                    .to_string()
+            )),
+            "invalid partition size was accepted"
+        );
+
+        assert!(
+            !apa::has_apa_magic(demo_file_path),
+            "disk was initialised despite an invalid partition size"
+        );
+
+        let options = InitializeOptions::new().net(None).system(Some(256));
+
+        let created = match ps2hdd.initialize_with(&options) {
+            Ok(created) => created,
+            Err(message) => panic!(message),
+        };
+
+        let mut created: Vec<String> = created
+            .into_iter()
+            .map(|partition| partition.name)
+            .collect();
+        created.sort();
+
+        assert_eq!(
+            created,
+            vec!["__common", "__mbr", "__sysconf", "__system"],
+            "unexpected default partitions"
+        );
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            partitions
+                .iter()
+                .filter(|partition| partition.name == "__system")
+                .map(|partition| partition.size)
+                .sum::<u64>(),
+            256 * 1024 * 1024,
+            "__system wasn't resized"
+        );
+
+        assert_eq!(
+            ps2hdd.defaults_present(),
+            Ok(vec!["__net"]),
+            "omitted partition was created"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn reserves_space() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let extra = 32 * 1024 * 1024;
+
+        if let Err(message) = ps2hdd.reserve_space("TESTPART", extra) {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.reserve_space("TESTPART", 256 * 1024 * 1024).is_err(),
+            "reserving more space than the partition holds succeeded"
+        );
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            pfs.list_dir("/").map(|entries| entries.len()),
+            Ok(0),
+            "placeholder file was left behind"
+        );
+
+        pfs.write_file("reserved.bin", &vec![0x42u8; extra as usize])
+            .expect("Could not write the reserved amount");
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn mounts_by_index() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 256)
+        {
+            panic!(message);
+        }
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        let index = partitions
+            .iter()
+            .position(|partition| partition.name == "TESTPART")
+            .expect("TESTPART wasn't listed");
+
+        match ps2hdd.mount_pfs_by_index(index) {
+            Ok(pfs) => assert_eq!(pfs.get_partition_name(), "TESTPART"),
+            Err(message) => panic!(message),
+        }
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        // A 256MiB partition on this disk is a main partition plus one
+        // sub-partition, listed immediately after it
+        assert_eq!(
+            ps2hdd.mount_pfs_by_index(index + 1).unwrap_err(),
+            Error::Other(format!(
+                "Partition index {} is a sub-partition of TESTPART",
+                index + 1
+            ))
+        );
+
+        assert_eq!(
+            ps2hdd.mount_pfs_by_index(100).unwrap_err(),
+            Error::Other(format!(
+                "Partition index 100 is out of range; the disk has {} partitions",
+                partitions.len()
+            ))
+        );
+
+        drop(ps2hdd);
+
+        // Give TESTPART, and its sub-partition, a name which isn't UTF-8
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(demo_file_path)
+            .expect("couldn't open demo file");
+
+        for mut header in apa::read_partition_map(&mut file).expect("couldn't read partition map") {
+            if apa::raw_id(&header) != b"TESTPART" {
+                continue;
+            }
+
+            header[0x10..0x18].copy_from_slice(b"BAD\xffNAME");
+            let checksum = apa::checksum(&header);
+            header[0x00..0x04].copy_from_slice(&checksum.to_le_bytes());
+
+            let start = apa::ApaHeader::parse(&header).start as u64;
+            apa::write_header(&mut file, start, &header).expect("couldn't write partition header");
+        }
+
+        drop(file);
+
+        let mut ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        match ps2hdd.mount_pfs_by_index(index) {
+            Ok(pfs) => {
+                assert_eq!(pfs.get_partition_name(), "BAD\u{fffd}NAME");
+                assert!(
+                    pfs.list_dir("/").is_ok(),
+                    "Couldn't list partition mounted by index"
+                );
+            }
+            Err(message) => panic!(message),
+        }
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn closes_and_reopens() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs
+                .write_file("kept.txt", b"kept")
+                .expect("Could not write kept.txt"),
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.close_and_reopen() {
+            panic!(message);
+        }
+
+        assert!(
+            !ps2hdd.is_mounted_anywhere(),
+            "partition was still mounted after reopening"
+        );
+        assert!(
+            PS2HDD::open(demo_file_path).is_err(),
+            "another instance could be opened after reopening"
+        );
+
+        assert!(
+            ps2hdd.partition_exists("TESTPART") == Ok(true),
+            "partition missing after reopening"
+        );
+
+        match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => assert_eq!(pfs.read_file("kept.txt"), Ok(b"kept".to_vec())),
+            Err(message) => panic!(message),
+        };
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn keeps_mount_points_apart() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.mount_pfs("__common") {
+            panic!(message);
+        }
+
+        assert_eq!(
+            ps2hdd.mount("pfs0:", "__sysconf"),
+            Err(Error::Other("pfs0: is already in use".to_string()))
+        );
+
+        match ps2hdd.mounted_driver() {
+            Some(driver) => driver
+                .write_file("still_mounted.txt", b"yes")
+                .expect("__common was unmounted"),
+            None => panic!("__common was unmounted"),
+        };
+
+        // The HDLFS driver isn't initialised, so an HDLFS partition can't
+        // really be mounted alongside; only the bookkeeping is tested here
+        ps2hdd.hdlfs = Some(HDLFS {
+            partition_name: "__sysconf".to_string(),
+        });
+
+        assert_eq!(
+            ps2hdd.mount("hdl0:", "__net"),
+            Err(Error::Other("hdl0: is already in use".to_string()))
+        );
+        assert_eq!(
+            ps2hdd.mount_hdlfs("__net").err(),
+            Some(Error::Other(
+                "An HDLFS partition is already mounted".to_string()
+            ))
+        );
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.mount_pfs("__net") {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.hdlfs.is_some(),
+            "mounting PFS disturbed the HDLFS mount"
+        );
+
+        ps2hdd.hdlfs = None;
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn umounts_all() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        assert!(!ps2hdd.is_mounted_anywhere(), "nothing should be mounted");
+
+        if let Err(message) = ps2hdd.umount_all() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.mount_pfs("TESTPART") {
+            panic!(message);
+        }
+
+        assert!(ps2hdd.is_mounted_anywhere(), "PFS should be mounted");
+
+        if let Err(message) = ps2hdd.umount_all() {
+            panic!(message);
+        }
+
+        assert!(!ps2hdd.is_mounted_anywhere(), "PFS should be unmounted");
+
+        assert!(
+            ps2hdd.mount_pfs("TESTPART").is_ok(),
+            "PFS couldn't be mounted again after unmounting everything"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn returns_mounted_driver() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.mounted_driver().is_none(),
+            "driver returned with nothing mounted"
+        );
+
+        if let Err(message) = ps2hdd.mount_pfs("TESTPART") {
+            panic!(message);
+        }
+
+        let driver = ps2hdd.mounted_driver().expect("no driver was returned");
+
+        assert_eq!(driver.get_device_root(), "pfs0:");
+        assert_eq!(driver.get_partition_name(), "TESTPART");
+
+        driver
+            .write_file("file.txt", b"contents")
+            .expect("Could not write file");
+        assert_eq!(driver.read_file("file.txt"), Ok(b"contents".to_vec()));
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        assert!(
+            ps2hdd.mounted_driver().is_none(),
+            "driver returned after unmounting"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn records_last_error() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("TESTPART") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let error = pfs
+            .read_file("missing.txt")
+            .expect_err("Reading a missing file didn't return an error");
+
+        assert_eq!(
+            error,
+            Error::NotFound {
+                name: "missing.txt".to_string(),
+                kind: "file or directory"
+            }
+        );
+
+        let last_error = ps2hdd.last_error().expect("No error was recorded");
+
+        assert_eq!(last_error.operation(), "failed to open file");
+        assert_eq!(last_error.code(), -libc::ENOENT);
+        assert_eq!(
+            last_error.to_string(),
+            "failed to open file: -2, No such file or directory"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn lists_partitions_sorted() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        for name in &["ZETA", "ALPHA", "MU"] {
+            if let Err(message) = ps2hdd.create_partition(name, FormattablePartitionKind::PFS, 128)
+            {
+                panic!(message);
+            }
+        }
+
+        let names = |partitions: Vec<PartEntry>| -> Vec<String> {
+            partitions
+                .into_iter()
+                .filter(|partition| partition.kind.is_some())
+                .map(|partition| partition.name)
+                .collect()
+        };
+
+        assert_eq!(
+            names(ps2hdd.list_partitions().expect("Could not list partitions")),
+            vec![
+                "__mbr",
+                "__net",
+                "__system",
+                "__sysconf",
+                "__common",
+                "ZETA",
+                "ALPHA",
+                "MU"
+            ],
+            "unexpected partition map order"
+        );
+
+        assert_eq!(
+            names(
+                ps2hdd
+                    .list_partitions_sorted()
+                    .expect("Could not list partitions")
+            ),
+            vec![
+                "ALPHA",
+                "MU",
+                "ZETA",
+                "__common",
+                "__mbr",
+                "__net",
+                "__sysconf",
+                "__system"
+            ],
+            "unexpected sorted partition order"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn clones_pfs_partitions() {
+        let demo_file_path = "hdd.img";
+
+        let mut ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.create_partition("SRC", FormattablePartitionKind::PFS, 128) {
+            panic!(message);
+        }
+
+        let pfs = match ps2hdd.mount_pfs("SRC") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        pfs.create_dir_all("a/b").expect("Could not create path");
+        pfs.write_file("a/one.txt", b"one")
+            .expect("Could not write file");
+        pfs.write_file("a/b/two.txt", b"two")
+            .expect("Could not write file");
+        pfs.write_file("a/empty.txt", b"")
+            .expect("Could not write file");
+
+        // Larger than the buffer, so it's copied across several mounts
+        let big: Vec<u8> = (0..2 * CLONE_BUFFER_SIZE + 12345)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        pfs.write_file("a/big.bin", &big)
+            .expect("Could not write file");
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        if let Err(message) = ps2hdd.clone_partition("SRC", "DST") {
+            panic!(message);
+        }
+
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
+
+        assert_eq!(
+            partitions.last(),
+            Some(&PartEntry {
+                name: "DST".to_string(),
+                kind: Some(PartitionKind::PFS),
+                flags: PartitionFlags::empty(),
+                size: 128 * 1024 * 1024,
+                start_sector: 1572864
+            }),
+            "unexpected cloned partition entry"
+        );
+
+        let pfs = match ps2hdd.mount_pfs("DST") {
+            Ok(pfs) => pfs,
+            Err(message) => panic!(message),
+        };
+
+        let mut names: Vec<_> = pfs
+            .walk_dir("/")
+            .expect("Could not walk directory")
+            .iter()
+            .map(|entry| entry.root().join(entry.file_name()))
+            .collect();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("/a"),
+                PathBuf::from("/a/b"),
+                PathBuf::from("/a/b/two.txt"),
+                PathBuf::from("/a/big.bin"),
+                PathBuf::from("/a/empty.txt"),
+                PathBuf::from("/a/one.txt")
+            ],
+            "unexpected cloned directory tree"
+        );
+
+        assert_eq!(
+            pfs.read_file("a/b/two.txt").expect("Could not read file"),
+            b"two",
+            "unexpected cloned file contents"
+        );
+        assert_eq!(
+            pfs.read_file("a/empty.txt").expect("Could not read file"),
+            b"",
+            "unexpected cloned file contents"
+        );
+        assert!(
+            pfs.read_file("a/big.bin").expect("Could not read file") == big,
+            "unexpected cloned file contents"
+        );
+
+        if let Err(message) = ps2hdd.umount_pfs() {
+            panic!(message);
+        }
+
+        // A failed clone leaves an existing destination alone
+        assert!(
+            ps2hdd.clone_partition("SRC", "DST").is_err(),
+            "cloned over an existing partition"
+        );
+        assert!(
+            ps2hdd.partition_exists("DST") == Ok(true),
+            "existing partition deleted by a failed clone"
+        );
+
+        if let Err(message) = ps2hdd.create_partition("GAME", FormattablePartitionKind::HDL, 128) {
+            panic!(message);
         }
 
-        self.pfs = None;
+        assert_eq!(
+            ps2hdd.clone_partition("GAME", "GAME2"),
+            Err(Error::Other("Cloning HDL partitions is not supported, as their games are located by absolute sector"
+                    .to_string()))
+        );
+        assert!(
+            ps2hdd.partition_exists("GAME2") == Ok(false),
+            "partition created by a rejected clone"
+        );
 
-        Ok(())
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
-    /// Acquire a file I/O object bound to the specified `hdlfs` partition.
-    pub fn mount_hdlfs(&mut self, partition_name: &str) -> Result<&HDLFS, String> {
-        if self.hdlfs.is_some() {
-            return Err("A PFS partition is already mounted".to_string());
-        }
-
-        self.mount("hdl0:", partition_name)?;
+    #[test]
+    #[serial(atad_device_path)]
+    fn detects_sub_partitions() {
+        let demo_file_path = "hdd.img";
 
-        self.hdlfs = Some(HDLFS {
-            partition_name: partition_name.to_string(),
-        });
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
 
-        self.hdlfs
-            .as_ref()
-            .ok_or_else(|| "Failed to retrieve reference".to_string())
-    }
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
 
-    /// Unmount the currently-mounted HDLFS device.
-    ///
-    /// Should not generally be called by user code.
-    /// Used internally to keep track of state.
-    pub fn umount_hdlfs(&mut self) -> Result<(), String> {
-        if self.hdlfs.is_none() {
-            return Err("No HDLFS partition is mounted; nothing to unmount".to_string());
+        // The largest single partition on a disk this size is 128MiB, so
+        // this must be split into a main partition and a sub-partition
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 256)
+        {
+            panic!(message);
         }
 
-        self.hdlfs = None;
+        let partitions = match ps2hdd.list_partitions() {
+            Ok(list) => list,
+            Err(message) => panic!(message),
+        };
 
-        Ok(())
+        assert!(
+            partitions
+                .iter()
+                .filter(|partition| partition.name != "TESTPART")
+                .all(|partition| partition.flags.is_main()),
+            "unexpected sub-partition in default partitions"
+        );
+
+        assert!(
+            partitions.iter().any(|partition| partition.flags.is_sub()),
+            "no sub-partition found"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
-    fn mount(&self, mount_point: &str, partition_name: &str) -> Result<(), String> {
-        let mount_path = match std::ffi::CString::new(mount_point) {
-            Ok(mount) => mount,
-            Err(error) => return Err(error.to_string()),
+    #[test]
+    #[serial(atad_device_path)]
+    fn backs_up_and_restores_partition_tables() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
         };
 
-        // TODO: Ensure path does not contain invalid characters?
+        if let Err(message) = ps2hdd.initialize() {
+            panic!(message);
+        }
 
-        let partition = match std::ffi::CString::new(format!("hdd0:{}", partition_name)) {
-            Ok(partition_path) => partition_path,
-            Err(error) => return Err(error.to_string()),
+        let table = match ps2hdd.backup_partition_table() {
+            Ok(table) => table,
+            Err(message) => panic!(message),
         };
 
-        ok_on_zero_or_strerror(
-            unsafe {
-                ps2hdd_sys::iomanx_mount(
-                    mount_path.as_ptr(),
-                    partition.as_ptr(),
-                    0,
-                    std::ptr::null_mut(),
-                    0,
-                )
-            },
-            "Mounting failed",
-        )?;
+        assert_eq!(table.len(), 5 * 1024, "unexpected partition table size");
 
-        Ok(())
-    }
-}
+        assert_eq!(
+            PS2HDD::restore_partition_table(demo_file_path, &table).unwrap_err(),
+            Error::Other(
+                "Partition tables may not be restored while a PS2HDD instance is mounted"
+                    .to_string()
+            ),
+            "Restoring while mounted didn't return an error"
+        );
 
-impl Drop for PS2HDD {
-    fn drop(&mut self) {
-        let was_active = IS_DEVICE_ACTIVE.swap(false, std::sync::atomic::Ordering::Relaxed);
-        assert!(was_active, "PS2HDD dropped while not active");
-        unsafe { ps2hdd_sys::atad_close() };
-    }
-}
+        if let Err(message) =
+            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
+        {
+            panic!(message);
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serial_test::serial;
+        drop(ps2hdd);
 
-    // A disk image needs to be at least 6GB in size for APA to work
-    // more is better, and 20GB is the "normal" minimum, but this is
-    // just enough  to fit any of the minimum-size 128MB partitions in
-    static DEMO_FILE_SIZE: u64 = 6 * 1024 * 1024 * 1024;
+        assert!(
+            PS2HDD::restore_partition_table(demo_file_path, &table[..1000]).is_err(),
+            "Restoring a truncated table didn't return an error"
+        );
 
-    #[test]
-    #[serial(atad_device_path)]
-    fn only_one_instance_allowed() {
-        let demo_file_path = "hdd.img";
+        if let Err(message) = PS2HDD::restore_partition_table(demo_file_path, &table) {
+            panic!(message);
+        }
 
-        let instance_1 = PS2HDD::create(demo_file_path, DEMO_FILE_SIZE);
-        let instance_2 = PS2HDD::open(demo_file_path);
-        let instance_3 = PS2HDD::open(demo_file_path);
+        let ps2hdd = match PS2HDD::open(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
 
-        let instance = match instance_1 {
-            Ok(instance) => instance,
-            Err(message) => panic!(format!(
-                "First construction should've been okay, instead got {:?}",
-                message
-            )),
+        let partitions: Vec<String> = match ps2hdd.list_partitions() {
+            Ok(list) => list.into_iter().map(|partition| partition.name).collect(),
+            Err(message) => panic!(message),
         };
 
         assert_eq!(
-            instance_2.unwrap_err(),
-            "Only one PS2HDD instance may be mounted at a time",
-            "Second construction didn't return an error"
-        );
-        assert_eq!(
-            instance_3.unwrap_err(),
-            "Only one PS2HDD instance may be mounted at a time",
-            "Third construction didn't return an error"
+            partitions,
+            vec!["__mbr", "__net", "__system", "__sysconf", "__common"],
+            "unexpected partition list"
         );
 
-        drop(instance);
+        drop(ps2hdd);
 
-        let instance_4 = PS2HDD::open(demo_file_path);
+        // A disk too small to hold the partitions can't take the table
+        let small_file_path = "small.img";
+
+        std::fs::File::create(small_file_path)
+            .expect("couldn't create small file")
+            .set_len(128 * 1024 * 1024)
+            .expect("couldn't make small file the right size");
 
         assert!(
-            instance_4.is_ok(),
-            "Fourth construction should've been okay"
+            PS2HDD::restore_partition_table(small_file_path, &table).is_err(),
+            "Restoring to a small disk didn't return an error"
         );
 
+        std::fs::remove_file(small_file_path).expect("could not delete small file");
         std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
     #[test]
     #[serial(atad_device_path)]
-    fn initializes_disks_and_lists_partitions() {
+    fn detects_ps2_disks() {
         let demo_file_path = "hdd.img";
 
         let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
@@ -576,45 +6358,63 @@ mod tests {
             Err(message) => panic!(message),
         };
 
+        assert!(
+            !PS2HDD::is_ps2_disk(demo_file_path),
+            "Uninitialized disk detected as a PS2 disk"
+        );
+
         if let Err(message) = ps2hdd.initialize() {
             panic!(message);
         }
 
-        let partitions = match ps2hdd.list_partitions() {
-            Ok(list) => list,
+        drop(ps2hdd);
+
+        assert!(
+            PS2HDD::is_ps2_disk(demo_file_path),
+            "Initialized disk not detected as a PS2 disk"
+        );
+
+        assert!(
+            !PS2HDD::is_ps2_disk("nonexistent.img"),
+            "Missing file detected as a PS2 disk"
+        );
+
+        std::fs::remove_file(demo_file_path).expect("could not delete demo file");
+    }
+
+    #[test]
+    #[serial(atad_device_path)]
+    fn opens_with_timeout() {
+        let demo_file_path = "hdd.img";
+
+        let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
+            Ok(ps2hdd) => ps2hdd,
             Err(message) => panic!(message),
         };
 
+        drop(ps2hdd);
+
+        let ps2hdd =
+            match PS2HDD::open_with_timeout(demo_file_path, std::time::Duration::from_secs(30)) {
+                Ok(ps2hdd) => ps2hdd,
+                Err(message) => panic!(message),
+            };
+
+        assert!(
+            PS2HDD::open_with_timeout(demo_file_path, std::time::Duration::from_secs(30)).is_err(),
+            "opened a second instance"
+        );
+
+        drop(ps2hdd);
+
         assert_eq!(
-            partitions,
-            vec![
-                PartEntry {
-                    name: "__mbr".to_string(),
-                    kind: Some(PartitionKind::MBR),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__net".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__system".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__sysconf".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__common".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                }
-            ],
-            "unexpected partition list"
+            PS2HDD::open_with_timeout("nonexistent.img", std::time::Duration::from_secs(30))
+                .unwrap_err(),
+            Error::NotFound {
+                name: "nonexistent.img".to_string(),
+                kind: "file"
+            },
+            "unexpected error for nonexistent file"
         );
 
         std::fs::remove_file(demo_file_path).expect("could not delete demo file");
@@ -622,7 +6422,7 @@ mod tests {
 
     #[test]
     #[serial(atad_device_path)]
-    fn initializes_disks_creates_and_formats_partitions() {
+    fn opens_without_file_check() {
         let demo_file_path = "hdd.img";
 
         let ps2hdd = match PS2HDD::create(demo_file_path, DEMO_FILE_SIZE) {
@@ -634,54 +6434,60 @@ mod tests {
             panic!(message);
         }
 
-        if let Err(message) =
-            ps2hdd.create_partition("TESTPART", FormattablePartitionKind::PFS, 128)
-        {
-            panic!(message);
-        }
+        drop(ps2hdd);
 
-        let partitions = match ps2hdd.list_partitions() {
-            Ok(list) => list,
+        let ps2hdd = match PS2HDD::open_unchecked(demo_file_path) {
+            Ok(ps2hdd) => ps2hdd,
             Err(message) => panic!(message),
         };
 
+        assert!(
+            ps2hdd.list_partitions().is_ok(),
+            "Couldn't list partitions after unchecked open"
+        );
+
+        drop(ps2hdd);
+
         assert_eq!(
-            partitions,
-            vec![
-                PartEntry {
-                    name: "__mbr".to_string(),
-                    kind: Some(PartitionKind::MBR),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__net".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__system".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__sysconf".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "__common".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                },
-                PartEntry {
-                    name: "TESTPART".to_string(),
-                    kind: Some(PartitionKind::PFS),
-                    size: 128 * 1024 * 1024
-                }
-            ],
-            "unexpected partition list"
+            PS2HDD::open_unchecked("nonexistent.img").unwrap_err(),
+            Error::NotFound {
+                name: "nonexistent.img".to_string(),
+                kind: "file or device"
+            },
+            "Unchecked construction without file didn't return an error"
         );
 
+        // Only the checked open rejects paths which aren't regular files
+        let directory_path = "opens_without_file_check.dir";
+        std::fs::create_dir(directory_path).expect("could not create directory");
+
+        assert_eq!(
+            PS2HDD::open(directory_path).unwrap_err(),
+            Error::NotFound {
+                name: "opens_without_file_check.dir".to_string(),
+                kind: "file"
+            },
+            "Checked construction with a directory didn't return an error"
+        );
+
+        std::fs::remove_dir(directory_path).expect("could not delete directory");
+
+        let link_path = "opens_without_file_check.lnk";
+        std::os::unix::fs::symlink(demo_file_path, link_path).expect("could not create symlink");
+
+        let ps2hdd = match PS2HDD::open_unchecked(link_path) {
+            Ok(ps2hdd) => ps2hdd,
+            Err(message) => panic!(message),
+        };
+
+        assert!(
+            ps2hdd.list_partitions().is_ok(),
+            "Couldn't list partitions after unchecked open through a symlink"
+        );
+
+        drop(ps2hdd);
+
+        std::fs::remove_file(link_path).expect("could not delete symlink");
         std::fs::remove_file(demo_file_path).expect("could not delete demo file");
     }
 
@@ -699,7 +6505,11 @@ mod tests {
         };
 
         assert_eq!(
-            err_message, "nonexistent.img: No such file",
+            err_message,
+            Error::NotFound {
+                name: "nonexistent.img".to_string(),
+                kind: "file"
+            },
             "Construction without file didn't return an error"
         );
     }
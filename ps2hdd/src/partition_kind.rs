@@ -3,6 +3,8 @@
 
 use std::convert::TryFrom;
 
+use crate::error::Error;
+
 /// Pretty way of representing the kind of APA partition we're talking about.
 ///
 /// Can be turned into a [`FormattablePartitionKind`] by use of the `TryInto`
@@ -26,28 +28,37 @@ use std::convert::TryFrom;
 ///
 /// ```
 /// use ps2hdd::partition_kind::{PartitionKind, FormattablePartitionKind};
+/// use ps2hdd::Error;
 /// use std::convert::TryInto;
 ///
 /// let kind = PartitionKind::EXT2;
-/// let formattable_kind: Result<FormattablePartitionKind, String> = kind.try_into();
+/// let formattable_kind: Result<FormattablePartitionKind, Error> = kind.try_into();
 ///
-/// assert_eq!(formattable_kind, Err("Not a formattable partition kind".to_string()));
+/// assert_eq!(
+///     formattable_kind,
+///     Err(Error::Other("Not a formattable partition kind".to_string()))
+/// );
 /// ```
 ///
 /// [`FormattablePartitionKind`]: enum.FormattablePartitionKind.html
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PartitionKind {
     /// A "Master Boot Record" partition
-    MBR = 0x0001,
+    MBR,
     /// An EXT2-formatted swap partition, for use with PS2 Linux
-    EXT2Swap = 0x0082,
+    EXT2Swap,
     /// A general-use EXT2-formatted partition, for use with PS2 Linux
-    EXT2 = 0x0083,
+    EXT2,
+    /// A ReiserFS-formatted partition, for use with PS2 Linux
+    Reiser,
     /// A PFS partition, formatted for PlayStation®2-specific use
-    PFS = 0x0100,
-    CFS = 0x0101,
+    PFS,
+    CFS,
     /// An HDLoader partition, which technically means it follows ISO9660 rules
-    HDL = 0x1337,
+    HDL,
+    /// A partition whose type code isn't otherwise known, carrying the raw
+    /// type code
+    Unknown(u32),
 }
 
 /// The subset of APA partition types in [`PartitionKind`] for which drivers
@@ -66,20 +77,152 @@ pub enum PartitionKind {
 /// ```
 ///
 /// [`PartitionKind`]: enum.PartitionKind.html
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
 pub enum FormattablePartitionKind {
     /// A "Master Boot Record" partition.
     ///
     /// Note that when formatting, this type is treated the same as
     /// [`PFS`](#variant.PFS).
-    MBR = PartitionKind::MBR as isize,
+    MBR = 0x0001,
     /// A PFS partition, formatted for PlayStation®2-specific use
-    PFS = PartitionKind::PFS as isize,
+    PFS = 0x0100,
     /// An HDLoader partition, which technically means it follows ISO9660 rules
-    HDL = PartitionKind::HDL as isize,
+    HDL = 0x1337,
+}
+
+/// The file system actually found within a partition by
+/// [`PS2HDD::detect_filesystem`], which may not match the kind recorded in
+/// its APA header.
+///
+/// ```
+/// use ps2hdd::partition_kind::{DetectedFs, PartitionKind};
+///
+/// assert!(DetectedFs::PFS.matches(PartitionKind::PFS));
+/// assert!(!DetectedFs::Unformatted.matches(PartitionKind::PFS));
+/// ```
+///
+/// [`PS2HDD::detect_filesystem`]: ../struct.PS2HDD.html#method.detect_filesystem
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DetectedFs {
+    /// A PFS file system, identified by its superblock
+    PFS,
+    /// An HDLoader game, identified by its game information
+    HDL,
+    /// An EXT2 file system, identified by its superblock
+    EXT2,
+    /// None of the known file systems were found
+    Unformatted,
+}
+
+impl DetectedFs {
+    /// Returns `true` if this file system is what's expected within a
+    /// partition of the given `kind`.
+    pub fn matches(&self, kind: PartitionKind) -> bool {
+        match self {
+            Self::PFS => kind == PartitionKind::PFS || kind == PartitionKind::MBR,
+            Self::HDL => kind == PartitionKind::HDL,
+            Self::EXT2 => kind == PartitionKind::EXT2,
+            Self::Unformatted => false,
+        }
+    }
+}
+
+/// The flags field of an APA partition header, describing the role of the
+/// partition within the partition map.
+///
+/// Any bits which aren't known flags are preserved, and can be inspected via
+/// [`bits`](#method.bits).
+///
+/// ```
+/// use ps2hdd::partition_kind::PartitionFlags;
+///
+/// let flags = PartitionFlags::from_bits(0x0001);
+///
+/// assert!(flags.is_sub());
+/// assert!(flags.contains(PartitionFlags::SUB));
+/// assert_eq!(flags.unknown_bits(), 0);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PartitionFlags(u32);
+
+impl PartitionFlags {
+    /// The partition is a sub-partition, linked to from a main partition in
+    /// order to extend it beyond the maximum size of a single partition
+    pub const SUB: Self = Self(ps2hdd_sys::APA_FLAG_SUB);
+
+    /// Every flag bit whose meaning is known
+    const KNOWN: u32 = ps2hdd_sys::APA_FLAG_SUB;
+
+    /// Returns a set with no flags set.
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Creates a set of flags from the raw flags field of a partition header,
+    /// preserving any unknown bits.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw value of the flags, including any unknown bits.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns any bits which are set but aren't known flags.
+    pub fn unknown_bits(&self) -> u32 {
+        self.0 & !Self::KNOWN
+    }
+
+    /// Returns `true` if all of the flags in `other` are set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if this is a sub-partition of another partition.
+    pub fn is_sub(&self) -> bool {
+        self.contains(Self::SUB)
+    }
+
+    /// Returns `true` if this is a main partition, rather than a sub-partition.
+    pub fn is_main(&self) -> bool {
+        !self.is_sub()
+    }
 }
 
 impl PartitionKind {
+    /// Returns every known kind of partition, in order of their type codes.
+    ///
+    /// [`Unknown`](#variant.Unknown) is not included, as it stands in for
+    /// any type code which isn't otherwise known.
+    pub fn all() -> &'static [Self] {
+        &[
+            Self::MBR,
+            Self::EXT2Swap,
+            Self::EXT2,
+            Self::Reiser,
+            Self::PFS,
+            Self::CFS,
+            Self::HDL,
+        ]
+    }
+
+    /// Returns the numeric type code used for this kind of partition within
+    /// the APA partition map.
+    pub fn type_code(&self) -> u32 {
+        match self {
+            Self::MBR => 0x0001,
+            Self::EXT2Swap => 0x0082,
+            Self::EXT2 => 0x0083,
+            Self::Reiser => 0x0088,
+            Self::PFS => 0x0100,
+            Self::CFS => 0x0101,
+            Self::HDL => 0x1337,
+            Self::Unknown(code) => *code,
+        }
+    }
+
     /// Returns a string representing the internal APA name for the filesystem.
     ///
     /// For use when communicating directly with functions of ps2hdd-sys.
@@ -89,37 +232,54 @@ impl PartitionKind {
             Self::EXT2Swap => "EXT2SWAP",
             Self::EXT2 => "EXT2",
             Self::PFS => "PFS",
+            Self::Reiser => "REISER",
             Self::CFS => "CFS",
             Self::HDL => "HDL",
+            Self::Unknown(_) => "UNKNOWN",
         }
     }
 }
 
+impl FormattablePartitionKind {
+    /// Returns every kind of partition which can be formatted, in order of
+    /// their type codes.
+    pub fn all() -> &'static [Self] {
+        &[Self::MBR, Self::PFS, Self::HDL]
+    }
+}
+
 impl TryFrom<u32> for PartitionKind {
-    type Error = String;
+    type Error = Error;
 
+    /// Converts a partition type code into a `PartitionKind`.
+    ///
+    /// Any unrecognised type code is converted into
+    /// [`Unknown`](#variant.Unknown), except for `0x0000`, which denotes free
+    /// space rather than a partition.
     fn try_from(number: u32) -> std::result::Result<Self, Self::Error> {
         match number {
-            n if Self::MBR as u32 == n => Ok(Self::MBR),
-            n if Self::EXT2Swap as u32 == n => Ok(Self::EXT2Swap),
-            n if Self::EXT2 as u32 == n => Ok(Self::EXT2),
-            n if Self::PFS as u32 == n => Ok(Self::PFS),
-            n if Self::CFS as u32 == n => Ok(Self::CFS),
-            n if Self::HDL as u32 == n => Ok(Self::HDL),
-            _ => Err("Not a valid partition kind value".to_string()),
+            0x0000 => Err(Error::Other("Not a valid partition kind value".to_string())),
+            0x0001 => Ok(Self::MBR),
+            0x0082 => Ok(Self::EXT2Swap),
+            0x0083 => Ok(Self::EXT2),
+            0x0088 => Ok(Self::Reiser),
+            0x0100 => Ok(Self::PFS),
+            0x0101 => Ok(Self::CFS),
+            0x1337 => Ok(Self::HDL),
+            code => Ok(Self::Unknown(code)),
         }
     }
 }
 
 impl TryFrom<PartitionKind> for FormattablePartitionKind {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(kind: PartitionKind) -> std::result::Result<Self, Self::Error> {
         match kind {
             PartitionKind::MBR => Ok(Self::MBR),
             PartitionKind::PFS => Ok(Self::PFS),
             PartitionKind::HDL => Ok(Self::HDL),
-            _ => Err("Not a formattable partition kind".to_string()),
+            _ => Err(Error::Other("Not a formattable partition kind".to_string())),
         }
     }
 }
@@ -133,3 +293,86 @@ impl From<FormattablePartitionKind> for PartitionKind {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_codes_round_trip() {
+        for code in &[
+            0x0001, 0x0082, 0x0083, 0x0088, 0x0100, 0x0101, 0x1337, 0x4242,
+        ] {
+            let kind = PartitionKind::try_from(*code).expect("couldn't convert type code");
+            assert_eq!(kind.type_code(), *code);
+        }
+    }
+
+    #[test]
+    fn unknown_type_codes_are_preserved() {
+        assert_eq!(
+            PartitionKind::try_from(0x4242),
+            Ok(PartitionKind::Unknown(0x4242))
+        );
+
+        assert_eq!(
+            PartitionKind::try_from(0x0000),
+            Err(Error::Other("Not a valid partition kind value".to_string(),))
+        );
+    }
+
+    #[test]
+    fn all_kinds_are_listed() {
+        // Adding a variant without listing it in `all` should fail to compile
+        // here, rather than going unnoticed
+        let listed = |kind: PartitionKind| match kind {
+            PartitionKind::MBR
+            | PartitionKind::EXT2Swap
+            | PartitionKind::EXT2
+            | PartitionKind::Reiser
+            | PartitionKind::PFS
+            | PartitionKind::CFS
+            | PartitionKind::HDL => PartitionKind::all().contains(&kind),
+            PartitionKind::Unknown(_) => !PartitionKind::all().contains(&kind),
+        };
+
+        assert_eq!(PartitionKind::all().len(), 7);
+        assert!(PartitionKind::all().iter().copied().all(listed));
+        assert!(listed(PartitionKind::Unknown(0x4242)));
+
+        let formattable_listed = |kind: FormattablePartitionKind| match kind {
+            FormattablePartitionKind::MBR
+            | FormattablePartitionKind::PFS
+            | FormattablePartitionKind::HDL => FormattablePartitionKind::all().contains(&kind),
+        };
+
+        assert_eq!(FormattablePartitionKind::all().len(), 3);
+        assert!(FormattablePartitionKind::all()
+            .iter()
+            .copied()
+            .all(formattable_listed));
+
+        for kind in FormattablePartitionKind::all() {
+            assert!(PartitionKind::all().contains(&PartitionKind::from(*kind)));
+        }
+    }
+
+    #[test]
+    fn kinds_can_be_hashed() {
+        let kinds: std::collections::HashSet<PartitionKind> = [
+            PartitionKind::PFS,
+            PartitionKind::PFS,
+            PartitionKind::HDL,
+            PartitionKind::Unknown(0x4242),
+            PartitionKind::Unknown(0x4242),
+            PartitionKind::Unknown(0x4343),
+        ]
+        .iter()
+        .copied()
+        .collect();
+
+        assert_eq!(kinds.len(), 4);
+        assert!(kinds.contains(&PartitionKind::Unknown(0x4343)));
+        assert!(!kinds.contains(&PartitionKind::MBR));
+    }
+}
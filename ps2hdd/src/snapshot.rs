@@ -0,0 +1,206 @@
+//! In-memory records of a disk's partitions and files, which can be compared
+//! to find what changed between two disks, or two states of the same disk
+//!
+//! Only the layout and file metadata are recorded, not the contents of files.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::fs::{DateTime, FileType, PartEntry};
+
+/// The metadata of a single file or directory recorded in a [`Snapshot`].
+///
+/// [`Snapshot`]: struct.Snapshot.html
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FileSnapshot {
+    pub len: u64,
+    pub file_type: FileType,
+    pub modified: Option<DateTime>,
+}
+
+/// A record of a disk's partitions and, optionally, the files within its PFS
+/// partitions, as taken by [`PS2HDD::snapshot`].
+///
+/// [`PS2HDD::snapshot`]: ../struct.PS2HDD.html#method.snapshot
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    /// Each partition's entries, keyed by name; a partition's main entry is
+    /// followed by those of any sub-partitions
+    pub(crate) partitions: BTreeMap<String, Vec<PartEntry>>,
+    /// Each file's metadata, keyed by the name of the partition it's on and
+    /// its path within that partition
+    pub(crate) files: BTreeMap<(String, PathBuf), FileSnapshot>,
+}
+
+/// The differences between two [`Snapshot`]s, as returned by
+/// [`Snapshot::diff`].
+///
+/// Partitions are identified by name, and files by the name of the partition
+/// they're on and their path within it. Changed items are listed with their
+/// old state first.
+///
+/// [`Snapshot`]: struct.Snapshot.html
+/// [`Snapshot::diff`]: struct.Snapshot.html#method.diff
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added_partitions: Vec<String>,
+    pub removed_partitions: Vec<String>,
+    pub changed_partitions: Vec<(String, Vec<PartEntry>, Vec<PartEntry>)>,
+    pub added_files: Vec<(String, PathBuf)>,
+    pub removed_files: Vec<(String, PathBuf)>,
+    pub changed_files: Vec<((String, PathBuf), FileSnapshot, FileSnapshot)>,
+}
+
+impl SnapshotDiff {
+    /// Returns `true` if the two snapshots were the same.
+    pub fn is_empty(&self) -> bool {
+        self.added_partitions.is_empty()
+            && self.removed_partitions.is_empty()
+            && self.changed_partitions.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.changed_files.is_empty()
+    }
+}
+
+impl Snapshot {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the entries of the named partition, if it was present.
+    pub fn partition(&self, name: &str) -> Option<&[PartEntry]> {
+        self.partitions.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the recorded metadata of a file, if it was present.
+    pub fn file(&self, partition_name: &str, path: &std::path::Path) -> Option<&FileSnapshot> {
+        self.files
+            .get(&(partition_name.to_string(), path.to_path_buf()))
+    }
+
+    /// Finds the partitions and files which were added, removed or changed
+    /// between this snapshot and a later one.
+    ///
+    /// If neither snapshot recorded files, no file differences are reported.
+    pub fn diff(&self, later: &Snapshot) -> SnapshotDiff {
+        let (added_partitions, removed_partitions, changed_partitions) =
+            diff_maps(&self.partitions, &later.partitions);
+        let (added_files, removed_files, changed_files) = diff_maps(&self.files, &later.files);
+
+        SnapshotDiff {
+            added_partitions,
+            removed_partitions,
+            changed_partitions,
+            added_files,
+            removed_files,
+            changed_files,
+        }
+    }
+}
+
+/// The keys only present in the later map, those only present in the earlier
+/// map, and those whose values changed, along with their values
+type MapDiff<K, V> = (Vec<K>, Vec<K>, Vec<(K, V, V)>);
+
+fn diff_maps<K: Ord + Clone, V: PartialEq + Clone>(
+    earlier: &BTreeMap<K, V>,
+    later: &BTreeMap<K, V>,
+) -> MapDiff<K, V> {
+    let added = later
+        .keys()
+        .filter(|key| !earlier.contains_key(key))
+        .cloned()
+        .collect();
+
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+
+    for (key, value) in earlier {
+        match later.get(key) {
+            None => removed.push(key.clone()),
+            Some(later_value) if later_value != value => {
+                changed.push((key.clone(), value.clone(), later_value.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    (added, removed, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partition_kind::{PartitionFlags, PartitionKind};
+
+    fn make_partition(name: &str, size: u64) -> PartEntry {
+        PartEntry {
+            name: name.to_string(),
+            kind: Some(PartitionKind::PFS),
+            flags: PartitionFlags::empty(),
+            size,
+            start_sector: 0,
+        }
+    }
+
+    fn make_file(len: u64) -> FileSnapshot {
+        FileSnapshot {
+            len,
+            file_type: FileType::from_mode(ps2hdd_sys::FIO_S_IFREG | 0o644),
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn diffs_snapshots() {
+        let mut before = Snapshot::new();
+        let mut after = Snapshot::new();
+
+        for name in &["KEPT", "REMOVED", "RESIZED"] {
+            before
+                .partitions
+                .insert(name.to_string(), vec![make_partition(name, 128)]);
+        }
+
+        for name in &["KEPT", "ADDED"] {
+            after
+                .partitions
+                .insert(name.to_string(), vec![make_partition(name, 128)]);
+        }
+
+        after
+            .partitions
+            .insert("RESIZED".to_string(), vec![make_partition("RESIZED", 256)]);
+
+        let key = |path: &str| ("KEPT".to_string(), PathBuf::from(path));
+
+        before.files.insert(key("/same.txt"), make_file(1));
+        before.files.insert(key("/old.txt"), make_file(1));
+        before.files.insert(key("/grown.txt"), make_file(1));
+        after.files.insert(key("/same.txt"), make_file(1));
+        after.files.insert(key("/new.txt"), make_file(1));
+        after.files.insert(key("/grown.txt"), make_file(2));
+
+        let diff = before.diff(&after);
+
+        assert_eq!(diff.added_partitions, vec!["ADDED".to_string()]);
+        assert_eq!(diff.removed_partitions, vec!["REMOVED".to_string()]);
+        assert_eq!(
+            diff.changed_partitions,
+            vec![(
+                "RESIZED".to_string(),
+                vec![make_partition("RESIZED", 128)],
+                vec![make_partition("RESIZED", 256)]
+            )]
+        );
+        assert_eq!(diff.added_files, vec![key("/new.txt")]);
+        assert_eq!(diff.removed_files, vec![key("/old.txt")]);
+        assert_eq!(
+            diff.changed_files,
+            vec![(key("/grown.txt"), make_file(1), make_file(2))]
+        );
+
+        assert!(before.diff(&before).is_empty());
+    }
+}